@@ -0,0 +1,277 @@
+use std::io::Read;
+
+use num_bigint::BigUint;
+
+use crate::cripto::hash256;
+use crate::helper::{bits_to_target, encode_varint, merkle_root, read_varint};
+use crate::tx::Tx;
+
+pub type BlockHash = [u8; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockHeader {
+    version: u32,
+    prev_block: BlockHash,
+    merkle_root: [u8; 32],
+    timestamp: u32,
+    bits: u32,
+    nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn new(version: u32, prev_block: BlockHash, merkle_root: [u8; 32], timestamp: u32, bits: u32, nonce: u32) -> Self {
+        BlockHeader { version, prev_block, merkle_root, timestamp, bits, nonce }
+    }
+
+    // prev_block and merkle_root are kept in the byte order they are displayed in, the
+    // serialization reverses them to little endian
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let mut buf = [0u8; 80];
+        reader.read_exact(&mut buf).map_err(|_| "not enough bytes for a block header")?;
+
+        let mut prev_block = [0u8; 32];
+        prev_block.copy_from_slice(&buf[4..36]);
+        prev_block.reverse();
+        let mut merkle_root = [0u8; 32];
+        merkle_root.copy_from_slice(&buf[36..68]);
+        merkle_root.reverse();
+
+        Ok(BlockHeader {
+            version: u32::from_le_bytes(buf[0..4].try_into().unwrap()),
+            prev_block,
+            merkle_root,
+            timestamp: u32::from_le_bytes(buf[68..72].try_into().unwrap()),
+            bits: u32::from_le_bytes(buf[72..76].try_into().unwrap()),
+            nonce: u32::from_le_bytes(buf[76..80].try_into().unwrap()),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = Vec::with_capacity(80);
+        result.extend_from_slice(&self.version.to_le_bytes());
+        result.extend(self.prev_block.iter().rev());
+        result.extend(self.merkle_root.iter().rev());
+        result.extend_from_slice(&self.timestamp.to_le_bytes());
+        result.extend_from_slice(&self.bits.to_le_bytes());
+        result.extend_from_slice(&self.nonce.to_le_bytes());
+        result
+    }
+
+    pub fn hash(&self) -> BlockHash {
+        let mut hash: BlockHash = hash256(&self.serialize()).try_into().unwrap();
+        hash.reverse();
+        hash
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn prev_block(&self) -> &BlockHash {
+        &self.prev_block
+    }
+
+    pub fn merkle_root(&self) -> &[u8; 32] {
+        &self.merkle_root
+    }
+
+    pub fn timestamp(&self) -> u32 {
+        self.timestamp
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn nonce(&self) -> u32 {
+        self.nonce
+    }
+
+    pub fn target(&self) -> Result<BigUint, &'static str> {
+        bits_to_target(self.bits)
+    }
+
+    pub fn difficulty(&self) -> Result<f64, &'static str> {
+        let target = self.target()?;
+        if target == BigUint::ZERO {
+            return Err("target is zero");
+        }
+        let lowest = BigUint::from(0xffffu32) << (8 * (0x1d - 3));
+        Ok(biguint_to_f64(&lowest) / biguint_to_f64(&target))
+    }
+
+    // expected number of hashes needed to find a header with this target, 2^256 / (target + 1)
+    pub fn work(&self) -> BigUint {
+        match self.target() {
+            Ok(target) if target != BigUint::ZERO => (BigUint::from(1u32) << 256u32) / (target + 1u32),
+            _ => BigUint::ZERO,
+        }
+    }
+
+    pub fn check_pow(&self, pow_limit: &BigUint) -> bool {
+        let target = match self.target() {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
+        if target == BigUint::ZERO || &target > pow_limit {
+            return false;
+        }
+        BigUint::from_bytes_be(&self.hash()) <= target
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    header: BlockHeader,
+    txs: Vec<Tx>,
+}
+
+impl Block {
+    pub fn new(header: BlockHeader, txs: Vec<Tx>) -> Self {
+        Block { header, txs }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let header = BlockHeader::parse(reader)?;
+        let num_txs = read_varint(reader)?;
+        let mut txs = Vec::new();
+        for _ in 0..num_txs {
+            txs.push(Tx::parse(reader)?);
+        }
+        Ok(Block { header, txs })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.header.serialize();
+        result.extend(encode_varint(self.txs.len() as u64));
+        for tx in &self.txs {
+            result.extend(tx.serialize());
+        }
+        result
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn txs(&self) -> &[Tx] {
+        &self.txs
+    }
+
+    // the root of the tree of txids, returned in display order like the header's merkle_root
+    pub fn compute_merkle_root(&self) -> Result<[u8; 32], &'static str> {
+        let hashes: Vec<[u8; 32]> = self.txs.iter().map(|tx| reversed(tx.id())).collect();
+        Ok(reversed(merkle_root(&hashes)?))
+    }
+
+    pub fn check_merkle_root(&self) -> bool {
+        self.compute_merkle_root().is_ok_and(|root| root == self.header.merkle_root)
+    }
+}
+
+fn reversed(mut hash: [u8; 32]) -> [u8; 32] {
+    hash.reverse();
+    hash
+}
+
+fn biguint_to_f64(num: &BigUint) -> f64 {
+    num.to_u64_digits().iter().rev().fold(0f64, |acc, digit| acc * 2f64.powi(64) + *digit as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::network::Network;
+
+    const GENESIS: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+    const BLOCK_471744: &str = "000000203471101bbda3fe307664b3283a9ef0e97d9a38a7eacd8800000000000000000010c8aba8479bbaa5e0848152fd3c2289ca50e1c3e58c9a4faaafbdf5803c5448ddb845597e8b0118e43a81d3";
+
+    fn parse_hex(header: &str) -> BlockHeader {
+        BlockHeader::parse(&mut Cursor::new(hex::decode(header).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let header = parse_hex(GENESIS);
+        assert_eq!(header.version(), 1);
+        assert_eq!(*header.prev_block(), [0u8; 32]);
+        assert_eq!(
+            hex::encode(header.merkle_root()),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(header.timestamp(), 1231006505);
+        assert_eq!(header.bits(), 0x1d00ffff);
+        assert_eq!(header.nonce(), 2083236893);
+    }
+
+    #[test]
+    fn test_parse_short_input() {
+        let bytes = hex::decode(&GENESIS[..100]).unwrap();
+        assert!(BlockHeader::parse(&mut Cursor::new(bytes)).is_err());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let header = parse_hex(BLOCK_471744);
+        assert_eq!(hex::encode(header.serialize()), BLOCK_471744);
+    }
+
+    #[test]
+    fn test_hash() {
+        let header = parse_hex(GENESIS);
+        assert_eq!(
+            hex::encode(header.hash()),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let header = parse_hex(GENESIS);
+        assert_eq!(header.difficulty().unwrap(), 1.0);
+        let header = parse_hex(BLOCK_471744);
+        assert_eq!(header.difficulty().unwrap().floor(), 711697198173.0);
+    }
+
+    #[test]
+    fn test_parse_block() {
+        let genesis_block = format!("{}01{}", GENESIS, GENESIS_COINBASE);
+        let block = Block::parse(&mut Cursor::new(hex::decode(&genesis_block).unwrap())).unwrap();
+        assert_eq!(*block.header(), parse_hex(GENESIS));
+        assert_eq!(block.txs().len(), 1);
+        assert_eq!(block.txs()[0].id(), *block.header().merkle_root());
+        assert_eq!(hex::encode(block.serialize()), genesis_block);
+        assert!(block.check_merkle_root());
+
+        let empty = Block::new(block.header().clone(), Vec::new());
+        assert!(!empty.check_merkle_root());
+    }
+
+    #[test]
+    fn test_work() {
+        let header = parse_hex(GENESIS);
+        assert_eq!(header.work(), BigUint::from(0x100010001u64));
+
+        let mut header = parse_hex(GENESIS);
+        header.bits = 0x01fedcba;
+        assert_eq!(header.work(), BigUint::ZERO);
+    }
+
+    #[test]
+    fn test_check_pow() {
+        let pow_limit = Network::Mainnet.pow_limit();
+        assert!(parse_hex(GENESIS).check_pow(pow_limit));
+        assert!(parse_hex(BLOCK_471744).check_pow(pow_limit));
+
+        let mut header = parse_hex(BLOCK_471744);
+        header.nonce += 1;
+        assert!(!header.check_pow(pow_limit));
+
+        let mut header = parse_hex(GENESIS);
+        header.bits = 0x1e00ffff;
+        assert!(!header.check_pow(pow_limit));
+    }
+}
@@ -0,0 +1,466 @@
+use std::io::Read;
+
+use crate::block::{Block, BlockHash};
+use crate::cripto::{hash256, siphash24};
+use crate::header_chain::HeaderChain;
+use crate::helper::{encode_var_bytes, encode_varint, read_bytes, read_var_bytes, read_varint};
+use crate::network::CFHeadersMessage;
+use crate::tx::TxOut;
+
+pub const BASIC_FILTER_TYPE: u8 = 0;
+
+// Golomb-Rice parameters of the basic filter: remainders are P bits and items are mapped into
+// a range of N * M, for a false positive rate of about 1/M
+pub const FILTER_P: u8 = 19;
+pub const FILTER_M: u64 = 784931;
+
+const OP_RETURN: u8 = 0x6a;
+
+pub type FilterHash = [u8; 32];
+pub type FilterHeader = [u8; 32];
+
+// A BIP 158 basic filter: a Golomb-coded set of every script_pubkey a block creates or spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockFilter {
+    block_hash: BlockHash,
+    content: Vec<u8>,
+}
+
+impl BlockFilter {
+    pub fn new(block_hash: BlockHash, content: Vec<u8>) -> Self {
+        BlockFilter { block_hash, content }
+    }
+
+    // `prevouts` are the outputs spent by the block's inputs, in block order, skipping the
+    // coinbase. Empty and OP_RETURN output scripts are left out.
+    pub fn from_block(block: &Block, prevouts: &[TxOut]) -> Result<Self, &'static str> {
+        let spent = block.txs().iter().filter(|tx| !tx.is_coinbase()).map(|tx| tx.inputs().len()).sum::<usize>();
+        if spent != prevouts.len() {
+            return Err("prevouts do not match the block's inputs");
+        }
+
+        let mut items: Vec<&[u8]> = block
+            .txs()
+            .iter()
+            .flat_map(|tx| tx.outputs())
+            .map(|output| output.script_pubkey())
+            .filter(|script| !script.is_empty() && script[0] != OP_RETURN)
+            .collect();
+        items.extend(prevouts.iter().map(|output| output.script_pubkey()).filter(|script| !script.is_empty()));
+        items.sort();
+        items.dedup();
+
+        let block_hash = block.header().hash();
+        let mut values = hashed_set(&block_hash, &items);
+        values.sort();
+
+        let mut writer = BitWriter::default();
+        let mut last = 0;
+        for value in values {
+            golomb_encode(&mut writer, value - last);
+            last = value;
+        }
+        let mut content = encode_varint(items.len() as u64);
+        content.extend(writer.bytes);
+        Ok(BlockFilter { block_hash, content })
+    }
+
+    // the payload of a cfilter message
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        if read_bytes::<R, 1>(reader)?[0] != BASIC_FILTER_TYPE {
+            return Err("unsupported filter type");
+        }
+        let mut block_hash: BlockHash = read_bytes(reader)?;
+        block_hash.reverse();
+        let content = read_var_bytes(reader)?;
+        Ok(BlockFilter { block_hash, content })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![BASIC_FILTER_TYPE];
+        result.extend(self.block_hash.iter().rev());
+        result.extend(encode_var_bytes(&self.content));
+        result
+    }
+
+    pub fn match_script(&self, script: &[u8]) -> Result<bool, &'static str> {
+        self.match_any(&[script])
+    }
+
+    // Hashes the queried scripts into the filter's range and walks both sorted sets at once,
+    // so the filter is decoded a single time however many scripts the wallet watches.
+    pub fn match_any(&self, scripts: &[&[u8]]) -> Result<bool, &'static str> {
+        let mut content = self.content.as_slice();
+        let count = read_varint(&mut content)?;
+        if count == 0 || scripts.is_empty() {
+            return Ok(false);
+        }
+        if count > u32::MAX as u64 {
+            return Err("too many items in filter");
+        }
+
+        let mut queries = hashed_range(&self.block_hash, scripts, count);
+        queries.sort();
+        let mut queries = queries.into_iter().peekable();
+
+        let mut reader = BitReader::new(content);
+        let mut value = 0;
+        for _ in 0..count {
+            value += golomb_decode(&mut reader)?;
+            while let Some(query) = queries.peek() {
+                if *query == value {
+                    return Ok(true);
+                }
+                if *query > value {
+                    break;
+                }
+                queries.next();
+            }
+            if queries.peek().is_none() {
+                return Ok(false);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn filter_hash(&self) -> FilterHash {
+        let mut hash: FilterHash = hash256(&self.content).try_into().unwrap();
+        hash.reverse();
+        hash
+    }
+
+    // commits to this filter and, through `prev_header`, to every filter before it
+    pub fn filter_header(&self, prev_header: &FilterHeader) -> FilterHeader {
+        next_filter_header(&self.filter_hash(), prev_header)
+    }
+
+    pub fn block_hash(&self) -> &BlockHash {
+        &self.block_hash
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+pub fn next_filter_header(filter_hash: &FilterHash, prev_header: &FilterHeader) -> FilterHeader {
+    let mut data: Vec<u8> = filter_hash.iter().rev().copied().collect();
+    data.extend(prev_header.iter().rev());
+    let mut header: FilterHeader = hash256(&data).try_into().unwrap();
+    header.reverse();
+    header
+}
+
+// The filter headers of the active chain, indexed by height. They are what a light client
+// checks filters against, ideally after comparing them across several peers.
+#[derive(Debug, Clone, Default)]
+pub struct FilterHeaderChain {
+    headers: Vec<FilterHeader>,
+}
+
+impl FilterHeaderChain {
+    pub fn new() -> Self {
+        FilterHeaderChain { headers: Vec::new() }
+    }
+
+    // number of filter headers we have, which is also the height of the next one
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    pub fn header_at(&self, height: u32) -> Option<&FilterHeader> {
+        self.headers.get(height as usize)
+    }
+
+    pub fn tip(&self) -> Option<&FilterHeader> {
+        self.headers.last()
+    }
+
+    // Appends the headers of a cfheaders response. Its last filter belongs to the block at
+    // stop_hash, which has to be on the active chain, and its previous header has to be our
+    // tip so the new headers connect. Returns how many headers were added.
+    pub fn accept_cfheaders(&mut self, message: &CFHeadersMessage, chain: &HeaderChain) -> Result<usize, &'static str> {
+        if message.filter_type() != BASIC_FILTER_TYPE {
+            return Err("unsupported filter type");
+        }
+        if !chain.is_active(message.stop_hash()) {
+            return Err("stop hash is not on the active chain");
+        }
+        let stop_height = chain.height_of(message.stop_hash()).ok_or("stop hash is not on the active chain")? as usize;
+        let count = message.filter_hashes().len();
+        if count == 0 || count > stop_height + 1 || stop_height + 1 - count != self.headers.len() {
+            return Err("filter headers do not start at our tip");
+        }
+        if message.prev_filter_header() != self.tip().unwrap_or(&[0u8; 32]) {
+            return Err("previous filter header does not match our tip");
+        }
+
+        let mut prev_header = *message.prev_filter_header();
+        for filter_hash in message.filter_hashes() {
+            prev_header = next_filter_header(filter_hash, &prev_header);
+            self.headers.push(prev_header);
+        }
+        Ok(count)
+    }
+
+    // Checks a filter against the header committed to for its block, returning the height
+    // of that block.
+    pub fn check_filter(&self, filter: &BlockFilter, chain: &HeaderChain) -> Result<u32, &'static str> {
+        if !chain.is_active(filter.block_hash()) {
+            return Err("filter is not for a block on the active chain");
+        }
+        let height = chain.height_of(filter.block_hash()).ok_or("filter is not for a block on the active chain")?;
+        let header = self.header_at(height).ok_or("missing filter header")?;
+        let prev_header = match height {
+            0 => [0u8; 32],
+            _ => self.headers[height as usize - 1],
+        };
+        if filter.filter_header(&prev_header) != *header {
+            return Err("filter does not match its filter header");
+        }
+        Ok(height)
+    }
+}
+
+// The siphash key is the first 16 bytes of the block hash, as serialized
+fn filter_key(block_hash: &BlockHash) -> (u64, u64) {
+    let serialized: Vec<u8> = block_hash.iter().rev().copied().collect();
+    let k0 = u64::from_le_bytes(serialized[0..8].try_into().unwrap());
+    let k1 = u64::from_le_bytes(serialized[8..16].try_into().unwrap());
+    (k0, k1)
+}
+
+fn hashed_set(block_hash: &BlockHash, items: &[&[u8]]) -> Vec<u64> {
+    hashed_range(block_hash, items, items.len() as u64)
+}
+
+// maps each item uniformly into [0, count * M) with a multiply and shift instead of a modulo
+fn hashed_range(block_hash: &BlockHash, items: &[&[u8]], count: u64) -> Vec<u64> {
+    let (k0, k1) = filter_key(block_hash);
+    let range = count * FILTER_M;
+    items
+        .iter()
+        .map(|item| ((siphash24(k0, k1, item) as u128 * range as u128) >> 64) as u64)
+        .collect()
+}
+
+fn golomb_encode(writer: &mut BitWriter, value: u64) {
+    for _ in 0..(value >> FILTER_P) {
+        writer.write_bit(true);
+    }
+    writer.write_bit(false);
+    writer.write_bits(value, FILTER_P);
+}
+
+fn golomb_decode(reader: &mut BitReader) -> Result<u64, &'static str> {
+    let mut quotient = 0u64;
+    while reader.read_bit()? {
+        quotient += 1;
+    }
+    let remainder = reader.read_bits(FILTER_P)?;
+    Ok((quotient << FILTER_P) + remainder)
+}
+
+// bits are packed most significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u8,
+}
+
+impl BitWriter {
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    fn write_bits(&mut self, value: u64, count: u8) {
+        for i in (0..count).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, position: 0 }
+    }
+
+    fn read_bit(&mut self) -> Result<bool, &'static str> {
+        let byte = self.bytes.get(self.position / 8).ok_or("unexpected end of filter")?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Ok(bit)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u64, &'static str> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::block::BlockHeader;
+    use crate::network::Network;
+    use crate::test_helpers;
+    use crate::tx::{Tx, TxIn};
+
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    fn genesis_block(network: Network) -> Block {
+        let coinbase = Tx::parse(&mut Cursor::new(hex::decode(GENESIS_COINBASE).unwrap())).unwrap();
+        Block::new(network.genesis_header(), vec![coinbase])
+    }
+
+    fn script(n: u8) -> Vec<u8> {
+        let mut script = vec![0x00, 0x14];
+        script.extend([n; 20]);
+        script
+    }
+
+    // a regtest block on top of `prev` paying to script(1..=outputs) and spending one input
+    fn mine_block(prev: &BlockHeader, outputs: u8) -> Block {
+        let spend = Tx::new(
+            2,
+            vec![TxIn::new([outputs; 32], 0, Vec::new(), 0xffffffff)],
+            (1..=outputs).map(|n| TxOut::new(1000, script(n))).chain([TxOut::new(0, vec![OP_RETURN, 0x01, 0xff])]).collect(),
+            0,
+        );
+        test_helpers::mine_block(prev, vec![spend])
+    }
+
+    #[test]
+    fn test_genesis_filter() {
+        // from the BIP 158 test vectors
+        let filter = BlockFilter::from_block(&genesis_block(Network::Testnet), &[]).unwrap();
+        assert_eq!(hex::encode(filter.content()), "019dfca8");
+        assert_eq!(
+            hex::encode(filter.filter_header(&[0u8; 32])),
+            "21584579b7eb08997773e5aeff3a7f932700042d0ed2a6129012b7d7ae81b750"
+        );
+
+        let script_pubkey = genesis_block(Network::Testnet).txs()[0].outputs()[0].script_pubkey().to_vec();
+        assert!(filter.match_script(&script_pubkey).unwrap());
+        assert!(!filter.match_script(&script(1)).unwrap());
+    }
+
+    #[test]
+    fn test_from_block() {
+        let block = mine_block(&Network::Regtest.genesis_header(), 20);
+        let spent = TxOut::new(5000, script(100));
+        let filter = BlockFilter::from_block(&block, &[spent]).unwrap();
+        assert_eq!(filter.block_hash(), &block.header().hash());
+
+        // 20 outputs and the spent output, the OP_RETURN output is left out
+        assert_eq!(read_varint(&mut filter.content()).unwrap(), 21);
+        for n in 1..=20 {
+            assert!(filter.match_script(&script(n)).unwrap());
+        }
+        assert!(filter.match_script(&script(100)).unwrap());
+        assert!(!filter.match_script(&[OP_RETURN, 0x01, 0xff]).unwrap());
+        assert!(!filter.match_any(&[&script(21), &script(99)]).unwrap());
+        assert!(filter.match_any(&[&script(21), &script(99), &script(7)]).unwrap());
+        assert!(!filter.match_any(&[]).unwrap());
+
+        assert!(BlockFilter::from_block(&block, &[]).is_err());
+    }
+
+    #[test]
+    fn test_duplicate_scripts() {
+        let block = mine_block(&Network::Regtest.genesis_header(), 3);
+        let filter = BlockFilter::from_block(&block, &[TxOut::new(1000, script(2))]).unwrap();
+        assert_eq!(read_varint(&mut filter.content()).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_empty_filter() {
+        let block = mine_block(&Network::Regtest.genesis_header(), 0);
+        let filter = BlockFilter::from_block(&block, &[TxOut::new(1000, Vec::new())]).unwrap();
+        assert_eq!(filter.content(), &[0]);
+        assert!(!filter.match_script(&script(1)).unwrap());
+    }
+
+    #[test]
+    fn test_truncated_filter() {
+        let block = mine_block(&Network::Regtest.genesis_header(), 20);
+        let filter = BlockFilter::from_block(&block, &[TxOut::new(1000, script(100))]).unwrap();
+        let content = filter.content();
+        let truncated = BlockFilter::new(*filter.block_hash(), content[..content.len() / 2].to_vec());
+        assert!(truncated.match_script(&script(200)).is_err());
+    }
+
+    #[test]
+    fn test_parse() {
+        let filter = BlockFilter::from_block(&genesis_block(Network::Testnet), &[]).unwrap();
+        let serialized = filter.serialize();
+        assert_eq!(serialized[0], BASIC_FILTER_TYPE);
+        assert_eq!(BlockFilter::parse(&mut Cursor::new(&serialized)).unwrap(), filter);
+
+        let mut other_type = serialized.clone();
+        other_type[0] = 1;
+        assert!(BlockFilter::parse(&mut Cursor::new(&other_type)).is_err());
+    }
+
+    #[test]
+    fn test_filter_header_chain() {
+        let network = Network::Regtest;
+        let mut chain = HeaderChain::new(network);
+        let mut blocks = vec![genesis_block(network)];
+        for n in 1..=5 {
+            let block = mine_block(blocks.last().unwrap().header(), n);
+            chain.accept(block.header().clone()).unwrap();
+            blocks.push(block);
+        }
+        let filters: Vec<BlockFilter> = blocks
+            .iter()
+            .map(|block| {
+                let prevouts = if block.txs()[0].is_coinbase() { Vec::new() } else { vec![TxOut::new(1000, script(50))] };
+                BlockFilter::from_block(block, &prevouts).unwrap()
+            })
+            .collect();
+        let hashes: Vec<FilterHash> = filters.iter().map(|filter| filter.filter_hash()).collect();
+
+        let mut filter_headers = FilterHeaderChain::new();
+        let first = CFHeadersMessage::new(blocks[2].header().hash(), [0u8; 32], hashes[..3].to_vec());
+        assert_eq!(filter_headers.accept_cfheaders(&first, &chain), Ok(3));
+
+        // a response that does not connect to our tip
+        let gap = CFHeadersMessage::new(blocks[5].header().hash(), *filter_headers.tip().unwrap(), hashes[4..].to_vec());
+        assert!(filter_headers.accept_cfheaders(&gap, &chain).is_err());
+        let wrong_prev = CFHeadersMessage::new(blocks[5].header().hash(), [1u8; 32], hashes[3..].to_vec());
+        assert!(filter_headers.accept_cfheaders(&wrong_prev, &chain).is_err());
+
+        let rest = CFHeadersMessage::new(blocks[5].header().hash(), *filter_headers.tip().unwrap(), hashes[3..].to_vec());
+        assert_eq!(filter_headers.accept_cfheaders(&rest, &chain), Ok(3));
+        assert_eq!(filter_headers.len(), 6);
+
+        for (height, filter) in filters.iter().enumerate() {
+            assert_eq!(filter_headers.check_filter(filter, &chain), Ok(height as u32));
+        }
+
+        // a filter that leaves out a script the block pays to
+        let forged = BlockFilter::from_block(&blocks[3], &[TxOut::new(1000, Vec::new())]).unwrap();
+        assert!(filter_headers.check_filter(&forged, &chain).is_err());
+    }
+}
@@ -0,0 +1,181 @@
+use std::f64::consts::LN_2;
+use std::io::Read;
+
+use crate::helper::{encode_var_bytes, murmur3, read_bytes, read_var_bytes};
+
+pub const MAX_BLOOM_FILTER_SIZE: usize = 36000;
+pub const MAX_HASH_FUNCS: u32 = 50;
+pub const MAX_FILTER_ADD_SIZE: usize = 520;
+
+// what a full node does with the filter when an output matches: nothing, always add the
+// outpoint, or only add it for pay-to-pubkey and bare multisig outputs
+pub const BLOOM_UPDATE_NONE: u8 = 0;
+pub const BLOOM_UPDATE_ALL: u8 = 1;
+pub const BLOOM_UPDATE_P2PUBKEY_ONLY: u8 = 2;
+
+const BIP37_CONSTANT: u32 = 0xfba4c795;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bit_field: Vec<u8>,
+    function_count: u32,
+    tweak: u32,
+    flags: u8,
+}
+
+impl BloomFilter {
+    // size is in bytes
+    pub fn new(size: usize, function_count: u32, tweak: u32) -> Self {
+        BloomFilter { bit_field: vec![0u8; size], function_count, tweak, flags: BLOOM_UPDATE_NONE }
+    }
+
+    // Sizes the filter for `elements` items at the given false positive rate, with the same
+    // formulas as Bitcoin Core, capped at the limits full nodes accept.
+    pub fn for_elements(elements: usize, fp_rate: f64, tweak: u32) -> Self {
+        let bits = -1.0 / (LN_2 * LN_2) * elements as f64 * fp_rate.ln();
+        let size = (bits as usize).min(MAX_BLOOM_FILTER_SIZE * 8) / 8;
+        let function_count = ((size * 8) as f64 / elements as f64 * LN_2) as u32;
+        BloomFilter::new(size, function_count.min(MAX_HASH_FUNCS), tweak)
+    }
+
+    pub fn with_flags(mut self, flags: u8) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn add(&mut self, item: &[u8]) {
+        // an empty filter would divide by zero, it matches everything instead
+        if self.bit_field.is_empty() {
+            return;
+        }
+        for i in 0..self.function_count {
+            let bit = self.bit_index(i, item);
+            self.bit_field[bit / 8] |= 1 << (bit % 8);
+        }
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        if self.bit_field.is_empty() {
+            return true;
+        }
+        (0..self.function_count).all(|i| {
+            let bit = self.bit_index(i, item);
+            self.bit_field[bit / 8] & (1 << (bit % 8)) != 0
+        })
+    }
+
+    fn bit_index(&self, function: u32, item: &[u8]) -> usize {
+        let seed = function.wrapping_mul(BIP37_CONSTANT).wrapping_add(self.tweak);
+        murmur3(item, seed) as usize % (self.bit_field.len() * 8)
+    }
+
+    // the payload of a filterload message
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let bit_field = read_var_bytes(reader)?;
+        if bit_field.len() > MAX_BLOOM_FILTER_SIZE {
+            return Err("bloom filter is too large");
+        }
+        let function_count = u32::from_le_bytes(read_bytes(reader)?);
+        if function_count > MAX_HASH_FUNCS {
+            return Err("bloom filter has too many hash functions");
+        }
+        let tweak = u32::from_le_bytes(read_bytes(reader)?);
+        let flags = read_bytes::<R, 1>(reader)?[0];
+        Ok(BloomFilter { bit_field, function_count, tweak, flags })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = encode_var_bytes(&self.bit_field);
+        result.extend_from_slice(&self.function_count.to_le_bytes());
+        result.extend_from_slice(&self.tweak.to_le_bytes());
+        result.push(self.flags);
+        result
+    }
+
+    pub fn bit_field(&self) -> &[u8] {
+        &self.bit_field
+    }
+
+    pub fn function_count(&self) -> u32 {
+        self.function_count
+    }
+
+    pub fn tweak(&self) -> u32 {
+        self.tweak
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_add() {
+        let mut filter = BloomFilter::new(10, 5, 99);
+        filter.add(b"Hello World");
+        assert_eq!(hex::encode(filter.bit_field()), "0000000a080000000140");
+        filter.add(b"Goodbye!");
+        assert_eq!(hex::encode(filter.bit_field()), "4000600a080000010940");
+        assert!(filter.contains(b"Hello World"));
+        assert!(filter.contains(b"Goodbye!"));
+        assert!(!filter.contains(b"Hello World!"));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let mut filter = BloomFilter::new(10, 5, 99).with_flags(BLOOM_UPDATE_ALL);
+        filter.add(b"Hello World");
+        filter.add(b"Goodbye!");
+        assert_eq!(hex::encode(filter.serialize()), "0a4000600a080000010940050000006300000001");
+        assert_eq!(BloomFilter::parse(&mut Cursor::new(filter.serialize())).unwrap(), filter);
+    }
+
+    #[test]
+    fn test_for_elements() {
+        for (tweak, expected) in [(0, "03614e9b050000000000000001"), (2147483649, "03ce4299050000000100008001")] {
+            let mut filter = BloomFilter::for_elements(3, 0.01, tweak).with_flags(BLOOM_UPDATE_ALL);
+            let items = [
+                "99108ad8ed9bb6274d3980bab5a85c048f0950c8",
+                "b5a2c786d9ef4658287ced5914b37a1b4aa32eee",
+                "b9300670b4c5366e95b2699e8b18bc75e5f729c5",
+            ];
+            for item in items {
+                filter.add(&hex::decode(item).unwrap());
+            }
+            assert_eq!(hex::encode(filter.serialize()), expected);
+            assert!(filter.contains(&hex::decode(items[0]).unwrap()));
+            assert!(!filter.contains(&hex::decode("19108ad8ed9bb6274d3980bab5a85c048f0950c8").unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_for_elements_limits() {
+        let filter = BloomFilter::for_elements(1_000_000, 0.0001, 0);
+        assert_eq!(filter.bit_field().len(), MAX_BLOOM_FILTER_SIZE);
+        let filter = BloomFilter::for_elements(1, 1e-30, 0);
+        assert_eq!(filter.function_count(), MAX_HASH_FUNCS);
+    }
+
+    #[test]
+    fn test_empty_filter() {
+        let mut filter = BloomFilter::new(0, 5, 0);
+        filter.add(b"Hello World");
+        assert!(filter.contains(b"anything"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let too_large = BloomFilter::new(MAX_BLOOM_FILTER_SIZE + 1, 5, 0);
+        assert!(BloomFilter::parse(&mut Cursor::new(too_large.serialize())).is_err());
+        let too_many_functions = BloomFilter::new(10, MAX_HASH_FUNCS + 1, 0);
+        assert!(BloomFilter::parse(&mut Cursor::new(too_many_functions.serialize())).is_err());
+        let truncated = BloomFilter::new(10, 5, 0).serialize();
+        assert!(BloomFilter::parse(&mut Cursor::new(&truncated[..truncated.len() - 1])).is_err());
+    }
+}
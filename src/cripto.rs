@@ -1,179 +1,440 @@
-// use num_bigint::{BigUint, RandBigInt};
-// use sha2::{Digest, Sha256};
-
-
-// use crate::elliptic_curve::{S256Point, N_S256};
-
-// pub struct Signature {
-//     pub r: BigUint,
-//     pub s: BigUint,
-// }
-
-// impl Signature {
-//     pub fn new(r: BigUint, s: BigUint) -> Self {
-//         Signature { r, s }
-//     }
-
-//     pub fn der(&self) -> Vec<u8> {
-//         let mut rbin = self.r.to_bytes_be();
-//         if rbin[0] >= hex::decode("80").unwrap()[0] {
-//             rbin.insert(0, hex::decode("00").unwrap()[0]);
-//         }
-        
-//         rbin.insert(0, rbin.len() as u8);
-//         rbin.insert(0, 2);
-        
-//         let mut sbin = self.s.to_bytes_be();
-//         if sbin[0] >= hex::decode("80").unwrap()[0] {
-//             sbin.insert(0, hex::decode("00").unwrap()[0]);
-//         }
-//         sbin.insert(0, sbin.len() as u8);
-//         sbin.insert(0, 2);
-
-//         let mut result= rbin;
-//         result.append(&mut sbin);
-//         result.insert(0, result.len() as u8);
-//         result.insert(0, hex::decode("30").unwrap()[0]);
-//         result
-//     }
-// }
-
-// pub struct PrivateKey {
-//     secret: BigUint,
-// }
-
-// impl PrivateKey {
-
-//     pub fn new(secret: BigUint) -> Self {
-//         PrivateKey { secret }
-//     }
-
-//     pub fn sign(&self, z: &BigUint) -> Signature {
-//         let mut rng = rand::thread_rng();
-//         let k = rng.gen_biguint_below(&N_S256);
-//         let one = BigUint::from(1u32);
-//         let g = S256Point::generator();
-//         let r_point = &g * &k;
-//         let r = match r_point.xy() {
-//             Some((x, _)) => x,
-//             None => panic!("infinity"),
-//         };
-//         let k_inv = &k.modinv(&N_S256).unwrap();
-//         let mut s = ((z + r*&self.secret) * k_inv).modpow(&one, &N_S256);
-//         if s > N_S256.clone()/BigUint::from(2u32) {
-//             s = N_S256.clone() - s;
-//         }
-//         Signature::new(r.clone(), s)
-//     }
-
-//     pub fn wif(&self, compressed: bool, testnet: bool) -> String {
-//         let prefix = if testnet {
-//             hex::decode("ef").unwrap()[0]
-//         } else {
-//             hex::decode("80").unwrap()[0]
-//         };
-
-//         let mut secret_bytes = self.secret.to_bytes_be();
-//         secret_bytes.insert(0, prefix);
-        
-//         if compressed {
-//             let sufix = hex::decode("01").unwrap()[0];
-//             secret_bytes.push(sufix);
-//         }
-
-//         encode_base58_checksum(&secret_bytes)
-//     }
-// }
-
-// pub fn hash160(bytes: &[u8]) -> Vec<u8> {
-//     let mut sha_hasher = Sha256::new();
-//     sha_hasher.update(bytes);
-//     let res_1 = sha_hasher.finalize();
-
-//     let mut ripemd_hasher = ripemd::Ripemd160::new();
-//     ripemd_hasher.update(res_1);
-//     let res = ripemd_hasher.finalize();
+use num_bigint::BigUint;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::error::{CurveError, EncodingError};
+use crate::scalar::{Scalar, SecretScalar};
+use crate::secp256k1::{S256Field, S256Point};
+
+// An ECDSA signature, r and s as scalars, so both are always below N
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    r: Scalar,
+    s: Scalar,
+}
+
+impl Signature {
+    pub fn new(r: Scalar, s: Scalar) -> Self {
+        Signature { r, s }
+    }
+
+    // from plain integers, which have to be in 1..N
+    pub fn try_new(r: BigUint, s: BigUint) -> Result<Self, EncodingError> {
+        let (r, s) = (Scalar::try_new(r)?, Scalar::try_new(s)?);
+        if r.is_zero() || s.is_zero() {
+            return Err(EncodingError::ScalarOutOfRange);
+        }
+        Ok(Signature { r, s })
+    }
+
+    pub fn r(&self) -> &Scalar {
+        &self.r
+    }
+
+    pub fn s(&self) -> &Scalar {
+        &self.s
+    }
+
+    pub fn der(&self) -> Vec<u8> {
+        let mut result = Vec::new();
+        for num in [&self.r, &self.s] {
+            // integers are signed, so a leading byte with the top bit set needs a zero before it
+            let mut bin = num.num().to_bytes_be();
+            if bin[0] >= 0x80 {
+                bin.insert(0, 0x00);
+            }
+            result.push(0x02);
+            result.push(bin.len() as u8);
+            result.extend(bin);
+        }
+        result.insert(0, result.len() as u8);
+        result.insert(0, 0x30);
+        result
+    }
+}
+
+// A BIP 340 signature: the x coordinate of the nonce point R and s = k + e·d, serialized as
+// 64 bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchnorrSignature {
+    r: S256Field,
+    s: Scalar,
+}
+
+impl SchnorrSignature {
+    pub fn new(r: S256Field, s: Scalar) -> Self {
+        SchnorrSignature { r, s }
+    }
+
+    pub fn r(&self) -> &S256Field {
+        &self.r
+    }
+
+    pub fn s(&self) -> &Scalar {
+        &self.s
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, EncodingError> {
+        if bytes.len() != 64 {
+            return Err(EncodingError::InvalidLength { expected: 64, found: bytes.len() });
+        }
+        let r = S256Field::from_bytes(bytes[..32].try_into().unwrap()).ok_or(EncodingError::NotAFieldElement)?;
+        let s = Scalar::from_bytes(bytes[32..].try_into().unwrap())?;
+        Ok(SchnorrSignature { r, s })
+    }
+
+    pub fn serialize(&self) -> [u8; 64] {
+        let mut result = [0u8; 64];
+        result[..32].copy_from_slice(&self.r.to_bytes());
+        result[32..].copy_from_slice(&self.s.to_bytes());
+        result
+    }
+}
+
+// the secret wipes itself when the key is dropped and Debug only shows the public point
+#[derive(Debug)]
+pub struct PrivateKey {
+    secret: SecretScalar,
+    point: S256Point,
+}
+
+impl PrivateKey {
+    // a SecretScalar is never zero, so there is always a point
+    pub fn new(secret: SecretScalar) -> Self {
+        let point = S256Point::mul_generator(secret.scalar().num());
+        PrivateKey { secret, point }
+    }
+
+    pub fn point(&self) -> &S256Point {
+        &self.point
+    }
+
+    pub fn sign(&self, z: &BigUint) -> Signature {
+        let k = SecretScalar::random();
+        let r = match S256Point::mul_generator(k.scalar().num()).xy() {
+            Some((x, _)) => Scalar::reduce(&x.num()),
+            None => panic!("infinity"),
+        };
+        let k_inv = k.scalar().inverse().expect("nonce is not zero");
+        let mut s = &(&Scalar::reduce(z) + &(&r * &self.secret.scalar())) * &k_inv;
+        // the low s form, the other one is malleable and nonstandard
+        if s.is_high() {
+            s = -&s;
+        }
+        Signature::new(r, s)
+    }
+
+    // BIP 340 signing. The nonce is derived from the key, the message and 32 bytes of
+    // auxiliary randomness, and the key and nonce are negated when needed so that both
+    // public points have an even y.
+    pub fn sign_schnorr(&self, msg: &[u8], aux_rand: &[u8; 32]) -> SchnorrSignature {
+        let pubkey = self.point.xonly();
+        let d = if self.point.xy().expect("secret is not zero").1.is_even() {
+            self.secret.clone()
+        } else {
+            SecretScalar::new(-&self.secret.scalar())
+        };
+        // t, the nonce seed derived from it and the nonce are as secret as d
+        let mut t = d.to_bytes();
+        for (byte, mask) in t.iter_mut().zip(tagged_hash("BIP0340/aux", aux_rand)) {
+            *byte ^= mask;
+        }
+        let seed = Zeroizing::new([&t[..], &pubkey, msg].concat());
+        let nonce_hash = Zeroizing::new(tagged_hash("BIP0340/nonce", &seed));
+        let k = SecretScalar::new(Scalar::reduce(&BigUint::from_bytes_be(&*nonce_hash)));
+        let point = S256Point::mul_generator(k.scalar().num());
+        let (r, y) = match point.xy() {
+            Some((x, y)) => (*x, y.is_even()),
+            None => panic!("infinity"),
+        };
+        let k = if y { k.scalar() } else { -&k.scalar() };
+        let e = schnorr_challenge(&r.to_bytes(), &pubkey, msg);
+        SchnorrSignature::new(r, &k + &(&e * &d.scalar()))
+    }
+
+    // shared secret with the owner of `other`: the SHA-256 of the compressed SEC encoding of
+    // secret·other, the same derivation libsecp256k1 uses by default
+    pub fn ecdh(&self, other: &S256Point) -> Result<[u8; 32], CurveError> {
+        let shared = other.mul_ct(self.secret.scalar().num());
+        if shared.is_infinity() {
+            return Err(CurveError::PointAtInfinity);
+        }
+        Ok(Sha256::digest(shared.compressed_sec()).into())
+    }
+
+    pub fn wif(&self, compressed: bool, testnet: bool) -> String {
+        let prefix = if testnet { 0xef } else { 0x80 };
+        let mut secret_bytes = Zeroizing::new(vec![prefix]);
+        secret_bytes.extend(*self.secret.to_bytes());
+        if compressed {
+            secret_bytes.push(0x01);
+        }
+        encode_base58_checksum(&secret_bytes)
+    }
+}
+
+pub fn hash160(bytes: &[u8]) -> Vec<u8> {
+    let mut sha_hasher = Sha256::new();
+    sha_hasher.update(bytes);
+    let res_1 = sha_hasher.finalize();
+
+    let mut ripemd_hasher = ripemd::Ripemd160::new();
+    ripemd_hasher.update(res_1);
+    let res = ripemd_hasher.finalize();
     
-//     res.to_vec()
-// }
+    res.to_vec()
+}
 
-// pub fn hash256(bytes: &[u8]) -> Vec<u8> {
-//     let mut sha_hasher_1 = Sha256::new();
-//     sha_hasher_1.update(bytes);
-//     let res_1 = sha_hasher_1.finalize();
+pub fn hash256(bytes: &[u8]) -> Vec<u8> {
+    let mut sha_hasher_1 = Sha256::new();
+    sha_hasher_1.update(bytes);
+    let res_1 = sha_hasher_1.finalize();
 
-//     let mut sha_hasher_2 = Sha256::new();
-//     sha_hasher_2.update(res_1);
-//     let res_2 = sha_hasher_2.finalize();
+    let mut sha_hasher_2 = Sha256::new();
+    sha_hasher_2.update(res_1);
+    let res_2 = sha_hasher_2.finalize();
     
-//     res_2.to_vec()
-// }
-
-// pub fn encode_base58_checksum(bytes: &[u8]) -> String {
-//     let checksum = &hash256(bytes)[..4];
-//     let mut to_be_encoded = bytes.to_vec();
-//     to_be_encoded.append(&mut checksum.to_vec());
-//     bs58::encode(to_be_encoded).into_string()
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     #[test]
-//     fn test_sign() {
-//         let mut rng = rand::thread_rng();
-//         let secret = rng.gen_biguint_below(&N_S256);
-//         let pk = PrivateKey::new(secret.clone());
-//         let z = rng.gen_biguint_below(&BigUint::from(2u32).pow(256u32));
-//         let sig = pk.sign(&z);
-//         let g = S256Point::generator();
-//         let point = &g * &secret;
-//         assert!(point.verify(&z, &sig))
-//     }
-
-//     #[test]
-//     fn test_der() {
-//         let r = BigUint::from_bytes_be(&hex::decode("37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6").unwrap());
-//         let s = BigUint::from_bytes_be(&hex::decode("8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec").unwrap());
-//         let sig = Signature::new(r, s);
-//         assert_eq!(
-//             "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec",
-//             hex::encode(sig.der())
-//         );
-//     }
-
-//     #[test]
-//     fn test_hash160() {
-//         let hash_160 = hash160(b"my secret");
-//         let hash160_my_secret = [144, 228, 193, 75, 235, 6, 103, 99, 58, 142, 47, 92, 110, 148, 240, 140, 171, 139, 187, 93];
-//         assert_eq!(hash_160, hash160_my_secret);
-//     }
-
-//     #[test]
-//     fn test_wif() {
-//         let mut pk = PrivateKey::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(199));
-//         let mut expected = "L5oLkpV3aqBJ4BgssVAsax1iRa77G5CVYnv9adQ6Z87te7TyUdSC";
-//         assert_eq!(pk.wif(true, false), expected);
-
-//         pk = PrivateKey::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(201));
-//         expected = "93XfLeifX7Jx7n7ELGMAf1SUR6f9kgQs8Xke8WStMwUtrDucMzn";
-//         assert_eq!(pk.wif(false, true), expected);
-
-//         pk = PrivateKey::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(201));
-//         expected = "93XfLeifX7Jx7n7ELGMAf1SUR6f9kgQs8Xke8WStMwUtrDucMzn";
-//         assert_eq!(pk.wif(false, true), expected);
-
-//         pk = PrivateKey::new(BigUint::from_bytes_be(
-//             &hex::decode("0dba685b4511dbd3d368e5c4358a1277de9486447af7b3604a69b8d9d8b7889d").unwrap())
-//         );
-//         expected = "5HvLFPDVgFZRK9cd4C5jcWki5Skz6fmKqi1GQJf5ZoMofid2Dty";
-//         assert_eq!(pk.wif(false, false), expected);
-
-//         pk = PrivateKey::new(
-//             BigUint::from_bytes_be(&hex::decode("1cca23de92fd1862fb5b76e5f4f50eb082165e5191e116c18ed1a6b24be6a53f").unwrap())
-//         );
-//         expected = "cNYfWuhDpbNM1JWc3c6JTrtrFVxU4AGhUKgw5f93NP2QaBqmxKkg";
-//         assert_eq!(pk.wif(true, true), expected);
-//     }
-// }
\ No newline at end of file
+    res_2.to_vec()
+}
+
+// SHA256(SHA256(tag) || SHA256(tag) || msg), the domain separated hash of BIP 340
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    hasher.update(msg);
+    hasher.finalize().into()
+}
+
+// the BIP 340 challenge e for nonce point x coordinate r, x-only key and message
+pub fn schnorr_challenge(r: &[u8; 32], pubkey: &[u8; 32], msg: &[u8]) -> Scalar {
+    Scalar::reduce(&BigUint::from_bytes_be(&tagged_hash("BIP0340/challenge", &[&r[..], pubkey, msg].concat())))
+}
+
+// SipHash-2-4 keyed with (k0, k1), used to map items into BIP 158 compact filters
+pub fn siphash24(k0: u64, k1: u64, bytes: &[u8]) -> u64 {
+    let mut v = [
+        0x736f6d6570736575 ^ k0,
+        0x646f72616e646f6d ^ k1,
+        0x6c7967656e657261 ^ k0,
+        0x7465646279746573 ^ k1,
+    ];
+    let sip_round = |v: &mut [u64; 4]| {
+        v[0] = v[0].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(13) ^ v[0];
+        v[0] = v[0].rotate_left(32);
+        v[2] = v[2].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(16) ^ v[2];
+        v[0] = v[0].wrapping_add(v[3]);
+        v[3] = v[3].rotate_left(21) ^ v[0];
+        v[2] = v[2].wrapping_add(v[1]);
+        v[1] = v[1].rotate_left(17) ^ v[2];
+        v[2] = v[2].rotate_left(32);
+    };
+
+    let chunks = bytes.chunks_exact(8);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let m = u64::from_le_bytes(chunk.try_into().unwrap());
+        v[3] ^= m;
+        sip_round(&mut v);
+        sip_round(&mut v);
+        v[0] ^= m;
+    }
+
+    // the last block holds the remaining bytes and the message length in its top byte
+    let last = tail.iter().rev().fold(0u64, |acc, byte| (acc << 8) | *byte as u64) | ((bytes.len() as u64) << 56);
+    v[3] ^= last;
+    sip_round(&mut v);
+    sip_round(&mut v);
+    v[0] ^= last;
+
+    v[2] ^= 0xff;
+    for _ in 0..4 {
+        sip_round(&mut v);
+    }
+    v[0] ^ v[1] ^ v[2] ^ v[3]
+}
+
+pub fn encode_base58_checksum(bytes: &[u8]) -> String {
+    let checksum = &hash256(bytes)[..4];
+    let mut to_be_encoded = bytes.to_vec();
+    to_be_encoded.append(&mut checksum.to_vec());
+    bs58::encode(to_be_encoded).into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use num_bigint::RandBigInt;
+
+    use super::*;
+    use crate::secp256k1::N_S256;
+
+    #[test]
+    fn test_sign() {
+        let mut rng = rand::thread_rng();
+        let secret = SecretScalar::random();
+        let pk = PrivateKey::new(secret.clone());
+        let z = rng.gen_biguint_below(&BigUint::from(2u32).pow(256u32));
+        let sig = pk.sign(&z);
+        let g = S256Point::generator();
+        let point = &g * secret.scalar().num();
+        assert!(point.verify(&z, &sig));
+        assert!(!sig.s().is_high());
+        assert!(format!("{:?}", pk).starts_with("PrivateKey { secret: SecretScalar(<redacted>), point: "))
+    }
+
+    #[test]
+    fn test_ecdh() {
+        let alice = PrivateKey::new(SecretScalar::random());
+        let bob = PrivateKey::new(SecretScalar::random());
+        assert_eq!(alice.ecdh(bob.point()).unwrap(), bob.ecdh(alice.point()).unwrap());
+        assert_ne!(alice.ecdh(bob.point()).unwrap(), alice.ecdh(alice.point()).unwrap());
+        assert_eq!(alice.ecdh(&S256Point::infinity()), Err(CurveError::PointAtInfinity));
+    }
+
+    #[test]
+    fn test_schnorr_bip340_vectors() {
+        // (secret key, public key, aux_rand, message, signature), vectors 0 and 1 of BIP 340
+        let vectors = [
+            (
+                "0000000000000000000000000000000000000000000000000000000000000003",
+                "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "0000000000000000000000000000000000000000000000000000000000000000",
+                "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+            ),
+            (
+                "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+                "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+                "0000000000000000000000000000000000000000000000000000000000000001",
+                "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+                "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+            ),
+        ];
+        for (secret, pubkey, aux, msg, sig) in vectors {
+            let pk = PrivateKey::new(SecretScalar::from_bytes(&hex::decode(secret).unwrap().try_into().unwrap()).unwrap());
+            assert_eq!(hex::encode(pk.point().xonly()), pubkey);
+            let msg = hex::decode(msg).unwrap();
+            let signature = pk.sign_schnorr(&msg, &hex::decode(aux).unwrap().try_into().unwrap());
+            assert_eq!(hex::encode(signature.serialize()), sig);
+            assert_eq!(SchnorrSignature::parse(&signature.serialize()).unwrap(), signature);
+
+            let point = S256Point::lift_x(&hex::decode(pubkey).unwrap().try_into().unwrap()).unwrap();
+            assert!(point.verify_schnorr(&msg, &signature));
+            let mut tampered = msg.clone();
+            tampered[0] ^= 1;
+            assert!(!point.verify_schnorr(&tampered, &signature));
+        }
+
+        // vector 4, a valid signature whose R has a tiny x coordinate
+        let point = S256Point::lift_x(&hex::decode("d69c3509bb99e412e68b0fe8544e72837dfa30746d8be2aa65975f29d22dc7b9").unwrap().try_into().unwrap()).unwrap();
+        let msg = hex::decode("4df3c3f68fcc83b27e9d42c90431a72499f17875c81a599b566c9889b9696703").unwrap();
+        let sig = SchnorrSignature::parse(&hex::decode("00000000000000000000003b78ce563f89a0ed9414f5aa28ad0d96d6795f9c6376afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4").unwrap()).unwrap();
+        assert!(point.verify_schnorr(&msg, &sig));
+
+        assert_eq!(SchnorrSignature::parse(&[0u8; 63]), Err(EncodingError::InvalidLength { expected: 64, found: 63 }));
+        assert_eq!(SchnorrSignature::parse(&[0xff; 64]), Err(EncodingError::NotAFieldElement));
+        let mut high_s = [0xffu8; 64];
+        high_s[..32].copy_from_slice(&[0u8; 32]);
+        assert_eq!(SchnorrSignature::parse(&high_s), Err(EncodingError::ScalarOutOfRange));
+    }
+
+    #[test]
+    fn test_schnorr_sign_odd_key() {
+        for _ in 0..4 {
+            let pk = PrivateKey::new(SecretScalar::random());
+            let msg = b"schnorr";
+            let sig = pk.sign_schnorr(msg, &[7u8; 32]);
+            // the full point may have an odd y, only its x coordinate is the key
+            assert!(pk.point().verify_schnorr(msg, &sig));
+        }
+    }
+
+    #[test]
+    fn test_der() {
+        let r = BigUint::from_bytes_be(&hex::decode("37206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c6").unwrap());
+        let s = BigUint::from_bytes_be(&hex::decode("8ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec").unwrap());
+        let sig = Signature::try_new(r, s).unwrap();
+        assert_eq!(
+            "3045022037206a0610995c58074999cb9767b87af4c4978db68c06e8e6e81d282047a7c60221008ca63759c1157ebeaec0d03cecca119fc9a75bf8e6d0fa65c841c8e2738cdaec",
+            hex::encode(sig.der())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "scalar is out of range for the group order")]
+    fn test_zero_private_key() {
+        PrivateKey::new(SecretScalar::new(Scalar::ZERO));
+    }
+
+    #[test]
+    fn test_signature_range() {
+        let one = BigUint::from(1u32);
+        assert!(Signature::try_new(one.clone(), &*N_S256 - 1u32).is_ok());
+        assert_eq!(Signature::try_new(BigUint::ZERO, one.clone()), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(Signature::try_new(one.clone(), BigUint::ZERO), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(Signature::try_new(N_S256.clone(), one.clone()), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(Signature::try_new(one, &*N_S256 + 1u32), Err(EncodingError::ScalarOutOfRange));
+    }
+
+    #[test]
+    fn test_hash160() {
+        let hash_160 = hash160(b"my secret");
+        let hash160_my_secret = [144, 228, 193, 75, 235, 6, 103, 99, 58, 142, 47, 92, 110, 148, 240, 140, 171, 139, 187, 93];
+        assert_eq!(hash_160, hash160_my_secret);
+    }
+
+    #[test]
+    fn test_hash256() {
+        let hash_256 = hash256(b"hello");
+        assert_eq!(
+            hex::encode(hash_256),
+            "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50"
+        );
+    }
+
+    #[test]
+    fn test_siphash24() {
+        let k0 = 0x0706050403020100;
+        let k1 = 0x0f0e0d0c0b0a0908;
+        assert_eq!(siphash24(k0, k1, &[]), 0x726fdb47dd0e0e31);
+        assert_eq!(siphash24(k0, k1, &[0]), 0x74f839c593dc67fd);
+        assert_eq!(siphash24(k0, k1, &(0..8).collect::<Vec<u8>>()), 0x93f5f5799a932462);
+        assert_eq!(siphash24(k0, k1, &(0..15).collect::<Vec<u8>>()), 0xa129ca6149be45e5);
+    }
+
+    #[test]
+    fn test_encode_base58_checksum() {
+        let bytes = hex::decode("00751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(encode_base58_checksum(&bytes), "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH");
+    }
+
+    #[test]
+    fn test_wif() {
+        let mut pk = PrivateKey::new(SecretScalar::new(Scalar::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(199))));
+        let mut expected = "L5oLkpV3aqBJ4BgssVAsax1iRa77G5CVYnv9adQ6Z87te7TyUdSC";
+        assert_eq!(pk.wif(true, false), expected);
+
+        pk = PrivateKey::new(SecretScalar::new(Scalar::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(201))));
+        expected = "93XfLeifX7Jx7n7ELGMAf1SUR6f9kgQs8Xke8WStMwUtrDucMzn";
+        assert_eq!(pk.wif(false, true), expected);
+
+        pk = PrivateKey::new(SecretScalar::new(Scalar::new(BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(201))));
+        expected = "93XfLeifX7Jx7n7ELGMAf1SUR6f9kgQs8Xke8WStMwUtrDucMzn";
+        assert_eq!(pk.wif(false, true), expected);
+
+        pk = PrivateKey::new(SecretScalar::new(Scalar::new(BigUint::from_bytes_be(
+            &hex::decode("0dba685b4511dbd3d368e5c4358a1277de9486447af7b3604a69b8d9d8b7889d").unwrap())
+        )));
+        expected = "5HvLFPDVgFZRK9cd4C5jcWki5Skz6fmKqi1GQJf5ZoMofid2Dty";
+        assert_eq!(pk.wif(false, false), expected);
+
+        pk = PrivateKey::new(
+            SecretScalar::new(Scalar::new(BigUint::from_bytes_be(&hex::decode("1cca23de92fd1862fb5b76e5f4f50eb082165e5191e116c18ed1a6b24be6a53f").unwrap())))
+        );
+        expected = "cNYfWuhDpbNM1JWc3c6JTrtrFVxU4AGhUKgw5f93NP2QaBqmxKkg";
+        assert_eq!(pk.wif(true, true), expected);
+    }
+}
@@ -0,0 +1,200 @@
+use num_bigint::BigUint;
+
+use crate::block::BlockHeader;
+use crate::helper::{bits_to_target, target_to_bits};
+use crate::network::Network;
+
+pub const TARGET_TIMESPAN: i64 = 14 * 24 * 60 * 60;
+pub const TARGET_SPACING: i64 = 10 * 60;
+pub const DIFFICULTY_ADJUSTMENT_INTERVAL: u32 = 2016;
+
+pub fn calculate_new_bits(prev_bits: u32, time_differential: i64, pow_limit: &BigUint) -> Result<u32, &'static str> {
+    let timespan = time_differential.clamp(TARGET_TIMESPAN / 4, TARGET_TIMESPAN * 4);
+    let mut new_target = bits_to_target(prev_bits)? * BigUint::from(timespan as u64) / BigUint::from(TARGET_TIMESPAN as u64);
+    if &new_target > pow_limit {
+        new_target = pow_limit.clone();
+    }
+    Ok(target_to_bits(&new_target))
+}
+
+// Like Bitcoin Core, the time differential is measured between the first and the last
+// block of the period, so it only spans 2015 block intervals.
+pub fn retarget(first: &BlockHeader, last: &BlockHeader, network: Network) -> Result<u32, &'static str> {
+    if network.no_retargeting() {
+        return Ok(last.bits());
+    }
+    let time_differential = last.timestamp() as i64 - first.timestamp() as i64;
+    calculate_new_bits(last.bits(), time_differential, network.pow_limit())
+}
+
+// `ancestor` looks up the header at a given height of the chain that ends at `last`.
+pub fn next_work_required<'a, F>(
+    network: Network,
+    last_height: u32,
+    last: &'a BlockHeader,
+    new_timestamp: u32,
+    ancestor: F,
+) -> Result<u32, &'static str>
+where
+    F: Fn(u32) -> Option<&'a BlockHeader>,
+{
+    let next_height = last_height + 1;
+
+    if !next_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+        if !network.allow_min_difficulty_blocks() {
+            return Ok(last.bits());
+        }
+
+        let pow_limit_bits = target_to_bits(network.pow_limit());
+        if new_timestamp as i64 > last.timestamp() as i64 + TARGET_SPACING * 2 {
+            return Ok(pow_limit_bits);
+        }
+
+        // otherwise use the bits of the last block that wasn't mined at the minimum difficulty
+        let mut height = last_height;
+        let mut header = last;
+        while !height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) && header.bits() == pow_limit_bits {
+            height -= 1;
+            header = ancestor(height).ok_or("missing ancestor header")?;
+        }
+        return Ok(header.bits());
+    }
+
+    let first = ancestor(next_height - DIFFICULTY_ADJUSTMENT_INTERVAL).ok_or("missing first header of the period")?;
+    retarget(first, last, network)
+}
+
+// `headers[0]` is the header at `start_height`, which must be the first block of a
+// difficulty period so that every transition in the slice can be checked.
+pub fn check_difficulty_transitions(headers: &[BlockHeader], start_height: u32, network: Network) -> Result<(), &'static str> {
+    if !start_height.is_multiple_of(DIFFICULTY_ADJUSTMENT_INTERVAL) {
+        return Err("header chain must start at a difficulty adjustment boundary");
+    }
+
+    for (i, pair) in headers.windows(2).enumerate() {
+        let last_height = start_height + i as u32;
+        let ancestor = |height: u32| {
+            height.checked_sub(start_height).and_then(|index| headers.get(index as usize))
+        };
+        let expected = next_work_required(network, last_height, &pair[0], pair[1].timestamp(), ancestor)?;
+        if pair[1].bits() != expected {
+            return Err("header has incorrect difficulty bits");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn parse_hex(header: &str) -> BlockHeader {
+        BlockHeader::parse(&mut Cursor::new(hex::decode(header).unwrap())).unwrap()
+    }
+
+    fn build_chain(bits: &[u32], timestamps: &[u32]) -> Vec<BlockHeader> {
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        for (bits, timestamp) in bits.iter().zip(timestamps) {
+            let prev_block = headers.last().map(|header| header.hash()).unwrap_or([0u8; 32]);
+            headers.push(BlockHeader::new(1, prev_block, [0u8; 32], *timestamp, *bits, 0));
+        }
+        headers
+    }
+
+    #[test]
+    fn test_calculate_new_bits() {
+        let pow_limit = Network::Mainnet.pow_limit();
+        assert_eq!(calculate_new_bits(0x1d00ffff, 1262152739 - 1261130161, pow_limit).unwrap(), 0x1d00d86a);
+        assert_eq!(calculate_new_bits(0x1801d854, 302400, pow_limit).unwrap(), 0x17761500);
+    }
+
+    #[test]
+    fn test_calculate_new_bits_clamps() {
+        let pow_limit = Network::Mainnet.pow_limit();
+        // never easier than the proof of work limit
+        assert_eq!(calculate_new_bits(0x1d00ffff, 1233061996 - 1231006505, pow_limit).unwrap(), 0x1d00ffff);
+        // at most 4x harder
+        assert_eq!(calculate_new_bits(0x1c05a3f4, 1279297671 - 1279008237, pow_limit).unwrap(), 0x1c0168fd);
+        // at most 4x easier
+        assert_eq!(calculate_new_bits(0x1c387f6f, 1269211443 - 1263163443, pow_limit).unwrap(), 0x1d00e1fd);
+        // a timestamp going backwards is clamped like any other short period
+        assert_eq!(calculate_new_bits(0x1c05a3f4, -1000, pow_limit).unwrap(), 0x1c0168fd);
+    }
+
+    #[test]
+    fn test_calculate_new_bits_invalid_bits() {
+        assert!(calculate_new_bits(0x04923456, TARGET_TIMESPAN, Network::Mainnet.pow_limit()).is_err());
+    }
+
+    #[test]
+    fn test_retarget() {
+        let first = parse_hex("000000203471101bbda3fe307664b3283a9ef0e97d9a38a7eacd8800000000000000000010c8aba8479bbaa5e0848152fd3c2289ca50e1c3e58c9a4faaafbdf5803c5448ddb845597e8b0118e43a81d3");
+        let last = parse_hex("02000020f1472d9db4b563c35f97c428ac903f23b7fc055d1cfc26000000000000000000b3f449fcbe1bc4cfbcb8283a0d2c037f961a3fdf2b8bedc144973735eea707e1264258597e8b0118e5f00474");
+        assert_eq!(retarget(&first, &last, Network::Mainnet).unwrap(), 0x18018d30);
+        assert_eq!(retarget(&first, &last, Network::Regtest).unwrap(), last.bits());
+    }
+
+    #[test]
+    fn test_check_difficulty_transitions_at_retarget() {
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let timestamps: Vec<u32> = (0..=interval as u32).map(|i| 1231006505 + i * 300).collect();
+        let new_bits = calculate_new_bits(0x1c05a3f4, 2015 * 300, Network::Mainnet.pow_limit()).unwrap();
+
+        let mut bits = vec![0x1c05a3f4; interval];
+        bits.push(new_bits);
+        let headers = build_chain(&bits, &timestamps);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Mainnet).is_ok());
+
+        let bits = vec![0x1c05a3f4; interval + 1];
+        let headers = build_chain(&bits, &timestamps);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_check_difficulty_transitions_within_period() {
+        let headers = build_chain(&[0x1c05a3f4, 0x1c05a3f4, 0x1c05a3f5], &[1000, 1600, 2200]);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Mainnet).is_err());
+
+        // mainnet has no minimum difficulty exception
+        let headers = build_chain(&[0x1c05a3f4, 0x1c05a3f4, 0x1d00ffff], &[1000, 1600, 5000]);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Mainnet).is_err());
+
+        let headers = build_chain(&[0x1c05a3f4, 0x1c05a3f4], &[1000, 1600]);
+        assert!(check_difficulty_transitions(&headers, 1, Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_check_difficulty_transitions_testnet_min_difficulty() {
+        // a block more than 20 minutes after its parent may use the minimum difficulty
+        let headers = build_chain(&[0x1c05a3f4, 0x1c05a3f4, 0x1d00ffff], &[1000, 1600, 2801]);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Testnet).is_ok());
+
+        // but exactly 20 minutes is not enough
+        let headers = build_chain(&[0x1c05a3f4, 0x1c05a3f4, 0x1d00ffff], &[1000, 1600, 2800]);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Testnet).is_err());
+
+        // the block after it goes back to the last real difficulty
+        let headers = build_chain(
+            &[0x1c05a3f4, 0x1c05a3f4, 0x1d00ffff, 0x1d00ffff, 0x1c05a3f4],
+            &[1000, 1600, 2801, 4002, 4100],
+        );
+        assert!(check_difficulty_transitions(&headers, 0, Network::Testnet).is_ok());
+
+        let headers = build_chain(
+            &[0x1c05a3f4, 0x1c05a3f4, 0x1d00ffff, 0x1d00ffff, 0x1d00ffff],
+            &[1000, 1600, 2801, 4002, 4100],
+        );
+        assert!(check_difficulty_transitions(&headers, 0, Network::Testnet).is_err());
+    }
+
+    #[test]
+    fn test_check_difficulty_transitions_regtest() {
+        let interval = DIFFICULTY_ADJUSTMENT_INTERVAL as usize;
+        let timestamps: Vec<u32> = (0..=interval as u32).map(|i| 1296688602 + i).collect();
+        let headers = build_chain(&vec![0x207fffff; interval + 1], &timestamps);
+        assert!(check_difficulty_transitions(&headers, 0, Network::Regtest).is_ok());
+    }
+}
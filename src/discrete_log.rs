@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use num_bigint::{BigInt, BigUint, RandBigInt};
+
+use crate::elliptic_curve::Point;
+use crate::finite_field::{FieldRef, PrimeField};
+
+// Solvers for the elliptic curve discrete log problem, finding k with k·P = Q. Both take about
+// √n steps for a point of order n, which is nothing on a toy curve and hopeless on secp256k1.
+
+// Baby-step giant-step: with m = ⌈√n⌉, store j·P for j < m, then walk Q, Q - m·P, Q - 2m·P, ...
+// until one of them is in the table, which makes k = i·m + j. `order` is the order of P, or
+// any multiple of it.
+pub fn baby_step_giant_step<F: PrimeField>(p: &Point<F>, q: &Point<F>, order: &BigUint) -> Option<BigUint> where for<'a> &'a F: FieldRef<F> {
+    let m = order.sqrt() + 1u32;
+    let mut baby_steps = HashMap::new();
+    let mut current = p * BigUint::ZERO;
+    let mut j = BigUint::ZERO;
+    while j < m {
+        baby_steps.entry(current.to_compressed()).or_insert_with(|| j.clone());
+        current = (&current + p).ok()?;
+        j += 1u32;
+    }
+
+    // giant steps subtract m·P each time
+    let giant = p * (order - &m % order);
+    let mut current = q.clone();
+    let mut i = BigUint::ZERO;
+    while i < m {
+        if let Some(j) = baby_steps.get(&current.to_compressed()) {
+            return Some((&i * &m + j) % order);
+        }
+        current = (&current + &giant).ok()?;
+        i += 1u32;
+    }
+    None
+}
+
+// Pollard's rho: a pseudo-random walk over points X = a·P + b·Q, split into three kinds of
+// step by the point's encoding, repeats after about √n steps. Floyd's cycle finding spots the
+// repetition a·P + b·Q = a'·P + b'·Q, so (a - a') = k·(b' - b) modulo n, the order of P. Gives
+// up after a few fresh starts when that can't be solved.
+pub fn pollard_rho<F: PrimeField>(p: &Point<F>, q: &Point<F>, order: &BigUint) -> Option<BigUint> where for<'a> &'a F: FieldRef<F> {
+    if *order == BigUint::from(1u32) {
+        return (p * BigUint::ZERO == *q).then_some(BigUint::ZERO);
+    }
+    let mut rng = rand::thread_rng();
+    for _ in 0..20 {
+        let a = rng.gen_biguint_below(order);
+        let b = rng.gen_biguint_below(order);
+        let start = ((&(p * a.clone()) + &(q * b.clone())).ok()?, a, b);
+        let mut tortoise = rho_step(&start, p, q, order)?;
+        let mut hare = rho_step(&tortoise, p, q, order)?;
+        while tortoise.0 != hare.0 {
+            tortoise = rho_step(&tortoise, p, q, order)?;
+            hare = rho_step(&rho_step(&hare, p, q, order)?, p, q, order)?;
+        }
+        if let Some(k) = solve_collision(&tortoise, &hare, p, q, order) {
+            return Some(k);
+        }
+    }
+    None
+}
+
+type RhoState<F> = (Point<F>, BigUint, BigUint);
+
+fn rho_step<F: PrimeField>(state: &RhoState<F>, p: &Point<F>, q: &Point<F>, order: &BigUint) -> Option<RhoState<F>> where for<'a> &'a F: FieldRef<F> {
+    let (x, a, b) = state;
+    let partition = x.to_compressed().iter().fold(0u32, |acc, byte| (acc * 256 + *byte as u32) % 3);
+    Some(match partition {
+        0 => ((x + q).ok()?, a.clone(), (b + 1u32) % order),
+        1 => ((x + x).ok()?, (a * 2u32) % order, (b * 2u32) % order),
+        _ => ((x + p).ok()?, (a + 1u32) % order, b.clone()),
+    })
+}
+
+// (a - a') = k·(b' - b) mod n has gcd(b' - b, n) solutions when that gcd divides a - a';
+// they are few for small gcds, so each one is checked against Q
+fn solve_collision<F: PrimeField>(first: &RhoState<F>, second: &RhoState<F>, p: &Point<F>, q: &Point<F>, order: &BigUint) -> Option<BigUint> where for<'a> &'a F: FieldRef<F> {
+    let n = BigInt::from(order.clone());
+    let lhs = (BigInt::from(first.1.clone()) - BigInt::from(second.1.clone())).modpow(&BigInt::from(1), &n);
+    let rhs = (BigInt::from(second.2.clone()) - BigInt::from(first.2.clone())).modpow(&BigInt::from(1), &n);
+    let g = gcd(&rhs, &n);
+    if g == BigInt::ZERO || &lhs % &g != BigInt::ZERO || g > BigInt::from(1000) {
+        return None;
+    }
+    let reduced_n = &n / &g;
+    let base = if reduced_n == BigInt::from(1) {
+        BigInt::ZERO
+    } else {
+        (&lhs / &g) * (&rhs / &g).modinv(&reduced_n)? % &reduced_n
+    };
+    let mut candidate = base;
+    while candidate < n {
+        let k = candidate.to_biguint()?;
+        if p * k.clone() == *q {
+            return Some(k);
+        }
+        candidate += &reduced_n;
+    }
+    None
+}
+
+fn gcd(a: &BigInt, b: &BigInt) -> BigInt {
+    let (mut a, mut b) = (a.clone(), b.clone());
+    while b != BigInt::ZERO {
+        let rest = &a % &b;
+        a = b;
+        b = rest;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::elliptic_curve::EllipticCurve;
+    use crate::finite_field::FieldElement;
+    use crate::named_curve::F223;
+
+    type F = FieldElement<F223>;
+
+    fn point(curve: &Arc<EllipticCurve<F>>, x: u32, y: u32) -> Point<F> {
+        Point::from(Arc::clone(curve), F::new(BigUint::from(x)), F::new(BigUint::from(y))).unwrap()
+    }
+
+    #[test]
+    fn test_discrete_log() {
+        let curve = Arc::new(EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::from(7u32))));
+        for generator in [point(&curve, 47, 71), point(&curve, 15, 86)] {
+            let order = generator.order().unwrap();
+            let mut k = BigUint::ZERO;
+            while k < order {
+                let q = &generator * k.clone();
+                assert_eq!(baby_step_giant_step(&generator, &q, &order), Some(k.clone()));
+                assert_eq!(pollard_rho(&generator, &q, &order), Some(k.clone()));
+                k += 1u32;
+            }
+        }
+
+        // (6, 0) has order 2, so it is no multiple of (47, 71), whose order is odd
+        let generator = point(&curve, 47, 71);
+        let q = point(&curve, 6, 0);
+        assert_eq!(baby_step_giant_step(&generator, &q, &generator.order().unwrap()), None);
+        assert_eq!(pollard_rho(&generator, &q, &generator.order().unwrap()), None);
+    }
+
+    #[test]
+    fn test_discrete_log_as_mul_oracle() {
+        // a point of the largest order in the group (42, as the group of order 252
+        // is Z/2 × Z/2 × Z/3 × Z/3 × Z/7)
+        // checks multiplication by every scalar against an independent solver
+        let curve = Arc::new(EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::from(7u32))));
+        let generator = curve.points().unwrap().into_iter().max_by_key(|p| p.order()).unwrap();
+        let order = generator.order().unwrap();
+        assert_eq!(order, BigUint::from(42u32));
+        let mut k = BigUint::ZERO;
+        while k < order {
+            let q = &generator * k.clone();
+            assert_eq!(baby_step_giant_step(&generator, &q, &order), Some(k.clone()));
+            k += 1u32;
+        }
+    }
+}
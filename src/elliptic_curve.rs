@@ -1,297 +1,687 @@
-use num_bigint::{BigInt, BigUint};
+use num_bigint::{BigInt, BigUint, Sign};
 use std::ops::{Add, BitAnd, Mul};
-use std::rc::Rc;
+use std::sync::Arc;
 
-// use crate::cripto::{hash160, encode_base58_checksum, Signature};
-use crate::finite_field::{FieldElement, P};
-// use crate::secp256k1::S256Field;
+use crate::error::{CurveError, EncodingError};
+use crate::finite_field::{FieldRef, PrimeField};
 
-use lazy_static::lazy_static;
+pub type Curve<F> = EllipticCurve<F>;
 
-// lazy_static! {
-//     pub static ref N_S256: BigUint = BigUint::from_bytes_be(&hex::decode("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141").unwrap());
+// window width of the wNAF digits used by multi_mul
+pub const WNAF_WIDTH: u32 = 4;
+// from this many terms on, multi_mul switches from Strauss to Pippenger
+pub const PIPPENGER_THRESHOLD: usize = 32;
+// the largest field prime EllipticCurve::order and points will count the points over
+pub const MAX_COUNTED_PRIME: u32 = 1 << 20;
 
-//     static ref A: S256Field = S256Field::new(BigUint::from(0u32));
-//     static ref B: S256Field = S256Field::new(BigUint::from(7u32));
-//     static ref Gx: S256Field = S256Field::new(BigUint::from_bytes_be(&hex::decode("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap()));
-//     static ref Gy: S256Field = S256Field::new(BigUint::from_bytes_be(&hex::decode("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8").unwrap()));
-// }
+// pub type Curve = dyn Fn(A, B);
 
-// #[derive(Debug, Clone)]
-// pub enum CoordsS256 {
-//     Infinity,
-//     Finite(S256Field, S256Field),
-// }
+#[derive(Debug, PartialEq, Clone)]
+enum XY<F> {
+    Infinity,
+    Finite{ x: F, y: F },
+}
 
-// #[derive(Debug, Clone)]
-// pub struct S256Point(pub Point);
+// pub trait Pointe {}
 
-// impl S256Point {
-//     pub fn new (xy: CoordsS256) -> Self {
-//         match xy {
-//             CoordsS256::Infinity => S256Point(Point::at_inifity(&A.0, &B.0)),
-//             CoordsS256::Finite(s256_x, s256_y ) => {
+// pub struct Infinity;
 
-//                 return S256Point(Point::new(Coords::Finite(s256_x.0, s256_y.0), &A.0, &B.0));
-//             }
-//         }
-//     }
+// impl Pointe for Infinity {}
+// pub struct Finite {
+//     x: X,
+//     y: Y,
+// }
 
-//     pub fn generator() -> Self {
-//         S256Point::new(CoordsS256::Finite(Gx.clone(), Gy.clone()))
-//     }
+// impl Pointe for Finite {}
 
-//     pub fn xy(&self) -> Option<(&BigUint, &BigUint)> {
-//         match &self.0.xy {
-//             Coords::Finite(x, y) => Some((&x.num, &y.num)),
-//             _ => None,
-//         }
-//     }
+// y² = x³ + a·x + b over the prime field F
+#[derive(Debug, Clone)]
+pub struct EllipticCurve<F> {
+    a: F,
+    b: F,
+    endomorphism: Option<Endomorphism<F>>,
+    known_order: Option<BigUint>,
+}
 
-//     pub fn verify(&self, z: &BigUint, sig: &Signature) -> bool {
-//         let one = BigUint::from(1u32);
-//         let s_inv = &sig.s.modinv(&N_S256).unwrap();
-//         let u = (z * s_inv).modpow(&one, &N_S256);
-//         let v = (&sig.r * s_inv).modpow(&one, &N_S256);
-//         let g = S256Point::generator();
-//         let ug = &g * &u;
-//         let vp = self * &v;
-//         let r_point = &ug + &vp;
-//         match r_point.xy() {
-//             Some((x, _)) => *x == sig.r,
-//             None => false,
-//         }
-//     }
+// A map (x, y) -> (β·x, y) that acts on the curve's points as multiplication by some λ, as
+// on curves with a = 0 over a prime p ≡ 1 mod 3. `decompose` splits k into k₁ + k₂·λ with k₁
+// and k₂ about half as long as k (GLV), which is specific to the curve and its group order.
+#[derive(Debug, Clone)]
+pub struct Endomorphism<F> {
+    beta: F,
+    decompose: fn(&BigUint) -> (BigInt, BigInt),
+}
 
-//     pub fn uncompressed_sec(&self) -> Vec<u8> {
-//         let (x, y) = self.xy().unwrap();
-//         let mut x_be = x.to_bytes_be();
-//         let mut y_be = y.to_bytes_be();
-//         let mut marker_be = BigUint::parse_bytes(b"04", 32).unwrap().to_bytes_be();
-//         marker_be.append(&mut x_be);
-//         marker_be.append(&mut y_be);
-//         marker_be
-//     }
+impl<F> Endomorphism<F> {
+    pub fn new(beta: F, decompose: fn(&BigUint) -> (BigInt, BigInt)) -> Self {
+        Endomorphism { beta, decompose }
+    }
+}
 
-//     pub fn compressed_sec(&self) -> Vec<u8> {
-//         let (x, y) = self.xy().unwrap();
-//         let mut marker = b"03";
-//         if y.modpow(&BigUint::from(1u32), &BigUint::from(2u32)) == BigUint::ZERO {
-//             marker = b"02";
-//         }
-//         let mut marker_be = BigUint::parse_bytes(marker, 32).unwrap().to_bytes_be();
-//         let mut x_be = x.to_bytes_be();
-//         marker_be.append(&mut x_be);
-//         marker_be
-//     }
+impl<F: PrimeField> EllipticCurve<F> {
+    pub fn new(a: F, b: F) -> Self {
+        match EllipticCurve::try_new(a, b) {
+            Ok(curve) => curve,
+            Err(err) => panic!("{}", err),
+        }
+    }
 
-//     pub fn parse(&self, sec_bin: &[u8]) -> Self {
-//         if sec_bin[0] == 4u8 {
-//             let x = BigUint::from_bytes_be(&sec_bin[1..33]);
-//             let y = BigUint::from_bytes_be(&sec_bin[33..]);
-//             return S256Point::new(CoordsS256::Finite(S256Field::new(x), S256Field::new(y)));
-//         }
+    // A zero discriminant 4a³ + 27b² means x³ + a·x + b has a repeated root, and the curve
+    // a cusp or a node where the chord and tangent rule breaks down.
+    pub fn try_new(a: F, b: F) -> Result<Self, CurveError> {
+        let (a_num, b_num) = (a.to_biguint(), b.to_biguint());
+        let discriminant = (BigUint::from(4u32) * a_num.pow(3) + BigUint::from(27u32) * b_num.pow(2)) % F::modulus();
+        if discriminant == BigUint::ZERO {
+            return Err(CurveError::Singular);
+        }
+        Ok(EllipticCurve { a, b, endomorphism: None, known_order: None })
+    }
 
-//         let is_even = sec_bin[0] == 2u8;
-//         let x = S256Field::new(BigUint::from_bytes_be(&sec_bin[1..]));
-//         let alpha = S256Field::from_S256_field(x.pow(&BigInt::from(3)) + B.clone());
-//         let beta = alpha.sqrt();
-//         let mut even_beta = beta.clone();
-//         let mut odd_beta = S256Field::new(P.clone() - beta.num());
-//         if beta.num().modpow(&BigUint::from(1u32), &BigUint::from(2u32)) != BigUint::ZERO {
-//             even_beta = S256Field::new(P.clone() - beta.num());
-//             odd_beta = beta;
-//         }
-//         if is_even {
-//             S256Point::new(CoordsS256::Finite(x, even_beta))
-//         } else {
-//             S256Point::new(CoordsS256::Finite(x, odd_beta))
-//         }
-//     }
+    // lets multiplication use the endomorphism, see glv_mul
+    pub fn with_endomorphism(mut self, endomorphism: Endomorphism<F>) -> Self {
+        self.endomorphism = Some(endomorphism);
+        self
+    }
 
-//     pub fn address(&self, compressed: bool, testnet: bool) -> String {
-//         let mut h160= if compressed {
-//             hash160(&self.compressed_sec())
-//         } else {
-//             hash160(&self.uncompressed_sec())
-//         };
-
-//         let prefix = if testnet {
-//             hex::decode("6f").unwrap()[0]
-//         } else {
-//             hex::decode("00").unwrap()[0]
-//         };
-
-//         h160.insert(0, prefix);
-//         encode_base58_checksum(&h160)
-//     }
-// }
+    pub fn endomorphism(&self) -> Option<&Endomorphism<F>> {
+        self.endomorphism.as_ref()
+    }
 
-// impl<'a> Mul<&'a BigUint> for &'a S256Point<'a> {
-//     type Output = S256Point<'a>;
+    // the number of points, n·h, for curves whose domain parameters give it, as counting
+    // them is out of reach on real curves
+    pub fn with_known_order(mut self, order: BigUint) -> Self {
+        self.known_order = Some(order);
+        self
+    }
 
-//     fn mul(self, other: &'a BigUint) -> S256Point<'a> {
-//         let one = BigUint::from(1u32);
-//         let coef = other.modpow(&one, &N_S256);
-//         let point = &self.0 * coef;
-//         S256Point(point)
-//     }   
-// }
+    pub fn known_order(&self) -> Option<&BigUint> {
+        self.known_order.as_ref()
+    }
 
-// impl<'a> Add<&'a S256Point<'a>> for &'a S256Point<'_> {
-//     type Output = S256Point<'a>;
+    pub fn a(&self) -> &F {
+        &self.a
+    }
 
-//     fn add(self, other: &'a S256Point) -> S256Point<'a> {
-//         let p = &self.0 + &other.0;
-//         match p.xy {
-//             Coords::Infinity => S256Point::new(CoordsS256::Infinity),
-//             Coords::Finite(x, y) => 
-//                 S256Point::new(CoordsS256::Finite(S256Field::new(x.num), S256Field::new(y.num)))
-//         }
-//     }
-// }
+    pub fn b(&self) -> &F {
+        &self.b
+    }
 
-pub type A = FieldElement;
-pub type B = FieldElement;
-pub type X = FieldElement;
-pub type Y = FieldElement;
-pub type Curve = EllipticCurve;
+    // x³ + a·x + b
+    fn rhs(&self, x: &F) -> F where for<'a> &'a F: FieldRef<F> {
+        &(&(&(x * x) * x) + &(x * &self.a)) + &self.b
+    }
 
-// pub type Curve = dyn Fn(A, B);
+    // The number of points including infinity, by counting: every x gives two points when
+    // x³ + a·x + b is a nonzero square and one when it is zero. This takes p steps, so it is
+    // only meant for small teaching curves and gives None above MAX_COUNTED_PRIME.
+    pub fn order(&self) -> Option<BigUint> where for<'a> &'a F: FieldRef<F> {
+        if !countable::<F>() {
+            return None;
+        }
+        let mut count = BigUint::from(1u32);
+        let mut x = BigUint::ZERO;
+        while x < *F::modulus() {
+            let rhs = self.rhs(&F::from_biguint(&x));
+            if rhs.is_zero() {
+                count += 1u32;
+            } else if rhs.is_square() {
+                count += 2u32;
+            }
+            x += 1u32;
+        }
+        Some(count)
+    }
 
-#[derive(Debug, PartialEq, Clone)]
-enum XY {
-    Infinity,
-    Finite{ x: X, y: Y },
+    // An odd number of points means no point of order two, x³ + a·x + b having no root, which
+    // is what the complete formulas need. False when the order is neither known nor countable.
+    pub fn has_odd_order(&self) -> bool where for<'a> &'a F: FieldRef<F> {
+        match self.known_order.clone().or_else(|| self.order()) {
+            Some(order) => order.bit(0),
+            None => false,
+        }
+    }
+
+    // every point of the curve, infinity first and then by x, again only for small curves
+    pub fn points(self: &Arc<Self>) -> Option<Vec<Point<F>>> where for<'a> &'a F: FieldRef<F> {
+        if !countable::<F>() {
+            return None;
+        }
+        let mut points = vec![Point { xy: XY::Infinity, curve: Arc::clone(self) }];
+        let mut x = BigUint::ZERO;
+        while x < *F::modulus() {
+            let fx = F::from_biguint(&x);
+            if let Some(y) = self.rhs(&fx).sqrt() {
+                let minus_y = -&y;
+                let (low, high) = if y.to_biguint() <= minus_y.to_biguint() { (y, minus_y) } else { (minus_y, y) };
+                points.push(Point { xy: XY::Finite { x: fx.clone(), y: low.clone() }, curve: Arc::clone(self) });
+                if high != low {
+                    points.push(Point { xy: XY::Finite { x: fx, y: high }, curve: Arc::clone(self) });
+                }
+            }
+            x += 1u32;
+        }
+        Some(points)
+    }
 }
 
-// pub trait Pointe {}
+#[derive(Debug, Clone)]
+pub struct Point<F> {
+    xy: XY<F>,
+    curve: Arc<EllipticCurve<F>>,
+}
 
-// pub struct Infinity;
+impl<F: PrimeField> Point<F> where for<'a> &'a F: FieldRef<F> {
+    pub fn from(curve: Arc<Curve<F>>, x: F, y: F) -> Result<Self, CurveError> {
+        let left = &y * &y;
+        let right = &(&(&(&x * &x) * &x) + &(&x * &curve.a)) + &curve.b;
+        if left != right {
+            return Err(CurveError::NotOnCurve);
+        };
 
-// impl Pointe for Infinity {}
-// pub struct Finite {
-//     x: X,
-//     y: Y,
-// }
+        Ok(Point{ xy: XY::Finite { x, y }, curve: Arc::clone(&curve)})
+    }
 
-// impl Pointe for Finite {}
+    pub fn at_inifity(curve: Arc<Curve<F>>) -> Result<Self, CurveError> {
+        Ok(Point { xy: XY::Infinity, curve: Arc::clone(&curve)})
+    }
+
+    // Multiplication for secret scalars, modelled on S256Point::mul_ct: fixed windows of 4
+    // bits over the complete projective formulas, reading every table entry for each window,
+    // so the additions done don't branch on the scalar. That is all it promises for a generic
+    // field. The BigUint arithmetic of the field types takes time that depends on the values,
+    // the multiplications by 0 and 1 that pick the table entry included, so this is not
+    // constant time. The formulas are only complete on curves of odd order, as prime order
+    // ones are, so other curves and those whose order isn't known get IncompleteFormulas.
+    pub fn mul_ct(&self, scalar: &BigUint) -> Result<Self, CurveError> {
+        if !self.curve.has_odd_order() {
+            return Err(CurveError::IncompleteFormulas);
+        }
+        let base = ProjectivePoint::from_affine(self);
+        let mut table = vec![ProjectivePoint::identity()];
+        for i in 1..16 {
+            let next = table[i - 1].add(&base, &self.curve);
+            table.push(next);
+        }
 
+        let bytes = scalar.to_bytes_be();
+        let mut padded = vec![0u8; field_size::<F>().saturating_sub(bytes.len())];
+        padded.extend(bytes);
+        let mut result = ProjectivePoint::identity();
+        for byte in padded {
+            for nibble in [byte >> 4, byte & 0xf] {
+                for _ in 0..4 {
+                    result = result.add(&result, &self.curve);
+                }
+                result = result.add(&ProjectivePoint::lookup(&table, nibble), &self.curve);
+            }
+        }
+        Ok(result.to_affine(Arc::clone(&self.curve)))
+    }
+
+    // k₁·P₁ + k₂·P₂ + ... in one pass with the doublings shared between all the terms, which
+    // is much cheaper than adding up separate multiplications
+    pub fn multi_mul(curve: Arc<Curve<F>>, terms: &[(&Self, &BigUint)]) -> Result<Self, CurveError> {
+        for (point, _) in terms {
+            if *point.curve != *curve {
+                return Err(CurveError::DifferentCurves);
+            }
+        }
+        let terms: Vec<_> = terms.iter().map(|(point, scalar)| (JacobianPoint::from_affine(point), (*scalar).clone())).collect();
+        Ok(sum_of_products(&*curve, &terms).to_affine(curve))
+    }
+
+    // SEC 1 compressed form: 0x02 or 0x03 for an even or odd y, then x big endian in as many
+    // bytes as the field prime takes. Infinity is the single byte 0x00.
+    pub fn to_compressed(&self) -> Vec<u8> {
+        match &self.xy {
+            XY::Finite { x, y } => {
+                let mut result = vec![if y.to_biguint().bit(0) { 0x03 } else { 0x02 }];
+                result.extend(to_field_bytes::<F>(&x.to_biguint()));
+                result
+            }
+            XY::Infinity => vec![0x00],
+        }
+    }
+
+    // SEC 1 uncompressed form: 0x04, then x and y big endian
+    pub fn to_uncompressed(&self) -> Vec<u8> {
+        match &self.xy {
+            XY::Finite { x, y } => {
+                let mut result = vec![0x04];
+                result.extend(to_field_bytes::<F>(&x.to_biguint()));
+                result.extend(to_field_bytes::<F>(&y.to_biguint()));
+                result
+            }
+            XY::Infinity => vec![0x00],
+        }
+    }
+
+    // either SEC 1 form, told apart by the prefix byte
+    pub fn from_sec(curve: Arc<Curve<F>>, bytes: &[u8]) -> Result<Self, EncodingError> {
+        let size = field_size::<F>();
+        match bytes.first() {
+            Some(0x04) if bytes.len() == 1 + 2 * size => {
+                let coordinate = |bytes: &[u8]| {
+                    let num = BigUint::from_bytes_be(bytes);
+                    if num >= *F::modulus() {
+                        return Err(EncodingError::NotAFieldElement);
+                    }
+                    Ok(F::from_biguint(&num))
+                };
+                let (x, y) = (coordinate(&bytes[1..1 + size])?, coordinate(&bytes[1 + size..])?);
+                Ok(Point::from(curve, x, y)?)
+            }
+            Some(0x04) => Err(EncodingError::InvalidLength { expected: 1 + 2 * size, found: bytes.len() }),
+            _ => Point::from_compressed(curve, bytes),
+        }
+    }
+
+    // finds y from x through the curve equation, picking the root of the right parity
+    pub fn from_compressed(curve: Arc<Curve<F>>, bytes: &[u8]) -> Result<Self, EncodingError> {
+        let expected = 1 + field_size::<F>();
+        match bytes.first() {
+            Some(0x00) if bytes.len() == 1 => Ok(Point { xy: XY::Infinity, curve }),
+            Some(prefix @ (0x02 | 0x03)) if bytes.len() == expected => {
+                let num = BigUint::from_bytes_be(&bytes[1..]);
+                if num >= *F::modulus() {
+                    return Err(EncodingError::NotAFieldElement);
+                }
+                let x = F::from_biguint(&num);
+                let alpha = &(&(&(&x * &x) * &x) + &(&x * &curve.a)) + &curve.b;
+                let beta = alpha.sqrt().ok_or(CurveError::NotOnCurve)?;
+                let odd = *prefix == 0x03;
+                let y = if beta.to_biguint().bit(0) == odd { beta } else { -&beta };
+                // y = 0 has no odd counterpart
+                if y.to_biguint().bit(0) != odd {
+                    return Err(CurveError::NotOnCurve.into());
+                }
+                Ok(Point { xy: XY::Finite { x, y }, curve })
+            }
+            Some(0x02 | 0x03) => Err(EncodingError::InvalidLength { expected, found: bytes.len() }),
+            Some(0x00) => Err(EncodingError::InvalidLength { expected: 1, found: bytes.len() }),
+            Some(prefix) => Err(EncodingError::InvalidPrefix(*prefix)),
+            None => Err(EncodingError::InvalidLength { expected, found: 0 }),
+        }
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.xy == XY::Infinity
+    }
+
+    // the affine coordinates, None at infinity
+    pub fn xy(&self) -> Option<(&F, &F)> {
+        match &self.xy {
+            XY::Finite { x, y } => Some((x, y)),
+            XY::Infinity => None,
+        }
+    }
+
+    // The smallest n > 0 with n·P = infinity. By Lagrange it divides the curve order, so
+    // starting from that, prime factors are divided out for as long as the multiple stays
+    // at infinity. Counts the curve's points, so None like EllipticCurve::order on large ones.
+    pub fn order(&self) -> Option<BigUint> {
+        let mut order = self.curve.order()?;
+        for factor in prime_factors(&order) {
+            while &order % &factor == BigUint::ZERO && (self * (&order / &factor)).is_infinity() {
+                order /= &factor;
+            }
+        }
+        Some(order)
+    }
+
+    fn negate(&self) -> Self {
+        match &self.xy {
+            XY::Finite { x, y } => Point { xy: XY::Finite { x: x.clone(), y: -y }, curve: Arc::clone(&self.curve) },
+            XY::Infinity => self.clone(),
+        }
+    }
+
+    fn add(curve: Arc<Curve<F>>, p1: &Self, p2: &Self) -> Self {
+        let sum = JacobianPoint::from_affine(p1).add(&JacobianPoint::from_affine(p2), &curve);
+        sum.to_affine(curve)
+    }
+}
+
+// Jacobian coordinates (X, Y, Z) stand for the affine point (X/Z², Y/Z³). Adding and doubling
+// them needs no field inversion, so a chain of operations only pays for one inversion when
+// the result is converted back to affine. Z = 0 is the point at infinity.
 #[derive(Debug, Clone)]
-pub struct EllipticCurve {
-    a: A,
-    b: B,
+struct JacobianPoint<F> {
+    x: F,
+    y: F,
+    z: F,
 }
 
-impl EllipticCurve {
-    pub fn new(a: A, b: B) -> Self {
-        EllipticCurve { a, b }
+impl<F: PrimeField> JacobianPoint<F> where for<'a> &'a F: FieldRef<F> {
+    fn from_affine(point: &Point<F>) -> Self {
+        match &point.xy {
+            XY::Finite { x, y } => JacobianPoint { x: x.clone(), y: y.clone(), z: F::one() },
+            XY::Infinity => JacobianPoint::infinity(),
+        }
     }
 
-    pub fn a(&self) -> &A {
-        &self.a
+    fn is_infinity(&self) -> bool {
+        self.z.is_zero()
     }
 
-    pub fn b(&self) -> &B {
-        &self.b
+    fn to_affine(&self, curve: Arc<Curve<F>>) -> Point<F> {
+        if self.is_infinity() {
+            return Point { xy: XY::Infinity, curve };
+        }
+        let z_inv = self.z.inverse();
+        let z_inv2 = &z_inv * &z_inv;
+        let x = &self.x * &z_inv2;
+        let y = &(&self.y * &z_inv2) * &z_inv;
+        Point { xy: XY::Finite { x, y }, curve }
+    }
+}
+
+impl<F: PrimeField> JacobianGroup for JacobianPoint<F> where for<'a> &'a F: FieldRef<F> {
+    type Curve = EllipticCurve<F>;
+
+    fn infinity() -> Self {
+        JacobianPoint { x: F::one(), y: F::one(), z: F::zero() }
+    }
+
+    fn neg(&self) -> Self {
+        JacobianPoint { x: self.x.clone(), y: -&self.y, z: self.z.clone() }
+    }
+
+    fn double(&self, curve: &EllipticCurve<F>) -> Self {
+        if self.is_infinity() || self.y.is_zero() {
+            return JacobianPoint::infinity();
+        }
+        let xx = &self.x * &self.x;
+        let yy = &self.y * &self.y;
+        let zz = &self.z * &self.z;
+        // s = 4·X·Y², m = 3·X² + a·Z⁴
+        let s = &small::<F>(4) * &(&self.x * &yy);
+        let m = &(&small::<F>(3) * &xx) + &(&curve.a * &(&zz * &zz));
+        let x = &(&m * &m) - &(&s + &s);
+        let y = &(&m * &(&s - &x)) - &(&small::<F>(8) * &(&yy * &yy));
+        let z = &small::<F>(2) * &(&self.y * &self.z);
+        JacobianPoint { x, y, z }
+    }
+
+    fn add(&self, other: &Self, curve: &EllipticCurve<F>) -> Self {
+        if self.is_infinity() {
+            return other.clone();
+        }
+        if other.is_infinity() {
+            return self.clone();
+        }
+        let z1z1 = &self.z * &self.z;
+        let z2z2 = &other.z * &other.z;
+        let u1 = &self.x * &z2z2;
+        let u2 = &other.x * &z1z1;
+        let s1 = &(&self.y * &other.z) * &z2z2;
+        let s2 = &(&other.y * &self.z) * &z1z1;
+
+        if u1 == u2 {
+            if s1 != s2 {
+                return JacobianPoint::infinity();
+            }
+            return self.double(curve);
+        }
+
+        let h = &u2 - &u1;
+        let r = &s2 - &s1;
+        let hh = &h * &h;
+        let hhh = &h * &hh;
+        let v = &u1 * &hh;
+        let x = &(&(&r * &r) - &hhh) - &(&v + &v);
+        let y = &(&r * &(&v - &x)) - &(&s1 * &hhh);
+        let z = &(&self.z * &other.z) * &h;
+        JacobianPoint { x, y, z }
     }
 }
 
+// Homogeneous projective coordinates (X : Y : Z) for the affine point (X/Z, Y/Z), with the
+// complete addition formula for any a of Renes, Costello and Batina (2015), which also doubles
+// and takes the identity (0 : 1 : 0) without special cases, see Point::mul_ct
 #[derive(Debug, Clone)]
-pub struct Point {
-    xy: XY,
-    curve: Rc<EllipticCurve>,
+struct ProjectivePoint<F> {
+    x: F,
+    y: F,
+    z: F,
 }
 
-impl Point {
-    pub fn from(curve: Rc<Curve>, x: X, y: Y) -> Result<Point, &'static str> {
-        let left = y.pow(&BigInt::from(2));
-        let right = (&(&x.pow(&BigInt::from(3)) + &(&x * &curve.a)?)? + &curve.b)?;
-        if left != right {
-            return Err("(x, y) is not on the curve");
-        };
+impl<F: PrimeField> ProjectivePoint<F> where for<'a> &'a F: FieldRef<F> {
+    fn identity() -> Self {
+        ProjectivePoint { x: F::zero(), y: F::one(), z: F::zero() }
+    }
 
-        Ok(Point{ xy: XY::Finite { x, y }, curve: Rc::clone(&curve)})
+    fn from_affine(point: &Point<F>) -> Self {
+        match &point.xy {
+            XY::Finite { x, y } => ProjectivePoint { x: x.clone(), y: y.clone(), z: F::one() },
+            XY::Infinity => ProjectivePoint::identity(),
+        }
     }
 
-    pub fn at_inifity(curve: Rc<Curve>) -> Result<Point, &'static str> {
-        Ok(Point { xy: XY::Infinity, curve: Rc::clone(&curve)})
+    fn to_affine(&self, curve: Arc<Curve<F>>) -> Point<F> {
+        if self.z.is_zero() {
+            return Point { xy: XY::Infinity, curve };
+        }
+        let z_inv = self.z.inverse();
+        Point { xy: XY::Finite { x: &self.x * &z_inv, y: &self.y * &z_inv }, curve }
     }
 
-    fn add(curve: Rc<Curve>, p1: &Point, p2: &Point) -> Result<Point, &'static str> {
-        let exp = BigInt::from(2);
+    // algorithm 1 of the paper
+    fn add(&self, other: &Self, curve: &Curve<F>) -> Self {
+        let b3 = &small::<F>(3) * &curve.b;
+        let t0 = &self.x * &other.x;
+        let t1 = &self.y * &other.y;
+        let t2 = &self.z * &other.z;
+        let t3 = &(&self.x + &self.y) * &(&other.x + &other.y);
+        let t3 = &t3 - &(&t0 + &t1);
+        let t4 = &(&self.x + &self.z) * &(&other.x + &other.z);
+        let t4 = &t4 - &(&t0 + &t2);
+        let t5 = &(&self.y + &self.z) * &(&other.y + &other.z);
+        let t5 = &t5 - &(&t1 + &t2);
+        let z3 = &(&curve.a * &t4) + &(&b3 * &t2);
+        let x3 = &t1 - &z3;
+        let z3 = &t1 + &z3;
+        let y3 = &x3 * &z3;
+        let t1 = &(&t0 + &t0) + &t0;
+        let t2 = &curve.a * &t2;
+        let t4 = &b3 * &t4;
+        let t1 = &t1 + &t2;
+        let t2 = &curve.a * &(&t0 - &t2);
+        let t4 = &t4 + &t2;
+        let y3 = &y3 + &(&t1 * &t4);
+        let x3 = &(&t3 * &x3) - &(&t5 * &t4);
+        let z3 = &(&t5 * &z3) + &(&t3 * &t1);
+        ProjectivePoint { x: x3, y: y3, z: z3 }
+    }
 
-        let (x1, y1) = match &p1.xy {
-            XY::Finite { x, y } => (x, y),
-            XY::Infinity => return Ok(p2.clone()),
-        };
+    // Reads every entry and keeps the one at `index` by multiplying the others by zero, as
+    // there is no access to the field's limbs here to select with masks
+    fn lookup(table: &[Self], index: u8) -> Self {
+        let mut result = ProjectivePoint { x: F::zero(), y: F::zero(), z: F::zero() };
+        for (i, entry) in table.iter().enumerate() {
+            let diff = (i as u8 ^ index) as u32;
+            let flag = small::<F>(((diff | diff.wrapping_neg()) >> 31) ^ 1);
+            result = ProjectivePoint {
+                x: &result.x + &(&entry.x * &flag),
+                y: &result.y + &(&entry.y * &flag),
+                z: &result.z + &(&entry.z * &flag),
+            };
+        }
+        result
+    }
+}
 
-        let (x2, y2) = match &p2.xy {
-            XY::Finite { x, y} => (x, y),
-            XY::Infinity => return Ok(p1.clone()),
-        };
+// The group operations strauss and pippenger need, so the generic Jacobian points and the
+// secp256k1 ones share a single copy of them. `Curve` is what the formulas need to know about
+// the curve, which is nothing when its constants are fixed.
+pub trait JacobianGroup: Clone {
+    type Curve;
+
+    fn infinity() -> Self;
+    fn neg(&self) -> Self;
+    fn double(&self, curve: &Self::Curve) -> Self;
+    fn add(&self, other: &Self, curve: &Self::Curve) -> Self;
+}
+
+// Strauss for a few terms and Pippenger for many
+pub fn sum_of_products<P: JacobianGroup>(curve: &P::Curve, terms: &[(P, BigUint)]) -> P {
+    if terms.len() < PIPPENGER_THRESHOLD {
+        strauss(curve, terms)
+    } else {
+        pippenger(curve, terms)
+    }
+}
+
+// Strauss-Shamir: a table of odd multiples P, 3P, 5P, ... per point and a single chain of
+// doublings, adding in table entries as the wNAF digits of each scalar come up
+pub fn strauss<P: JacobianGroup>(curve: &P::Curve, terms: &[(P, BigUint)]) -> P {
+    let mut tables = Vec::with_capacity(terms.len());
+    let mut digits = Vec::with_capacity(terms.len());
+    for (point, scalar) in terms {
+        let twice = point.double(curve);
+        let mut table = vec![point.clone()];
+        for i in 1..1 << (WNAF_WIDTH - 2) {
+            let next = table[i - 1].add(&twice, curve);
+            table.push(next);
+        }
+        tables.push(table);
+        digits.push(wnaf(scalar, WNAF_WIDTH));
+    }
+
+    let mut result = P::infinity();
+    let len = digits.iter().map(Vec::len).max().unwrap_or(0);
+    for i in (0..len).rev() {
+        result = result.double(curve);
+        for (table, naf) in tables.iter().zip(&digits) {
+            match naf.get(i) {
+                Some(&digit) if digit > 0 => result = result.add(&table[(digit / 2) as usize], curve),
+                Some(&digit) if digit < 0 => result = result.add(&table[(-digit / 2) as usize].neg(), curve),
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+// Pippenger's bucket method: for every window of bits, each point is added once into the
+// bucket of its scalar's digit, and the buckets are then weighted with a running sum. The
+// cost grows like n / log n, so it wins over Strauss for large batches.
+pub fn pippenger<P: JacobianGroup>(curve: &P::Curve, terms: &[(P, BigUint)]) -> P {
+    let width = pippenger_window(terms.len());
+    let bits = terms.iter().map(|(_, scalar)| scalar.bits()).max().unwrap_or(0);
+
+    let mut result = P::infinity();
+    for window in (0..bits.div_ceil(width as u64)).rev() {
+        for _ in 0..width {
+            result = result.double(curve);
+        }
+        let mut buckets = vec![P::infinity(); (1 << width) - 1];
+        for (point, scalar) in terms {
+            let digit = scalar_window(scalar, window * width as u64, width);
+            if digit > 0 {
+                buckets[digit - 1] = buckets[digit - 1].add(point, curve);
+            }
+        }
+        // the running sum holds buckets j and above, so adding it once per step counts
+        // bucket j exactly j times
+        let mut running = P::infinity();
+        let mut window_sum = P::infinity();
+        for bucket in buckets.iter().rev() {
+            running = running.add(bucket, curve);
+            window_sum = window_sum.add(&running, curve);
+        }
+        result = result.add(&window_sum, curve);
+    }
+    result
+}
 
-        if x1 == x2 && y1 != y2 {
-            Ok(Point::at_inifity(Rc::clone(&curve)))?
-        } else {
-            if y1 == &(&FieldElement::new(BigUint::ZERO, y1.prime()) * x1)? {
-                return Ok(Point::at_inifity(Rc::clone(&curve)))?;
-            } 
-            if x1 == x2 && y1 == y2 {
-                let field_elem_2 = FieldElement::new(BigUint::from(2u32), Rc::clone(&x1.prime()));
-                let field_elem_3 = FieldElement::new(BigUint::from(3u32), Rc::clone(&x1.prime()));
-                let s = (&(&(&field_elem_3 * &x1.pow(&exp))? + &curve.a)? / &(&field_elem_2 * y1)?)?;
-                let x = (&s.pow(&BigInt::from(2)) - &(&field_elem_2 * x1)?)?;
-                let y = (&(&s * &(x1 - &x)?)? - y1)?;
-                Ok(Point::from(Rc::clone(&curve), x, y))?
+// Width-w non-adjacent form, least significant digit first: every nonzero digit is odd and
+// smaller than 2^(w-1) in absolute value, and any w consecutive digits hold at most one of
+// them, so multiplication needs far fewer additions than with plain binary.
+pub fn wnaf(scalar: &BigUint, width: u32) -> Vec<i64> {
+    let modulus = 1i64 << width;
+    let mut k = scalar.clone();
+    let mut digits = Vec::with_capacity(k.bits() as usize + 1);
+    while k > BigUint::ZERO {
+        let mut digit = 0;
+        if k.bit(0) {
+            digit = (k.iter_u64_digits().next().unwrap_or(0) & (modulus as u64 - 1)) as i64;
+            if digit >= modulus / 2 {
+                digit -= modulus;
+            }
+            if digit > 0 {
+                k -= digit as u64;
             } else {
-                let s = (&(y2 - y1)? / &(x2 - x1)?)?;
-                let x = (&(&s.pow(&exp) - x1)? - x2)?;
-                let y = (&(&s * &(x1 - &x)?)? - y1)?;
-                Ok(Point::from(Rc::clone(&curve), x, y))?
-            }   
+                k += (-digit) as u64;
+            }
         }
+        digits.push(digit);
+        k >>= 1u32;
     }
+    digits
 }
 
-// impl Point {
-//     fn new(xy: XY, curve: Rc<EllipticCurve>) -> Self {
-//         if let XY::Finite{x, y} = xy {
-//             if y.pow(&BigInt::from(2)) != &(x.pow(&BigInt::from(3)) + x*a) + &b {
-//                     panic!("({:#?}, {:#?}) is not on the curve", x, y);     
-//             };
-//         };
+// bucket window width for a Pippenger multiplication of `count` terms
+pub fn pippenger_window(count: usize) -> u32 {
+    count.max(1).ilog2().saturating_sub(1).clamp(2, 16)
+}
 
-//         Point { xy, a, b }
-//     }
+// the `width` bits of the scalar starting at bit `start`
+pub fn scalar_window(scalar: &BigUint, start: u64, width: u32) -> usize {
+    (0..width as u64).filter(|i| scalar.bit(start + i)).map(|i| 1 << i).sum()
+}
 
-//     fn at_inifity(a: Rc<FieldElement>, b: Rc<FieldElement>) -> Self {
-//         Point::new(XY::Infinity, a, b)
-//     }
+// the distinct prime factors, by trial division
+fn prime_factors(num: &BigUint) -> Vec<BigUint> {
+    let mut factors = Vec::new();
+    let mut rest = num.clone();
+    let mut divisor = BigUint::from(2u32);
+    while &divisor * &divisor <= rest {
+        if &rest % &divisor == BigUint::ZERO {
+            factors.push(divisor.clone());
+            while &rest % &divisor == BigUint::ZERO {
+                rest /= &divisor;
+            }
+        }
+        divisor += 1u32;
+    }
+    if rest > BigUint::from(1u32) {
+        factors.push(rest);
+    }
+    factors
+}
 
-//     fn from_slope(x1: Rc<FieldElement>, y1: Rc<FieldElement>, x2: Rc<FieldElement>, y2: Rc<FieldElement>, a: Rc<FieldElement>, b: Rc<FieldElement>) -> Self {
-//         let exp = BigInt::from(2);
-//         if x1 == x2 && y1 == y2 {
-//             let field_elem_2 = FieldElement::new(BigUint::from(2u32), Rc::clone(x1.prime()));
-//             let field_elem_3 = FieldElement::new(BigUint::from(3u32), Rc::clone(x1.prime()));
-//             let s = (field_elem_3 * x1.pow(&exp) + Rc::clone(&a)) / (field_elem_2 * *y1);
-//             let x = s.pow(&BigInt::from(2)) - field_elem_2 * *x1;
-//             let y = s.clone() * (*x1 - x) - *y1;
-//             Point {xy: XY::Finite{x: Rc::new(x), y: Rc::new(y)}, a, b}
-//         } else {
-//            let s = (*y2 - *y1) / (*x2 - *x1);
-//            let x = s.pow(&exp) - *x1 - *x2;
-//            let y = (s * (*x1 - x)) - *y1;
-//            Point {xy: XY::Finite{x: Rc::new(x), y: Rc::new(y)}, a, b}
-//         }
-//     }
-// }
+fn countable<F: PrimeField>() -> bool {
+    *F::modulus() <= BigUint::from(MAX_COUNTED_PRIME)
+}
 
+// bytes needed for an element of F
+fn field_size<F: PrimeField>() -> usize {
+    F::modulus().bits().div_ceil(8) as usize
+}
 
+fn to_field_bytes<F: PrimeField>(num: &BigUint) -> Vec<u8> {
+    let bytes = num.to_bytes_be();
+    let mut result = vec![0u8; field_size::<F>() - bytes.len()];
+    result.extend(bytes);
+    result
+}
+
+// small constants, reduced so fields of order 2 or 3 don't panic
+fn small<F: PrimeField>(num: u32) -> F {
+    F::from_biguint(&BigUint::from(num))
+}
 
-impl PartialEq for Point {
+// the endomorphism is only a shortcut, curves with the same a and b are the same curve
+impl<F: PartialEq> PartialEq for EllipticCurve<F> {
     fn eq(&self, other: &Self) -> bool {
-        self.xy == other.xy && self.curve.a() == other.curve.a() && self.curve.b() == other.curve.b()
+        self.a == other.a && self.b == other.b
+    }
+}
+
+impl<F: PrimeField> PartialEq for Point<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.xy == other.xy && self.curve == other.curve
     }
 }
 
@@ -324,15 +714,15 @@ impl PartialEq for Point {
 //     }
 // }
 
-impl<'a> Add<&'a Point> for &'a Point {
-    type Output = Result<Point, &'static str>;
+impl<'a, F: PrimeField> Add<&'a Point<F>> for &'a Point<F> where for<'b> &'b F: FieldRef<F> {
+    type Output = Result<Point<F>, CurveError>;
 
-    fn add(self, other: &'a Point) -> Self::Output {
-        if self.curve.a() != other.curve.a() || self.curve.b() != other.curve.b() {
-            return Err("Points are not in the same curve");
+    fn add(self, other: &'a Point<F>) -> Self::Output {
+        if self.curve != other.curve {
+            return Err(CurveError::DifferentCurves);
         }
 
-        Point::add(Rc::clone(&self.curve), self,other)
+        Ok(Point::add(Arc::clone(&self.curve), self,other))
         // match self.xy {
         //     XY::Infinity => other.clone(),
         //     XY::Finite{x: x1, y: y1} => {
@@ -383,8 +773,8 @@ impl<'a> Add<&'a Point> for &'a Point {
 //     }
 // }
 
-impl Mul<BigUint> for &Point {
-    type Output = Result<Point, &'static str>;
+impl<F: PrimeField> Mul<BigUint> for &Point<F> where for<'a> &'a F: FieldRef<F> {
+    type Output = Point<F>;
 
     // fn mul(self, other: u32) -> Point {
         
@@ -394,95 +784,133 @@ impl Mul<BigUint> for &Point {
     //     };
     //     prod
     // }   
+    // double-and-add, which branches on the scalar bits; secret scalars on secp256k1 go
+    // through S256Point::mul_ct instead
     fn mul(self, other: BigUint) -> Self::Output {
-        let mut result = Point::at_inifity(Rc::clone(&self.curve))?;
-        let mut current = self.clone();
-        let mut coef = other;
-        let zero = BigUint::ZERO;
-        let one = BigUint::from(1u32);
-        while coef > zero {
-            let current1 = current.clone();
-            let current2 = current;
-            if coef.clone().bitand(&one) == one {
-                result = (&result + &current1)?;
-            }
-            // if coef.clone().bitand(&one) == one {
-            //     result = match &result + &current1 {
-            //         Ok(point) => point,
-            //         Err(err) => return Err(err),
-            //     }
-            // }
-            current = (&current1 + &current2)?;
-            coef >>= 1u32;
-        };
-
-        Ok(result)
+        match &self.curve.endomorphism {
+            Some(endomorphism) if cfg!(feature = "glv") => glv_mul(self, endomorphism, &other),
+            _ => double_and_add(self, other),
+        }
     }   
 }
 
+fn double_and_add<F: PrimeField>(point: &Point<F>, scalar: BigUint) -> Point<F> where for<'a> &'a F: FieldRef<F> {
+    let mut result = JacobianPoint::infinity();
+    let mut current = JacobianPoint::from_affine(point);
+    let mut coef = scalar;
+    let zero = BigUint::ZERO;
+    let one = BigUint::from(1u32);
+    while coef > zero {
+        if coef.clone().bitand(&one) == one {
+            result = result.add(&current, &point.curve);
+        }
+        current = current.double(&point.curve);
+        coef >>= 1u32;
+    };
+
+    result.to_affine(Arc::clone(&point.curve))
+}
+
+// With φ(x, y) = (β·x, y) = λ·(x, y), k·P = k₁·P + k₂·φ(P) with k₁ and k₂ half as long as k,
+// which halves the number of doublings
+fn glv_mul<F: PrimeField>(point: &Point<F>, endomorphism: &Endomorphism<F>, scalar: &BigUint) -> Point<F> where for<'a> &'a F: FieldRef<F> {
+    let (x, y) = match &point.xy {
+        XY::Finite { x, y } => (x, y),
+        XY::Infinity => return point.clone(),
+    };
+    let endo = Point { xy: XY::Finite { x: x * &endomorphism.beta, y: y.clone() }, curve: Arc::clone(&point.curve) };
+    let (k1, k2) = (endomorphism.decompose)(scalar);
+    let p1 = if k1.sign() == Sign::Minus { point.negate() } else { point.clone() };
+    let p2 = if k2.sign() == Sign::Minus { endo.negate() } else { endo };
+    let terms = [
+        (JacobianPoint::from_affine(&p1), k1.magnitude().clone()),
+        (JacobianPoint::from_affine(&p2), k2.magnitude().clone()),
+    ];
+    strauss(&*point.curve, &terms).to_affine(Arc::clone(&point.curve))
+}
+
 #[cfg(test)]
 mod tests {
+    use num_bigint::{BigInt, RandBigInt};
+
     use super::*;
+    use crate::finite_field::FieldElement;
+    use crate::named_curve::{NamedCurve, Secp256k1Prime, F223};
+
+    type F = FieldElement<F223>;
 
     #[test]
     fn test_new_valid_points() {
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
         
-        let x1 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let point = Point::from(Rc::clone(&curve), x1, y1);
+        let x1 = F::new(BigUint::from(192u32));
+        let y1 = F::new(BigUint::from(105u32));
+        let point = Point::from(Arc::clone(&curve), x1, y1);
         assert!(point.is_ok());
 
-        let x2 = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(56u32), Rc::clone(&prime));
-        let point = Point::from(Rc::clone(&curve), x2, y2);
+        let x2 = F::new(BigUint::from(17u32));
+        let y2 = F::new(BigUint::from(56u32));
+        let point = Point::from(Arc::clone(&curve), x2, y2);
         assert!(point.is_ok());
 
-        let x3 = FieldElement::new(BigUint::from(1u32), Rc::clone(&prime));
-        let y3 = FieldElement::new(BigUint::from(193u32), Rc::clone(&prime));
-        let point = Point::from(Rc::clone(&curve), x3, y3);
+        let x3 = F::new(BigUint::from(1u32));
+        let y3 = F::new(BigUint::from(193u32));
+        let point = Point::from(Arc::clone(&curve), x3, y3);
         assert!(point.is_ok());
     }
 
     #[test]
     fn test_new_invalid_points() {
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
         
-        let x1 = FieldElement::new(BigUint::from(200u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(119u32), Rc::clone(&prime));
-        let point = Point::from(Rc::clone(&curve), x1, y1);
-        assert!(point.is_err());
+        let x1 = F::new(BigUint::from(200u32));
+        let y1 = F::new(BigUint::from(119u32));
+        let point = Point::from(Arc::clone(&curve), x1, y1);
+        assert_eq!(point, Err(CurveError::NotOnCurve));
+
+        let x2 = F::new(BigUint::from(42u32));
+        let y2 = F::new(BigUint::from(99u32));
+        let point = Point::from(Arc::clone(&curve), x2, y2);
+        assert_eq!(point, Err(CurveError::NotOnCurve));
+    }
 
-        let x2 = FieldElement::new(BigUint::from(42u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(99u32), Rc::clone(&prime));
-        let point = Point::from(Rc::clone(&curve), x2, y2);
-        assert!(point.is_err());
+    #[test]
+    fn test_singular_curve() {
+        // y² = x³ has a cusp and y² = x³ - 3x + 2 = (x - 1)²(x + 2) a node
+        let zero = F::new(BigUint::ZERO);
+        assert_eq!(EllipticCurve::try_new(zero.clone(), zero.clone()), Err(CurveError::Singular));
+        let (a, b) = (F::new(BigUint::from(220u32)), F::new(BigUint::from(2u32)));
+        assert_eq!(EllipticCurve::try_new(a, b), Err(CurveError::Singular));
+        assert!(EllipticCurve::try_new(zero, F::new(BigUint::from(7u32))).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "singular")]
+    fn test_new_singular_curve_panics() {
+        EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::ZERO));
     }
 
     #[test]
     fn test_eq() {
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
         
-        let x1 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1);
+        let x1 = F::new(BigUint::from(192u32));
+        let y1 = F::new(BigUint::from(105u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1);
 
-        let x2 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2);
+        let x2 = F::new(BigUint::from(192u32));
+        let y2 = F::new(BigUint::from(105u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2);
 
-        let x3 = FieldElement::new(BigUint::from(1u32), Rc::clone(&prime));
-        let y3 = FieldElement::new(BigUint::from(193u32), Rc::clone(&prime));
-        let p3 = Point::from(Rc::clone(&curve), x3, y3);
+        let x3 = F::new(BigUint::from(1u32));
+        let y3 = F::new(BigUint::from(193u32));
+        let p3 = Point::from(Arc::clone(&curve), x3, y3);
 
         assert_eq!(p1, p2);
         assert_ne!(p1, p3);
@@ -490,16 +918,15 @@ mod tests {
 
     #[test]
     fn test_add_inf() {
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
 
-        let p_inf = Point::at_inifity(Rc::clone(&curve)).unwrap();
+        let p_inf = Point::at_inifity(Arc::clone(&curve)).unwrap();
         
-        let x1 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(192u32));
+        let y1 = F::new(BigUint::from(105u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
         assert_eq!((&p_inf + &p1).unwrap(), p1.clone());
         assert_eq!((&p1 + &p_inf).unwrap(), p1.clone());
@@ -513,52 +940,51 @@ mod tests {
     #[test]
     fn test_add() {
         // (192, 105, 17, 56, 170, 142)
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
         
-        let x1 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(192u32));
+        let y1 = F::new(BigUint::from(105u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(56u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(17u32));
+        let y2 = F::new(BigUint::from(56u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
-        let x3 = FieldElement::new(BigUint::from(170u32), Rc::clone(&prime));
-        let y3 = FieldElement::new(BigUint::from(142u32), Rc::clone(&prime));
-        let p3 = Point::from(Rc::clone(&curve), x3, y3).unwrap();
+        let x3 = F::new(BigUint::from(170u32));
+        let y3 = F::new(BigUint::from(142u32));
+        let p3 = Point::from(Arc::clone(&curve), x3, y3).unwrap();
 
         assert_eq!((&p1 + &p2).unwrap(), p3);
 
         // (47, 71, 117, 141, 60, 139)
-        let x1 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(47u32));
+        let y1 = F::new(BigUint::from(71u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(117u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(141u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(117u32));
+        let y2 = F::new(BigUint::from(141u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
-        let x3 = FieldElement::new(BigUint::from(60u32), Rc::clone(&prime));
-        let y3 = FieldElement::new(BigUint::from(139u32), Rc::clone(&prime));
-        let p3 = Point::from(Rc::clone(&curve), x3, y3).unwrap();
+        let x3 = F::new(BigUint::from(60u32));
+        let y3 = F::new(BigUint::from(139u32));
+        let p3 = Point::from(Arc::clone(&curve), x3, y3).unwrap();
 
         assert_eq!((&p1 + &p2).unwrap(), p3);
 
         // (143, 98, 76, 66, 47, 71)
-        let x1 = FieldElement::new(BigUint::from(143u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(98u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(143u32));
+        let y1 = F::new(BigUint::from(98u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(76u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(66u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(76u32));
+        let y2 = F::new(BigUint::from(66u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
-        let x3 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y3 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p3 = Point::from(Rc::clone(&curve), x3, y3).unwrap();
+        let x3 = F::new(BigUint::from(47u32));
+        let y3 = F::new(BigUint::from(71u32));
+        let p3 = Point::from(Arc::clone(&curve), x3, y3).unwrap();
 
         assert_eq!((&p1 + &p2).unwrap(), p3);
     }
@@ -566,176 +992,340 @@ mod tests {
     #[test]
     fn test_mul() {
         // (2, 192, 105, 49, 71)
-        let prime = Rc::new(BigUint::from(223u32));
-        let a = FieldElement::new(BigUint::ZERO, Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        let curve = Rc::new(EllipticCurve::new(a, b));
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
         
-        let x1 = FieldElement::new(BigUint::from(192u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(105u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(192u32));
+        let y1 = F::new(BigUint::from(105u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(49u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(49u32));
+        let y2 = F::new(BigUint::from(71u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
         let scalar = BigUint::from(2u32);
 
-        assert_eq!((&p1 * scalar).unwrap(), p2);
+        assert_eq!(&p1 * scalar, p2);
 
         // (2, 143, 98, 64, 168)
-        let x1 = FieldElement::new(BigUint::from(143u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(98u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(143u32));
+        let y1 = F::new(BigUint::from(98u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(64u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(168u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(64u32));
+        let y2 = F::new(BigUint::from(168u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
         let scalar = BigUint::from(2u32);
 
-        assert_eq!((&p1 * scalar).unwrap(), p2);
+        assert_eq!(&p1 * scalar, p2);
 
         // (2, 47, 71, 36, 111)
-        let x1 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(47u32));
+        let y1 = F::new(BigUint::from(71u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(36u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(111u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(36u32));
+        let y2 = F::new(BigUint::from(111u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
         let scalar = BigUint::from(2u32);
 
-        assert_eq!((&p1 * scalar).unwrap(), p2);
+        assert_eq!(&p1 * scalar, p2);
 
         // (4, 47, 71, 194, 51)
-        let x1 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(47u32));
+        let y1 = F::new(BigUint::from(71u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(194u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(51u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(194u32));
+        let y2 = F::new(BigUint::from(51u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
         let scalar = BigUint::from(4u32);
 
-        assert_eq!((&p1 * scalar).unwrap(), p2);
+        assert_eq!(&p1 * scalar, p2);
 
         // (8, 47, 71, 116, 55)
-        let x1 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
+        let x1 = F::new(BigUint::from(47u32));
+        let y1 = F::new(BigUint::from(71u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
 
-        let x2 = FieldElement::new(BigUint::from(116u32), Rc::clone(&prime));
-        let y2 = FieldElement::new(BigUint::from(55u32), Rc::clone(&prime));
-        let p2 = Point::from(Rc::clone(&curve), x2, y2).unwrap();
+        let x2 = F::new(BigUint::from(116u32));
+        let y2 = F::new(BigUint::from(55u32));
+        let p2 = Point::from(Arc::clone(&curve), x2, y2).unwrap();
 
         let scalar = BigUint::from(8u32);
 
-        assert_eq!((&p1 * scalar).unwrap(), p2);
+        assert_eq!(&p1 * scalar, p2);
 
         // (21, 47, 71, None, None)
-        let x1 = FieldElement::new(BigUint::from(47u32), Rc::clone(&prime));
-        let y1 = FieldElement::new(BigUint::from(71u32), Rc::clone(&prime));
-        let p1 = Point::from(Rc::clone(&curve), x1, y1).unwrap();
-
-        let p2 = Point::at_inifity(Rc::clone(&curve)).unwrap();
-
-        let scalar = BigUint::from(21u32);
-
-        assert_eq!((&p1 * scalar).unwrap(), p2);
-
-    }
-
-    // #[test]
-    // fn test_s256_point_generator() {
-    //     let g = S256Point::generator();
-    //     let p = &g * &N_S256;
-    //     assert_eq!(p.0.xy, XY::Infinity);
-    // }
-
-    // #[test]
-    // fn test_s256_verify() {
-    //     let x = BigUint::from_bytes_be(&hex::decode("887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c").unwrap());
-    //     let y = BigUint::from_bytes_be(&hex::decode("61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap());
-    //     let point = S256Point::new(CoordsS256::Finite(S256Field::new(x), S256Field::new(y)));
-    //     let z = BigUint::from_bytes_be(&hex::decode("ec208baa0fc1c19f708a9ca96fdeff3ac3f230bb4a7ba4aede4942ad003c0f60").unwrap());
-    //     let r = BigUint::from_bytes_be(&hex::decode("ac8d1c87e51d0d441be8b3dd5b05c8795b48875dffe00b7ffcfac23010d3a395").unwrap());
-    //     let s = BigUint::from_bytes_be(&hex::decode("068342ceff8935ededd102dd876ffd6ba72d6a427a3edb13d26eb0781cb423c4").unwrap());
-    //     let sig = Signature::new(r, s);
-    //     assert!(point.verify(&z, &sig));
-    //     let z = BigUint::from_bytes_be(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap());
-    //     let r = BigUint::from_bytes_be(&hex::decode("00eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c").unwrap());
-    //     let s = BigUint::from_bytes_be(&hex::decode("c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab6").unwrap());
-    //     let sig = Signature::new(r, s);
-    //     assert!(point.verify(&z, &sig));
-    // }
-
-    // #[test]
-    // fn test_s256_sec() {
-    //     let mut uncompressed = "049d5ca49670cbe4c3bfa84c96a8c87df086c6ea6a24ba6b809c9de234496808d56fa15cc7f3d38cda98dee2419f415b7513dde1301f8643cd9245aea7f3f911f9";
-    //     let mut compressed = "039d5ca49670cbe4c3bfa84c96a8c87df086c6ea6a24ba6b809c9de234496808d5";
-    //     let g = S256Point::generator();
-    //     let mut coef = BigUint::from(999u32).pow(3);
-    //     let mut point = &g * &coef;
-    //     assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
-    //     assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
-
-    //     coef = BigUint::from(123u32);
-    //     uncompressed = "04a598a8030da6d86c6bc7f2f5144ea549d28211ea58faa70ebf4c1e665c1fe9b5204b5d6f84822c307e4b4a7140737aec23fc63b65b35f86a10026dbd2d864e6b";
-    //     compressed = "03a598a8030da6d86c6bc7f2f5144ea549d28211ea58faa70ebf4c1e665c1fe9b5";
-    //     point = &g * &coef;
-    //     assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
-    //     assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
-
-    //     coef = BigUint::from(42424242u32);
-    //     uncompressed = "04aee2e7d843f7430097859e2bc603abcc3274ff8169c1a469fee0f20614066f8e21ec53f40efac47ac1c5211b2123527e0e9b57ede790c4da1e72c91fb7da54a3";
-    //     compressed = "03aee2e7d843f7430097859e2bc603abcc3274ff8169c1a469fee0f20614066f8e";
-    //     point = &g * &coef;
-    //     assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
-    //     assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
-    // }
-
-    // #[test]
-    // fn test_s256_parse() {
-    //     let g = S256Point::generator();
-    //     let mut coef = BigUint::from(999u32).pow(3);
-    //     let mut point = &g * &coef;
-    //     let mut sec_bin = point.uncompressed_sec();
-    //     let mut parsed_point = point.parse(&sec_bin);
-    //     assert_eq!(parsed_point.0, point.0);
-
-    //     coef = BigUint::from(123u32);
-    //     point = &g * &coef;
-    //     sec_bin = point.compressed_sec();
-    //     parsed_point = point.parse(&sec_bin);
-    //     assert_eq!(parsed_point.0, point.0);
-        
-    // }
-
-    // #[test]
-    // fn test_s256_address() {
-    //     let g = S256Point::generator();
-    //     let mut secret = BigUint::from(888u32).pow(3);
-    //     let mut point = &g * &secret;
-    //     let mut mainnet_address = "148dY81A9BmdpMhvYEVznrM45kWN32vSCN";
-    //     let mut testnet_address = "mieaqB68xDCtbUBYFoUNcmZNwk74xcBfTP";
-    //     assert_eq!(point.address(true, false), mainnet_address);
-    //     assert_eq!(point.address(true, true), testnet_address);
-
-    //     secret = BigUint::from(321u32);
-    //     point = &g * &secret;
-    //     mainnet_address = "1S6g2xBJSED7Qr9CYZib5f4PYVhHZiVfj";
-    //     testnet_address = "mfx3y63A7TfTtXKkv7Y6QzsPFY6QCBCXiP";
-    //     assert_eq!(point.address(false, false), mainnet_address);
-    //     assert_eq!(point.address(false, true), testnet_address);
-
-    //     secret = BigUint::from(4242424242u32);
-    //     point = &g * &secret;
-    //     mainnet_address = "1226JSptcStqn4Yq9aAmNXdwdc2ixuH9nb";
-    //     testnet_address = "mgY3bVusRUL6ZB2Ss999CSrGVbdRwVpM8s";
-    //     assert_eq!(point.address(false, false), mainnet_address);
-    //     assert_eq!(point.address(false, true), testnet_address);
-    // }
-}
\ No newline at end of file
+        let x1 = F::new(BigUint::from(47u32));
+        let y1 = F::new(BigUint::from(71u32));
+        let p1 = Point::from(Arc::clone(&curve), x1, y1).unwrap();
+
+        let p2 = Point::at_inifity(Arc::clone(&curve)).unwrap();
+
+        let scalar = p1.order().unwrap();
+
+        assert_eq!(scalar, BigUint::from(21u32));
+        assert_eq!(&p1 * scalar, p2);
+
+    }
+
+    #[test]
+    fn test_add_inverse_and_double() {
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+
+        let p1 = Point::from(Arc::clone(&curve), F::new(BigUint::from(47u32)), F::new(BigUint::from(71u32))).unwrap();
+        let minus_p1 = Point::from(Arc::clone(&curve), F::new(BigUint::from(47u32)), F::new(BigUint::from(152u32))).unwrap();
+        assert_eq!((&p1 + &minus_p1).unwrap(), Point::at_inifity(Arc::clone(&curve)).unwrap());
+
+        let doubled = Point::from(Arc::clone(&curve), F::new(BigUint::from(36u32)), F::new(BigUint::from(111u32))).unwrap();
+        assert_eq!((&p1 + &p1).unwrap(), doubled);
+    }
+
+    #[test]
+    fn test_mul_matches_repeated_addition() {
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+
+        let p1 = Point::from(Arc::clone(&curve), F::new(BigUint::from(15u32)), F::new(BigUint::from(86u32))).unwrap();
+        let mut sum = Point::at_inifity(Arc::clone(&curve)).unwrap();
+        for k in 0..20u32 {
+            assert_eq!(&p1 * BigUint::from(k), sum);
+            sum = (&sum + &p1).unwrap();
+        }
+
+        // (15, 86) has order 7, so a large scalar only matters modulo 7
+        let scalar = (BigUint::from(1u32) << 200u32) + BigUint::from(3u32);
+        let reduced = &scalar % BigUint::from(7u32);
+        assert_eq!(&p1 * scalar, &p1 * reduced);
+    }
+
+    #[test]
+    fn test_mul_ct_matches_mul() {
+        // y² = x³ + 5 over F_223 has 229 points, a prime
+        let curve = Arc::new(EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::from(5u32))));
+        assert!(curve.has_odd_order());
+        let p = Point::from(Arc::clone(&curve), F::new(BigUint::from(3u32)), F::new(BigUint::from(60u32))).unwrap();
+        for k in 0..=255u32 {
+            assert_eq!(p.mul_ct(&BigUint::from(k)).unwrap(), &p * BigUint::from(k));
+        }
+        let infinity = Point::at_inifity(Arc::clone(&curve)).unwrap();
+        assert_eq!(infinity.mul_ct(&BigUint::from(5u32)).unwrap(), infinity);
+    }
+
+    #[test]
+    fn test_mul_ct_even_order() {
+        // y² = x³ + 7 over F_223 has 252 points and (6, 0) of order 2, where the formulas
+        // aren't complete, so even the points of odd order like (47, 71) are refused
+        let curve = Arc::new(EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::from(7u32))));
+        assert!(!curve.has_odd_order());
+        let two_torsion = Point::from(Arc::clone(&curve), F::new(BigUint::from(6u32)), F::new(BigUint::ZERO)).unwrap();
+        assert_eq!(two_torsion.order(), Some(BigUint::from(2u32)));
+        assert_eq!(two_torsion.mul_ct(&BigUint::from(3u32)), Err(CurveError::IncompleteFormulas));
+        let p = Point::from(Arc::clone(&curve), F::new(BigUint::from(47u32)), F::new(BigUint::from(71u32))).unwrap();
+        assert_eq!(p.mul_ct(&BigUint::from(3u32)), Err(CurveError::IncompleteFormulas));
+
+        // the registered order counts too, and a curve too large to count needs one
+        let f223 = NamedCurve::<FieldElement<F223>>::by_name("f223").unwrap();
+        assert_eq!(f223.curve().known_order(), Some(&BigUint::from(252u32)));
+        assert_eq!(f223.generator().mul_ct(&BigUint::from(3u32)), Err(CurveError::IncompleteFormulas));
+        let secp256k1 = NamedCurve::<FieldElement<Secp256k1Prime>>::by_name("secp256k1").unwrap();
+        assert!(secp256k1.curve().has_odd_order());
+        let bare = Arc::new(EllipticCurve::new(secp256k1.curve().a().clone(), secp256k1.curve().b().clone()));
+        assert!(!bare.has_odd_order());
+    }
+
+    #[test]
+    fn test_wnaf() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let scalar = rng.gen_biguint(256);
+            let digits = wnaf(&scalar, WNAF_WIDTH);
+            let mut total = BigInt::ZERO;
+            for (i, &digit) in digits.iter().enumerate().rev() {
+                total = total * 2 + digit;
+                assert!(digit == 0 || (digit % 2 != 0 && digit.abs() < 1 << (WNAF_WIDTH - 1)));
+                if digit != 0 {
+                    assert!(digits[i.saturating_sub(WNAF_WIDTH as usize - 1)..i].iter().all(|&d| d == 0));
+                }
+            }
+            assert_eq!(total, BigInt::from(scalar));
+        }
+    }
+
+    #[test]
+    fn test_multi_mul() {
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+
+        let coordinates = [(192u32, 105u32), (17, 56), (1, 193), (47, 71), (15, 86), (143, 98), (76, 66)];
+        let points: Vec<Point<F>> = coordinates.iter().map(|&(x, y)| {
+            Point::from(Arc::clone(&curve), F::new(BigUint::from(x)), F::new(BigUint::from(y))).unwrap()
+        }).collect();
+
+        let mut rng = rand::thread_rng();
+        for count in [0, 1, 2, 5, 40] {
+            let scalars: Vec<BigUint> = (0..count).map(|_| rng.gen_biguint(64)).collect();
+            let terms: Vec<(&Point<F>, &BigUint)> = scalars.iter().enumerate().map(|(i, k)| (&points[i % points.len()], k)).collect();
+            let mut expected = Point::at_inifity(Arc::clone(&curve)).unwrap();
+            for (point, scalar) in &terms {
+                expected = (&expected + &(*point * (*scalar).clone())).unwrap();
+            }
+            assert_eq!(Point::multi_mul(Arc::clone(&curve), &terms).unwrap(), expected);
+            let jacobian: Vec<_> = terms.iter().map(|(p, k)| (JacobianPoint::from_affine(p), (*k).clone())).collect();
+            assert_eq!(strauss(&*curve, &jacobian).to_affine(Arc::clone(&curve)), expected);
+            assert_eq!(pippenger(&*curve, &jacobian).to_affine(Arc::clone(&curve)), expected);
+        }
+
+        let other = Arc::new(EllipticCurve::new(F::new(BigUint::ZERO), F::new(BigUint::from(5u32))));
+        let one = BigUint::from(1u32);
+        assert_eq!(Point::multi_mul(other, &[(&points[0], &one)]), Err(CurveError::DifferentCurves));
+    }
+
+    #[test]
+    fn test_glv_mul_matches_double_and_add() {
+        let secp256k1 = NamedCurve::<FieldElement<Secp256k1Prime>>::by_name("secp256k1").unwrap();
+        let endomorphism = secp256k1.curve().endomorphism().unwrap();
+        let g = secp256k1.generator();
+
+        let mut rng = rand::thread_rng();
+        let mut scalars: Vec<BigUint> = (0..5).map(|_| rng.gen_biguint(256)).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), BigUint::from(2u32)]);
+        for scalar in scalars {
+            assert_eq!(glv_mul(g, endomorphism, &scalar), double_and_add(g, scalar.clone()));
+            assert_eq!(g * scalar.clone(), double_and_add(g, scalar));
+        }
+        let infinity = Point::at_inifity(Arc::clone(secp256k1.curve())).unwrap();
+        assert_eq!(glv_mul(&infinity, endomorphism, &BigUint::from(5u32)), infinity);
+
+        // the same curve built by hand has no endomorphism attached, but is still equal
+        let a = FieldElement::<Secp256k1Prime>::new(BigUint::ZERO);
+        let b = FieldElement::<Secp256k1Prime>::new(BigUint::from(7u32));
+        let plain = EllipticCurve::new(a, b);
+        assert!(plain.endomorphism().is_none());
+        assert_eq!(plain, **secp256k1.curve());
+        // the toy curve has a = 0 and b = 7 too, but p = 223 is not 1 mod 3
+        let toy = NamedCurve::<FieldElement<F223>>::by_name("f223").unwrap();
+        assert!(toy.curve().endomorphism().is_none());
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<F>();
+        assert_send_sync::<EllipticCurve<F>>();
+        assert_send_sync::<Point<F>>();
+
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+        let g = Arc::new(Point::from(Arc::clone(&curve), F::new(BigUint::from(47u32)), F::new(BigUint::from(71u32))).unwrap());
+
+        // (47, 71) has order 21, so each worker's multiple plus the rest comes back to infinity
+        let workers: Vec<_> = (1..5u32).map(|k| {
+            let g = Arc::clone(&g);
+            std::thread::spawn(move || g.as_ref() * BigUint::from(k))
+        }).collect();
+        for (k, worker) in (1..5u32).zip(workers) {
+            let point = worker.join().unwrap();
+            assert_eq!((&point + &(g.as_ref() * BigUint::from(21 - k))).unwrap(), Point::at_inifity(Arc::clone(&curve)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_compression() {
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+
+        let p1 = Point::from(Arc::clone(&curve), F::new(BigUint::from(192u32)), F::new(BigUint::from(105u32))).unwrap();
+        assert_eq!(p1.to_compressed(), vec![0x03, 192]);
+        let p2 = Point::from(Arc::clone(&curve), F::new(BigUint::from(17u32)), F::new(BigUint::from(56u32))).unwrap();
+        assert_eq!(p2.to_compressed(), vec![0x02, 17]);
+
+        // every point of the curve survives a round trip
+        let mut count = 0;
+        for x in 0..223u32 {
+            for prefix in [0x02, 0x03] {
+                if let Ok(point) = Point::from_compressed(Arc::clone(&curve), &[prefix, x as u8]) {
+                    assert_eq!(point.to_compressed(), vec![prefix, x as u8]);
+                    let XY::Finite { x: px, y } = &point.xy else { panic!("finite point expected") };
+                    assert_eq!(Point::from(Arc::clone(&curve), px.clone(), y.clone()).unwrap(), point);
+                    count += 1;
+                }
+            }
+        }
+        // y² = x³ + 7 over F_223 has 251 finite points
+        assert_eq!(count, 251);
+
+        let infinity = Point::at_inifity(Arc::clone(&curve)).unwrap();
+        assert_eq!(infinity.to_compressed(), vec![0x00]);
+        assert_eq!(Point::from_compressed(Arc::clone(&curve), &[0x00]).unwrap(), infinity);
+
+        // 4³ + 7 = 71 is not a square modulo 223
+        assert_eq!(Point::from_compressed(Arc::clone(&curve), &[0x02, 4]), Err(EncodingError::Curve(CurveError::NotOnCurve)));
+        assert_eq!(Point::from_compressed(Arc::clone(&curve), &[0x02, 230]), Err(EncodingError::NotAFieldElement));
+        assert_eq!(Point::from_compressed(Arc::clone(&curve), &[0x02, 0, 1]), Err(EncodingError::InvalidLength { expected: 2, found: 3 }));
+        assert_eq!(Point::from_compressed(Arc::clone(&curve), &[0x04, 1]), Err(EncodingError::InvalidPrefix(0x04)));
+        assert!(Point::from_compressed(Arc::clone(&curve), &[]).is_err());
+
+        // the uncompressed form carries y itself, from_sec reads both
+        assert_eq!(p1.to_uncompressed(), vec![0x04, 192, 105]);
+        assert_eq!(p1.xy(), Some((&F::new(BigUint::from(192u32)), &F::new(BigUint::from(105u32)))));
+        assert_eq!(Point::from_sec(Arc::clone(&curve), &[0x04, 192, 105]).unwrap(), p1);
+        assert_eq!(Point::from_sec(Arc::clone(&curve), &[0x03, 192]).unwrap(), p1);
+        assert_eq!(Point::from_sec(Arc::clone(&curve), &[0x04, 192, 106]), Err(EncodingError::Curve(CurveError::NotOnCurve)));
+        assert_eq!(Point::from_sec(curve, &[0x04, 192]), Err(EncodingError::InvalidLength { expected: 3, found: 2 }));
+    }
+
+    #[test]
+    fn test_order_and_points() {
+        let a = F::new(BigUint::ZERO);
+        let b = F::new(BigUint::from(7u32));
+        let curve = Arc::new(EllipticCurve::new(a, b));
+
+        let order = curve.order().unwrap();
+        assert_eq!(order, BigUint::from(252u32));
+        let points = curve.points().unwrap();
+        assert_eq!(BigUint::from(points.len()), order);
+        assert!(points[0].is_infinity());
+        assert_eq!(points[1], Point::from(Arc::clone(&curve), F::new(BigUint::ZERO), F::new(BigUint::from(26u32))).unwrap());
+
+        let mut total = Point::at_inifity(Arc::clone(&curve)).unwrap();
+        for point in &points {
+            let point_order = point.order().unwrap();
+            assert_eq!(&order % &point_order, BigUint::ZERO);
+            assert!((point * point_order.clone()).is_infinity());
+            if point_order > BigUint::from(1u32) {
+                for factor in prime_factors(&point_order) {
+                    assert!(!(point * (&point_order / factor)).is_infinity());
+                }
+            }
+            total = (&total + point).unwrap();
+        }
+        // the points pair up with their negatives, except the ones with y = 0
+        assert!(total.is_infinity());
+
+        let p1 = Point::from(Arc::clone(&curve), F::new(BigUint::from(15u32)), F::new(BigUint::from(86u32))).unwrap();
+        assert_eq!(p1.order(), Some(BigUint::from(7u32)));
+        assert_eq!(points[0].order(), Some(BigUint::from(1u32)));
+        let p3 = Point::from(Arc::clone(&curve), F::new(BigUint::from(47u32)), F::new(BigUint::from(71u32))).unwrap();
+        assert_eq!(p3.order(), Some(BigUint::from(21u32)));
+        let factors: Vec<u32> = prime_factors(&BigUint::from(252u32)).iter().map(|f| f.try_into().unwrap()).collect();
+        assert_eq!(factors, vec![2, 3, 7]);
+
+        // secp256k1 is far too large to count
+        let secp256k1 = NamedCurve::<FieldElement<Secp256k1Prime>>::by_name("secp256k1").unwrap();
+        assert_eq!(secp256k1.curve().order(), None);
+        assert!(secp256k1.curve().points().is_none());
+        assert_eq!(secp256k1.generator().order(), None);
+    }
+}
@@ -0,0 +1,116 @@
+use std::error::Error;
+use std::fmt;
+
+use num_bigint::BigUint;
+
+// Errors of finite field arithmetic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldError {
+    DivisionByZero,
+    NotInField { num: BigUint, prime: BigUint },
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldError::DivisionByZero => write!(f, "can't divide by zero"),
+            FieldError::NotInField { num, prime } => write!(f, "num {} not in field of order {}", num, prime),
+        }
+    }
+}
+
+impl Error for FieldError {}
+
+// Errors of elliptic curve points and of the keys built on them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CurveError {
+    NotOnCurve,
+    DifferentCurves,
+    PointAtInfinity,
+    Singular,
+    UnknownCurve(String),
+    IncompleteFormulas,
+    Field(FieldError),
+}
+
+impl fmt::Display for CurveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurveError::NotOnCurve => write!(f, "(x, y) is not on the curve"),
+            CurveError::DifferentCurves => write!(f, "points are not in the same curve"),
+            CurveError::PointAtInfinity => write!(f, "unexpected point at infinity"),
+            CurveError::Singular => write!(f, "curve is singular, 4a³ + 27b² = 0"),
+            CurveError::UnknownCurve(name) => write!(f, "no curve named {} over this field", name),
+            CurveError::IncompleteFormulas => write!(f, "the complete formulas need a curve of known odd order"),
+            CurveError::Field(err) => write!(f, "field error: {}", err),
+        }
+    }
+}
+
+impl Error for CurveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CurveError::Field(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<FieldError> for CurveError {
+    fn from(err: FieldError) -> Self {
+        CurveError::Field(err)
+    }
+}
+
+// Errors decoding keys and signatures from bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    InvalidLength { expected: usize, found: usize },
+    InvalidPrefix(u8),
+    NotAFieldElement,
+    ScalarOutOfRange,
+    Curve(CurveError),
+}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::InvalidLength { expected, found } => write!(f, "expected {} bytes, found {}", expected, found),
+            EncodingError::InvalidPrefix(prefix) => write!(f, "invalid prefix byte {:#04x}", prefix),
+            EncodingError::NotAFieldElement => write!(f, "number is not below the field prime"),
+            EncodingError::ScalarOutOfRange => write!(f, "scalar is out of range for the group order"),
+            EncodingError::Curve(err) => write!(f, "curve error: {}", err),
+        }
+    }
+}
+
+impl Error for EncodingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EncodingError::Curve(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<CurveError> for EncodingError {
+    fn from(err: CurveError) -> Self {
+        EncodingError::Curve(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_source() {
+        let err = EncodingError::from(CurveError::from(FieldError::DivisionByZero));
+        assert_eq!(err.to_string(), "curve error: field error: can't divide by zero");
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "field error: can't divide by zero");
+        assert_eq!(source.source().unwrap().to_string(), "can't divide by zero");
+        assert_eq!(EncodingError::InvalidPrefix(5).to_string(), "invalid prefix byte 0x05");
+        assert!(CurveError::NotOnCurve.source().is_none());
+    }
+}
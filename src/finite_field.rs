@@ -1,140 +1,327 @@
-use std::ops::{Add, Div, Mul, Sub};
-use std::rc::Rc;
-use num_bigint::{BigInt, BigUint, Sign, ToBigInt};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use num_bigint::{BigInt, Sign};
 use lazy_static::lazy_static;
 
+use crate::error::FieldError;
+
+// re-exported for modulus!, so crates using the macro don't need num_bigint themselves
+pub use num_bigint::BigUint;
+
 lazy_static! {
     pub static ref P: BigUint = BigUint::from(2u32).pow(256) - BigUint::from(2u32).pow(32) - BigUint::from(977u32);
     pub static ref ONE: BigUint = BigUint::from(1u32);
 }
 
 pub type Num = BigUint;
-pub type Prime = Rc<BigUint>;
 
-#[derive(Debug, Clone)]
-pub struct FieldElement {
+// A field modulus lifted to the type level: every prime gets its own zero sized type, so
+// elements of different fields are different types and can't be mixed by mistake.
+pub trait Modulus: Debug + Clone + Copy + PartialEq + Eq + Send + Sync + 'static {
+    fn modulus() -> &'static BigUint;
+}
+
+// declares a Modulus type, e.g. `modulus!(pub F223 = 223u32);`, the value being anything a
+// BigUint can be made from
+#[macro_export]
+macro_rules! modulus {
+    ($vis:vis $name:ident = $value:expr) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        $vis struct $name;
+
+        impl $crate::finite_field::Modulus for $name {
+            fn modulus() -> &'static $crate::finite_field::BigUint {
+                static MODULUS: ::std::sync::OnceLock<$crate::finite_field::BigUint> = ::std::sync::OnceLock::new();
+                MODULUS.get_or_init(|| $crate::finite_field::BigUint::from($value))
+            }
+        }
+    };
+}
+
+// Arithmetic on references, so that generic code can write &a * &b without cloning; use it
+// as `where for<'a> &'a F: FieldRef<F>`.
+pub trait FieldRef<F>: Sized + Add<Self, Output = F> + Sub<Self, Output = F> + Mul<Self, Output = F> + Neg<Output = F> {}
+
+// What the curve code needs from a field of prime order. FieldElement is the textbook
+// implementation for any prime, secp256k1::S256Field the fast one for secp256k1's.
+pub trait PrimeField: Debug + Clone + PartialEq + Send + Sync + 'static {
+    fn modulus() -> &'static BigUint;
+
+    // reduces modulo the prime
+    fn from_biguint(num: &BigUint) -> Self;
+
+    fn to_biguint(&self) -> BigUint;
+
+    fn is_zero(&self) -> bool;
+
+    // the multiplicative inverse, zero for zero
+    fn inverse(&self) -> Self;
+
+    // one of the two square roots, None if there is none
+    fn sqrt(&self) -> Option<Self>;
+
+    fn is_square(&self) -> bool;
+
+    fn zero() -> Self {
+        Self::from_biguint(&BigUint::ZERO)
+    }
+
+    fn one() -> Self {
+        Self::from_biguint(&BigUint::from(1u32))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldElement<M: Modulus> {
     num: Num,
-    prime: Prime,
+    modulus: PhantomData<M>,
 }
 
-impl FieldElement {
-    pub fn new(num: Num, prime: Prime) -> Self {
-        if num >= *prime {
-            panic!("num {} not in field of order {}", num, prime);
+impl<M: Modulus> FieldElement<M> {
+    pub fn new(num: Num) -> Self {
+        match FieldElement::try_new(num) {
+            Ok(element) => element,
+            Err(err) => panic!("{}", err),
         }
-        FieldElement { num, prime }
+    }
+
+    pub fn try_new(num: Num) -> Result<Self, FieldError> {
+        if num >= *M::modulus() {
+            return Err(FieldError::NotInField { num, prime: M::modulus().clone() });
+        }
+        Ok(FieldElement { num, modulus: PhantomData })
+    }
+
+    fn reduced(num: Num) -> Self {
+        FieldElement { num: num % M::modulus(), modulus: PhantomData }
     }
 
     pub fn pow(&self, exp: &BigInt) -> Self {
+        let prime = M::modulus();
         if exp.sign() == Sign::Minus {
-            let inv = &(self.num.modinv(&self.prime).unwrap()).to_bigint().unwrap();
-            let num = inv.modpow(&exp.magnitude().to_bigint().unwrap(), &self.prime.to_bigint().unwrap());
-            return FieldElement::new(num.to_biguint().unwrap(), Rc::clone(&self.prime));
+            let inv = self.num.modinv(prime).unwrap();
+            return FieldElement::reduced(inv.modpow(exp.magnitude(), prime));
         }
-        let num = self.num.modpow(&exp.to_biguint().unwrap(), &self.prime);
-        FieldElement::new(num, Rc::clone(&self.prime))
+        FieldElement::reduced(self.num.modpow(exp.magnitude(), prime))
     }
 
     pub fn num(&self) -> &Num {
         &self.num
     }
 
-    pub fn prime(&self) -> Prime {
-        Rc::clone(&self.prime)
+    // Euler's criterion: n^((p-1)/2) is the Legendre symbol, 1 for nonzero squares
+    pub fn is_square(&self) -> bool {
+        let prime = M::modulus();
+        if self.num == BigUint::ZERO || *prime == BigUint::from(2u32) {
+            return true;
+        }
+        self.num.modpow(&((prime - 1u32) >> 1u32), prime) == BigUint::from(1u32)
     }
-}
 
-impl PartialEq for FieldElement {
-    fn eq(&self, other: &Self) -> bool {
-        self.num == other.num && self.prime == other.prime
+    // Tonelli-Shanks, which works for every odd prime. With p - 1 = q·2^s and q odd, r = n^((q+1)/2)
+    // is a root up to the factor t = n^q, whose order is a power of two; each round swaps in a
+    // power of a non-residue to halve that order until t is 1. For p = 3 mod 4, s is 1 and this
+    // is just n^((p+1)/4).
+    pub fn sqrt(&self) -> Option<Self> {
+        let prime = M::modulus();
+        if self.num == BigUint::ZERO || *prime == BigUint::from(2u32) {
+            return Some(self.clone());
+        }
+        if !self.is_square() {
+            return None;
+        }
+        let one = BigUint::from(1u32);
+        let mut q = prime - 1u32;
+        let mut s = 0u32;
+        while !q.bit(0) {
+            q >>= 1u32;
+            s += 1;
+        }
+        let mut z = BigUint::from(2u32);
+        while FieldElement::<M>::reduced(z.clone()).is_square() {
+            z += 1u32;
+        }
+
+        let mut m = s;
+        let mut c = z.modpow(&q, prime);
+        let mut t = self.num.modpow(&q, prime);
+        let mut r = self.num.modpow(&((&q + 1u32) >> 1u32), prime);
+        while t != one {
+            // the least i with t^(2^i) = 1
+            let mut i = 0;
+            let mut t2i = t.clone();
+            while t2i != one {
+                t2i = &t2i * &t2i % prime;
+                i += 1;
+            }
+            let b = c.modpow(&(BigUint::from(1u32) << (m - i - 1)), prime);
+            m = i;
+            c = &b * &b % prime;
+            t = t * &c % prime;
+            r = r * b % prime;
+        }
+        Some(FieldElement::reduced(r))
+    }
+
+    pub fn prime(&self) -> &'static BigUint {
+        M::modulus()
     }
 }
 
-impl<'a> Add<&'a FieldElement> for &FieldElement {
-    type Output = Result<FieldElement, &'static str>;
+impl<M: Modulus> PrimeField for FieldElement<M> {
+    fn modulus() -> &'static BigUint {
+        M::modulus()
+    }
+
+    fn from_biguint(num: &BigUint) -> Self {
+        FieldElement::reduced(num.clone())
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.num.clone()
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == BigUint::ZERO
+    }
 
-    fn add(self, other: &'a FieldElement) -> Self::Output {
-        if same_field(self, other) {
-            return Err("can't add numbers in different fields");
+    fn inverse(&self) -> Self {
+        match self.num.modinv(M::modulus()) {
+            Some(inv) => FieldElement::reduced(inv),
+            None => FieldElement::reduced(BigUint::ZERO),
         }
-        let num = (&self.num + &other.num).modpow(&BigUint::from(1u32), &self.prime);
-        Ok(FieldElement::new(num, Rc::clone(&self.prime)))
+    }
+
+    fn sqrt(&self) -> Option<Self> {
+        FieldElement::sqrt(self)
+    }
+
+    fn is_square(&self) -> bool {
+        FieldElement::is_square(self)
     }
 }
 
-impl<'a> Sub<&'a FieldElement> for &FieldElement {
-    type Output = Result<FieldElement, &'static str>;
+impl<M: Modulus> FieldRef<FieldElement<M>> for &FieldElement<M> {}
 
-    fn sub(self, other: &'a FieldElement) -> Self::Output {
-        if same_field(self, other) {
-            return Err("can't subtract numbers in different fields");
-        }
-        // ((a % b) + b) % b // workaround for modulus operation of a negative number in rust
-        let num = &self.num.to_bigint().unwrap() - &other.num.to_bigint().unwrap();
-        let prime = &self.prime.to_bigint().unwrap();
-        let res = ((num % prime) + prime) % prime;
-        Ok(FieldElement::new(res.to_biguint().unwrap(), Rc::clone(&self.prime)))
+impl<'a, M: Modulus> Add<&'a FieldElement<M>> for &FieldElement<M> {
+    type Output = FieldElement<M>;
+
+    fn add(self, other: &'a FieldElement<M>) -> Self::Output {
+        FieldElement::reduced(&self.num + &other.num)
     }
 }
 
-impl<'a> Mul<&'a FieldElement> for &FieldElement {
-    type Output = Result<FieldElement, &'static str>;
+impl<'a, M: Modulus> Sub<&'a FieldElement<M>> for &FieldElement<M> {
+    type Output = FieldElement<M>;
 
-    fn mul(self, other: &'a FieldElement) -> Self::Output {
-        if same_field(self, other) {
-            return Err("can't multiply numbers in different fields");
-        }
-        let num = (&self.num * &other.num).modpow(&BigUint::from(1u32), &self.prime);
-        Ok(FieldElement::new(num, Rc::clone(&self.prime)))
+    fn sub(self, other: &'a FieldElement<M>) -> Self::Output {
+        // adding the prime first keeps the unsigned subtraction from going negative
+        FieldElement::reduced(&self.num + M::modulus() - &other.num)
     }
 }
 
-impl<'a> Div<&'a FieldElement> for &FieldElement {
-    type Output = Result<FieldElement, &'static str>;
+impl<'a, M: Modulus> Mul<&'a FieldElement<M>> for &FieldElement<M> {
+    type Output = FieldElement<M>;
 
-    fn div(self, other: &'a FieldElement) -> Self::Output {
-        if other.num == BigUint::from(0u32) {
-            return Err("can't divide by zero!");
-        }
-        if same_field(self, other) {
-            return Err("can't divide numbers in different fields");
+    fn mul(self, other: &'a FieldElement<M>) -> Self::Output {
+        FieldElement::reduced(&self.num * &other.num)
+    }
+}
+
+impl<'a, M: Modulus> Div<&'a FieldElement<M>> for &FieldElement<M> {
+    type Output = Result<FieldElement<M>, FieldError>;
+
+    fn div(self, other: &'a FieldElement<M>) -> Self::Output {
+        if other.is_zero() {
+            return Err(FieldError::DivisionByZero);
         }
-        self * &other.pow(&BigInt::from(-1))
+        Ok(Mul::mul(self, &other.inverse()))
     }
 }
 
-fn same_field(elem_1: &FieldElement, elem_2: &FieldElement) -> bool {
-    elem_1.prime != elem_2.prime 
+impl<M: Modulus> Neg for &FieldElement<M> {
+    type Output = FieldElement<M>;
+
+    fn neg(self) -> Self::Output {
+        FieldElement::reduced(M::modulus() - &self.num)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::named_curve::F223;
+
+    crate::modulus!(F29 = 29u32);
+    crate::modulus!(F31 = 31u32);
+    crate::modulus!(F2 = 2u32);
+    crate::modulus!(F17 = 17u32);
+    crate::modulus!(F97 = 97u32);
+    crate::modulus!(F25519 = (BigUint::from(1u32) << 255u32) - 19u32);
+
+    // nothing imported, the macro has to name everything through the crate
+    mod bare {
+        crate::modulus!(pub F7 = 7u32);
+    }
+
+    #[test]
+    fn test_modulus_macro_without_imports() {
+        assert_eq!(bare::F7::modulus(), &BigUint::from(7u32));
+        let (a, b) = (FieldElement::<bare::F7>::new(BigUint::from(3u32)), FieldElement::new(BigUint::from(5u32)));
+        assert_eq!(&a * &b, FieldElement::new(BigUint::from(1u32)));
+    }
+
+    // checks sqrt and is_square against squaring every element of a small field
+    fn check_small_field<M: Modulus>() {
+        let prime: u32 = M::modulus().try_into().unwrap();
+        let squares: Vec<FieldElement<M>> = (0..prime).map(|n| {
+            let x = FieldElement::<M>::new(BigUint::from(n));
+            &x * &x
+        }).collect();
+        for n in 0..prime {
+            let element = FieldElement::<M>::new(BigUint::from(n));
+            let expected = squares.contains(&element);
+            assert_eq!(element.is_square(), expected, "{} mod {}", n, prime);
+            match element.sqrt() {
+                Some(root) => assert_eq!(&root * &root, element),
+                None => assert!(!expected),
+            }
+        }
+    }
+
+    fn fe(num: u32) -> FieldElement<F31> {
+        FieldElement::new(BigUint::from(num))
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(FieldElement::<F29>::try_new(BigUint::from(28u32)).unwrap().num(), &BigUint::from(28u32));
+        let err = FieldElement::<F29>::try_new(BigUint::from(31u32)).unwrap_err();
+        assert_eq!(err, FieldError::NotInField { num: BigUint::from(31u32), prime: BigUint::from(29u32) });
+        assert_eq!(err.to_string(), "num 31 not in field of order 29");
+    }
 
     #[test]
     #[should_panic]
     fn test_new_invalid_finite_element() {
         let num = BigUint::from(31u32);
-        let prime = Rc::new(BigUint::from(29u32));
-        FieldElement::new(num, prime);
+        FieldElement::<F29>::new(num);
     }
 
     #[test]
     fn test_new_valid_finite_element() {
         let num = BigUint::from(29u32);
-        let prime = Rc::new(BigUint::from(31u32));
-        let field_element = FieldElement::new(num, prime);
+        let field_element = FieldElement::<F31>::new(num);
         assert_eq!(field_element.num, BigUint::from(29u32));
-        assert_eq!(field_element.prime, Rc::new(BigUint::from(31u32)));
+        assert_eq!(field_element.prime(), &BigUint::from(31u32));
     }
 
     #[test]
     fn test_eq() {
-
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(2u32), Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(2u32), Rc::clone(&prime));
-        let c = FieldElement::new(BigUint::from(15u32), Rc::clone(&prime));
+        let a = fe(2);
+        let b = fe(2);
+        let c = fe(15);
         assert_eq!(a, b);
         assert_ne!(a, c);
         assert_ne!(b, c);
@@ -142,106 +329,73 @@ mod tests {
     
     #[test]
     fn test_add() {
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(2u32), Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(15u32), Rc::clone(&prime));
-        let res_add_a_b = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        assert_eq!((&a + &b).unwrap(), res_add_a_b);
-        let c = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        let d = FieldElement::new(BigUint::from(21u32), Rc::clone(&prime));
-        let res_add_c_d = FieldElement::new(BigUint::from(7u32), Rc::clone(&prime));
-        assert_eq!((&c + &d).unwrap(), res_add_c_d);
+        assert_eq!(&fe(2) + &fe(15), fe(17));
+        assert_eq!(&fe(17) + &fe(21), fe(7));
     }
 
-    #[test]
-    fn test_add_elements_in_different_fields() {
-        let prime_1 = Rc::new(BigUint::from(31u32));
-        let prime_2 = Rc::new(BigUint::from(29u32));
-        let a = FieldElement::new(BigUint::from(2u32), prime_1);
-        let b = FieldElement::new(BigUint::from(15u32), prime_2);
-        let res = &a + &b;
-        assert!(res.is_err());
-    }
-    
     #[test]
     fn test_sub() {
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(29u32), Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(4u32), Rc::clone(&prime));
-        let res_sub_a_b = FieldElement::new(BigUint::from(25u32), Rc::clone(&prime));
-        assert_eq!((&a - &b).unwrap(), res_sub_a_b);
-        let c = FieldElement::new(BigUint::from(15u32), Rc::clone(&prime));
-        let d = FieldElement::new(BigUint::from(30u32), Rc::clone(&prime));
-        let res_sub_c_d = FieldElement::new(BigUint::from(16u32), Rc::clone(&prime));
-        assert_eq!((&c - &d).unwrap(), res_sub_c_d);
-    }
-
-    #[test]
-    fn test_sub_elements_in_different_fields() {
-        let prime_1 = Rc::new(BigUint::from(31u32));
-        let prime_2 = Rc::new(BigUint::from(29u32));
-        let a = FieldElement::new(BigUint::from(2u32), prime_1);
-        let b = FieldElement::new(BigUint::from(15u32), prime_2);
-        let res = &a - &b;
-        assert!(res.is_err());
+        assert_eq!(&fe(29) - &fe(4), fe(25));
+        assert_eq!(&fe(15) - &fe(30), fe(16));
+        assert_eq!(-&fe(15), fe(16));
+        assert_eq!(-&fe(0), fe(0));
     }
 
     #[test]
     fn test_mul() {
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(24u32), Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(19u32), Rc::clone(&prime));
-        let res_mul_a_b = FieldElement::new(BigUint::from(22u32), Rc::clone(&prime));
-        assert_eq!((&a * &b).unwrap(), res_mul_a_b);
+        assert_eq!(&fe(24) * &fe(19), fe(22));
     }
 
     #[test]
-    fn test_mul_elements_in_different_fields() {
-        let prime_1 = Rc::new(BigUint::from(31u32));
-        let prime_2 = Rc::new(BigUint::from(29u32));
-        let a = FieldElement::new(BigUint::from(2u32), prime_1);
-        let b = FieldElement::new(BigUint::from(15u32), prime_2);
-        let res = &a * &b;
-        assert!(res.is_err());
+    fn test_pow() {
+        let a = fe(17);
+        assert_eq!(&a.pow(&BigInt::from(3)), &fe(15));
+        assert_eq!(a.pow(&BigInt::from(3)), fe(15));
+        assert_eq!(&fe(5).pow(&BigInt::from(5)) * &fe(18), fe(16));
+        let d = fe(17);
+        assert_eq!(&d.pow(&BigInt::from(-3)), &fe(29));
+        assert_eq!(d.pow(&BigInt::from(-3)), fe(29));
+        assert_eq!(&fe(4).pow(&BigInt::from(-4)) * &fe(11), fe(13));
     }
 
     #[test]
-    fn test_pow() {
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        let a_pow_3 = FieldElement::new(BigUint::from(15u32), Rc::clone(&prime));
-        assert_eq!(&a.pow(&BigInt::from(3)), &a_pow_3);
-        assert_eq!(a.pow(&BigInt::from(3)), a_pow_3);
-        let b = FieldElement::new(BigUint::from(5u32), Rc::clone(&prime));
-        let c = FieldElement::new(BigUint::from(18u32), Rc::clone(&prime));
-        let res_b_pow_5_mul_c = FieldElement::new(BigUint::from(16u32), Rc::clone(&prime));
-        assert_eq!((&b.pow(&BigInt::from(5)) * &c).unwrap(), res_b_pow_5_mul_c);
-        let d = FieldElement::new(BigUint::from(17u32), Rc::clone(&prime));
-        let res_d_pow_minus3 =FieldElement::new(BigUint::from(29u32), Rc::clone(&prime));
-        assert_eq!(&d.pow(&BigInt::from(-3)), &res_d_pow_minus3);
-        assert_eq!(d.pow(&BigInt::from(-3)), res_d_pow_minus3);
-        let e = FieldElement::new(BigUint::from(4u32), Rc::clone(&prime));
-        let f = FieldElement::new(BigUint::from(11u32), Rc::clone(&prime));
-        let res_e_pow_minus4_mul_f = FieldElement::new(BigUint::from(13u32), Rc::clone(&prime));
-        assert_eq!((&e.pow(&BigInt::from(-4)) * &f).unwrap(), res_e_pow_minus4_mul_f);
+    fn test_div() {
+        assert_eq!((&fe(3) / &fe(24)).unwrap(), fe(4));
+        assert_eq!(&fe(3) / &fe(0), Err(FieldError::DivisionByZero));
     }
 
     #[test]
-    fn test_div() {
-        let prime = Rc::new(BigUint::from(31u32));
-        let a = FieldElement::new(BigUint::from(3u32), Rc::clone(&prime));
-        let b = FieldElement::new(BigUint::from(24u32), Rc::clone(&prime));
-        let res_div_a_b = FieldElement::new(BigUint::from(4u32), Rc::clone(&prime));
-        assert_eq!((&a/&b).unwrap(), res_div_a_b);
+    fn test_prime_field() {
+        assert_eq!(<FieldElement<F31> as PrimeField>::modulus(), &BigUint::from(31u32));
+        assert_eq!(FieldElement::<F31>::from_biguint(&BigUint::from(33u32)), fe(2));
+        assert_eq!(fe(3).inverse(), fe(21));
+        assert_eq!(fe(0).inverse(), FieldElement::zero());
+        assert!(FieldElement::<F31>::zero().is_zero());
+        assert_eq!(FieldElement::<F31>::one(), fe(1));
+        assert_eq!(fe(30).to_biguint(), BigUint::from(30u32));
     }
 
     #[test]
-    fn test_div_elements_in_different_fields() {
-        let prime_1 = Rc::new(BigUint::from(31u32));
-        let prime_2 = Rc::new(BigUint::from(29u32));
-        let a = FieldElement::new(BigUint::from(2u32), prime_1);
-        let b = FieldElement::new(BigUint::from(15u32), prime_2);
-        let res = &a / &b;
-        assert!(res.is_err());
+    fn test_sqrt() {
+        // 17 = 1 + 2⁴ takes the most Tonelli-Shanks rounds, 97 = 1 + 3·2⁵ and 223 = 3 mod 4
+        check_small_field::<F2>();
+        check_small_field::<F17>();
+        check_small_field::<F31>();
+        check_small_field::<F97>();
+        check_small_field::<F223>();
+
+        // 2^255 - 19 is 5 mod 8
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let x = FieldElement::<F25519>::new(num_bigint::RandBigInt::gen_biguint_below(&mut rng, F25519::modulus()));
+            let square = &x * &x;
+            assert!(square.is_square());
+            let root = square.sqrt().unwrap();
+            assert!(root == x || root == -&x);
+        }
+        // 2 is a non-residue modulo 2^255 - 19
+        let two = FieldElement::<F25519>::new(BigUint::from(2u32));
+        assert!(!two.is_square());
+        assert_eq!(two.sqrt(), None);
     }
 }
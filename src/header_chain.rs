@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+use num_bigint::BigUint;
+
+use crate::block::{BlockHash, BlockHeader};
+use crate::difficulty::next_work_required;
+use crate::network::Network;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainUpdate {
+    Known,
+    SideChain,
+    Extended,
+    Reorg { fork_height: u32, disconnected: Vec<BlockHash> },
+}
+
+#[derive(Debug, Clone)]
+struct ChainEntry {
+    header: BlockHeader,
+    height: u32,
+    chain_work: BigUint,
+}
+
+// Keeps every valid header it has seen, including the ones on side chains, and follows the
+// branch with the most cumulative work. Accepted headers are appended to `file` (if any) in
+// the order they arrived, so replaying the file rebuilds the same tree.
+#[derive(Debug)]
+pub struct HeaderChain {
+    network: Network,
+    entries: HashMap<BlockHash, ChainEntry>,
+    active: Vec<BlockHash>,
+    file: Option<File>,
+}
+
+impl HeaderChain {
+    pub fn new(network: Network) -> Self {
+        let genesis = network.genesis_header();
+        let hash = genesis.hash();
+        let entry = ChainEntry { chain_work: genesis.work(), header: genesis, height: 0 };
+
+        HeaderChain {
+            network,
+            entries: HashMap::from([(hash, entry)]),
+            active: vec![hash],
+            file: None,
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(network: Network, path: P) -> Result<Self, &'static str> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|_| "can't open header file")?;
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes).map_err(|_| "can't read header file")?;
+        if bytes.len() % 80 != 0 {
+            return Err("header file is corrupted");
+        }
+
+        let mut chain = HeaderChain::new(network);
+        let mut reader = Cursor::new(bytes);
+        while (reader.position() as usize) < reader.get_ref().len() {
+            let header = BlockHeader::parse(&mut reader)?;
+            chain.connect(header)?;
+        }
+        chain.file = Some(file);
+        Ok(chain)
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn tip(&self) -> &BlockHeader {
+        &self.entries[self.tip_hash()].header
+    }
+
+    pub fn tip_hash(&self) -> &BlockHash {
+        self.active.last().unwrap()
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.active.len() - 1) as u32
+    }
+
+    pub fn chain_work(&self) -> &BigUint {
+        &self.entries[self.tip_hash()].chain_work
+    }
+
+    pub fn header_at(&self, height: u32) -> Option<&BlockHeader> {
+        self.active.get(height as usize).map(|hash| &self.entries[hash].header)
+    }
+
+    pub fn get(&self, hash: &BlockHash) -> Option<&BlockHeader> {
+        self.entries.get(hash).map(|entry| &entry.header)
+    }
+
+    pub fn height_of(&self, hash: &BlockHash) -> Option<u32> {
+        self.entries.get(hash).map(|entry| entry.height)
+    }
+
+    pub fn contains(&self, hash: &BlockHash) -> bool {
+        self.entries.contains_key(hash)
+    }
+
+    pub fn is_active(&self, hash: &BlockHash) -> bool {
+        match self.entries.get(hash) {
+            Some(entry) => self.active.get(entry.height as usize) == Some(hash),
+            None => false,
+        }
+    }
+
+    // Hashes from the tip back to genesis, dense for the tip and the 10 blocks below it and
+    // then doubling the step, as Bitcoin Core builds them, so a peer on a different branch
+    // can find the fork point in one round trip.
+    pub fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut height = self.height() as i64;
+        let mut step = 1;
+        while height > 0 {
+            locator.push(self.active[height as usize]);
+            if locator.len() > 10 {
+                step *= 2;
+            }
+            height -= step;
+        }
+        locator.push(self.active[0]);
+        locator
+    }
+
+    pub fn accept(&mut self, header: BlockHeader) -> Result<ChainUpdate, &'static str> {
+        let serialized = header.serialize();
+        let update = self.connect(header)?;
+        if update != ChainUpdate::Known {
+            if let Some(file) = &mut self.file {
+                file.write_all(&serialized).map_err(|_| "can't write header file")?;
+            }
+        }
+        Ok(update)
+    }
+
+    // Stops at the first invalid header; the ones before it stay accepted.
+    pub fn accept_headers(&mut self, headers: &[BlockHeader]) -> Result<Vec<ChainUpdate>, &'static str> {
+        let mut updates = Vec::with_capacity(headers.len());
+        for header in headers {
+            updates.push(self.accept(header.clone())?);
+        }
+        if let Some(file) = &mut self.file {
+            file.flush().map_err(|_| "can't write header file")?;
+        }
+        Ok(updates)
+    }
+
+    // The entries of the branch that ends at `hash` down to where it joins the active chain,
+    // highest first. Empty when `hash` is on the active chain.
+    fn side_branch(&self, hash: &BlockHash) -> Vec<&ChainEntry> {
+        let mut branch = Vec::new();
+        let mut current = *hash;
+        while let Some(entry) = self.entries.get(&current) {
+            if self.is_active(&current) {
+                break;
+            }
+            branch.push(entry);
+            current = *entry.header.prev_block();
+        }
+        branch
+    }
+
+    fn connect(&mut self, header: BlockHeader) -> Result<ChainUpdate, &'static str> {
+        let hash = header.hash();
+        if self.entries.contains_key(&hash) {
+            return Ok(ChainUpdate::Known);
+        }
+
+        let parent = self.entries.get(header.prev_block()).ok_or("header does not connect to a known header")?;
+        if !header.check_pow(self.network.pow_limit()) {
+            return Err("header has invalid proof of work");
+        }
+
+        // the side branch is collected once, as the difficulty rules can look up every header
+        // back to the last retarget
+        let parent_hash = *header.prev_block();
+        let branch = self.side_branch(&parent_hash);
+        let branch_bottom = parent.height + 1 - branch.len() as u32;
+        let expected_bits = next_work_required(self.network, parent.height, &parent.header, header.timestamp(), |height| {
+            if height > parent.height {
+                None
+            } else if height >= branch_bottom {
+                Some(&branch[(parent.height - height) as usize].header)
+            } else {
+                self.header_at(height)
+            }
+        })?;
+        if header.bits() != expected_bits {
+            return Err("header has incorrect difficulty bits");
+        }
+
+        let entry = ChainEntry {
+            height: parent.height + 1,
+            chain_work: &parent.chain_work + header.work(),
+            header,
+        };
+        let has_more_work = &entry.chain_work > self.chain_work();
+        self.entries.insert(hash, entry);
+
+        if !has_more_work {
+            return Ok(ChainUpdate::SideChain);
+        }
+        if parent_hash == *self.tip_hash() {
+            self.active.push(hash);
+            return Ok(ChainUpdate::Extended);
+        }
+        Ok(self.reorganize(hash))
+    }
+
+    fn reorganize(&mut self, new_tip: BlockHash) -> ChainUpdate {
+        let mut branch = Vec::new();
+        let mut current = new_tip;
+        while !self.is_active(&current) {
+            branch.push(current);
+            current = *self.entries[&current].header.prev_block();
+        }
+
+        let fork_height = self.entries[&current].height;
+        let disconnected = self.active.split_off(fork_height as usize + 1);
+        self.active.extend(branch.into_iter().rev());
+        ChainUpdate::Reorg { fork_height, disconnected }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{mine_branch, mine_header, temp_path};
+
+    #[test]
+    fn test_new() {
+        let chain = HeaderChain::new(Network::Regtest);
+        assert_eq!(chain.height(), 0);
+        assert_eq!(*chain.tip(), Network::Regtest.genesis_header());
+        assert_eq!(*chain.chain_work(), BigUint::from(2u32));
+    }
+
+    #[test]
+    fn test_accept_extends_chain() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        let headers = mine_branch(chain.tip(), 5, 1);
+        let updates = chain.accept_headers(&headers).unwrap();
+        assert!(updates.iter().all(|update| *update == ChainUpdate::Extended));
+        assert_eq!(chain.height(), 5);
+        assert_eq!(*chain.tip(), headers[4]);
+        assert_eq!(chain.header_at(3), Some(&headers[2]));
+        assert_eq!(chain.height_of(&headers[2].hash()), Some(3));
+        assert_eq!(*chain.chain_work(), BigUint::from(12u32));
+
+        assert_eq!(chain.accept(headers[1].clone()).unwrap(), ChainUpdate::Known);
+    }
+
+    #[test]
+    fn test_accept_rejects_unconnected_header() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        let headers = mine_branch(chain.tip(), 2, 1);
+        assert!(chain.accept(headers[1].clone()).is_err());
+        assert_eq!(chain.height(), 0);
+    }
+
+    #[test]
+    fn test_accept_rejects_invalid_pow() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        let genesis = chain.tip().clone();
+        let mut nonce = 0;
+        let header = loop {
+            let header = BlockHeader::new(0x20000000, genesis.hash(), [0u8; 32], genesis.timestamp() + 600, 0x207fffff, nonce);
+            if !header.check_pow(Network::Regtest.pow_limit()) {
+                break header;
+            }
+            nonce += 1;
+        };
+        assert!(chain.accept(header).is_err());
+    }
+
+    #[test]
+    fn test_accept_rejects_incorrect_difficulty() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        let header = mine_header(&chain.tip().clone(), [1u8; 32], chain.tip().timestamp() + 600, 0x1f7fffff);
+        assert!(chain.accept(header).is_err());
+    }
+
+    #[test]
+    fn test_side_chain_and_reorg() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        let main = mine_branch(chain.tip(), 3, 1);
+        chain.accept_headers(&main).unwrap();
+
+        // a branch with the same work as the tip doesn't replace it
+        let fork = mine_branch(&main[0], 2, 100);
+        assert_eq!(chain.accept(fork[0].clone()).unwrap(), ChainUpdate::SideChain);
+        assert_eq!(chain.accept(fork[1].clone()).unwrap(), ChainUpdate::SideChain);
+        assert_eq!(*chain.tip(), main[2]);
+        assert!(chain.contains(&fork[1].hash()));
+        assert!(!chain.is_active(&fork[1].hash()));
+
+        let extra = mine_branch(&fork[1], 1, 200);
+        let update = chain.accept(extra[0].clone()).unwrap();
+        assert_eq!(
+            update,
+            ChainUpdate::Reorg { fork_height: 1, disconnected: vec![main[1].hash(), main[2].hash()] }
+        );
+        assert_eq!(chain.height(), 4);
+        assert_eq!(*chain.tip(), extra[0]);
+        assert_eq!(chain.header_at(2), Some(&fork[0]));
+        assert!(chain.is_active(&fork[1].hash()));
+        assert!(!chain.is_active(&main[2].hash()));
+    }
+
+    #[test]
+    fn test_locator() {
+        let mut chain = HeaderChain::new(Network::Regtest);
+        assert_eq!(chain.locator(), vec![*chain.tip_hash()]);
+
+        let headers = mine_branch(chain.tip(), 30, 1);
+        chain.accept_headers(&headers).unwrap();
+        let heights: Vec<u32> = chain.locator().iter().map(|hash| chain.height_of(hash).unwrap()).collect();
+        assert_eq!(heights, vec![30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 18, 14, 6, 0]);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let path = temp_path("header-chain-persistence");
+        let main;
+        let fork;
+        {
+            let mut chain = HeaderChain::open(Network::Regtest, &path).unwrap();
+            assert_eq!(chain.height(), 0);
+            main = mine_branch(chain.tip(), 4, 1);
+            fork = mine_branch(&main[1], 1, 100);
+            chain.accept_headers(&main).unwrap();
+            chain.accept_headers(&fork).unwrap();
+        }
+
+        let chain = HeaderChain::open(Network::Regtest, &path).unwrap();
+        assert_eq!(chain.height(), 4);
+        assert_eq!(*chain.tip(), main[3]);
+        assert!(chain.contains(&fork[0].hash()));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 5 * 80);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_corrupted_file() {
+        let path = temp_path("header-chain-corrupted");
+        std::fs::write(&path, [0u8; 81]).unwrap();
+        assert!(HeaderChain::open(Network::Regtest, &path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
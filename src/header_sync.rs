@@ -0,0 +1,199 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use tokio::time::timeout;
+
+use crate::block::BlockHeader;
+use crate::header_chain::{ChainUpdate, HeaderChain};
+use crate::network::{GetHeadersMessage, Network, NetworkMessage, MAX_HEADERS_RESULTS};
+use crate::peer::Peer;
+
+pub const HEADERS_RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// Asks the peer for headers until it answers with a partial batch, which means we have reached
+// its tip. Each request after the first starts from the last header received rather than from
+// our tip, so a heavier fork that takes more than one batch to overtake our chain is still
+// followed. Returns how many new headers were accepted.
+pub async fn sync_headers(peer: &mut Peer, chain: &mut HeaderChain) -> Result<usize, &'static str> {
+    if peer.network() != chain.network() {
+        return Err("peer and header chain are on different networks");
+    }
+
+    let mut accepted = 0;
+    let mut locator = chain.locator();
+    loop {
+        let getheaders = GetHeadersMessage::new(locator, [0u8; 32]);
+        peer.send(&NetworkMessage::GetHeaders(getheaders)).await?;
+
+        let headers = timeout(HEADERS_RESPONSE_TIMEOUT, receive_headers(peer))
+            .await
+            .map_err(|_| "timed out waiting for headers")??;
+
+        let updates = chain.accept_headers(&headers)?;
+        accepted += updates.iter().filter(|update| **update != ChainUpdate::Known).count();
+        match headers.last() {
+            Some(last) if headers.len() == MAX_HEADERS_RESULTS => {
+                locator = std::iter::once(last.hash()).chain(chain.locator()).collect();
+            }
+            _ => return Ok(accepted),
+        }
+    }
+}
+
+// Opens (or creates) the header file at `path` and follows the chain of the node at `addr`,
+// so an interrupted sync picks up from the last persisted header.
+pub async fn sync_from_peer<P: AsRef<Path>>(addr: SocketAddr, network: Network, path: P) -> Result<HeaderChain, &'static str> {
+    let mut chain = HeaderChain::open(network, path)?;
+    let mut peer = Peer::connect(addr, network, chain.height() as i32).await?;
+    sync_headers(&mut peer, &mut chain).await?;
+    Ok(chain)
+}
+
+async fn receive_headers(peer: &mut Peer) -> Result<Vec<BlockHeader>, &'static str> {
+    loop {
+        if let NetworkMessage::Headers(headers) = peer.receive().await? {
+            return Ok(headers);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::*;
+    use crate::network::VersionMessage;
+    use crate::peer::{read_message, write_message};
+    use crate::test_helpers::{mine_branch, temp_path};
+
+    const NETWORK: Network = Network::Regtest;
+
+    // genesis followed by `len` mined headers
+    fn mine_chain(len: usize) -> Vec<BlockHeader> {
+        let genesis = NETWORK.genesis_header();
+        let mut headers = mine_branch(&genesis, len, 0);
+        headers.insert(0, genesis);
+        headers
+    }
+
+    // Answers the handshake and then serves getheaders requests from `chain` (which starts at
+    // genesis) until the connection is closed. Returns the heights the requests started from.
+    async fn serve_headers(listener: TcpListener, chain: Vec<BlockHeader>) -> Vec<u32> {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        handshake(&mut stream, chain.len() as i32 - 1).await;
+
+        let hashes: Vec<_> = chain.iter().map(|header| header.hash()).collect();
+        let mut start_heights = Vec::new();
+        while let Ok(message) = read_message(&mut stream, NETWORK).await {
+            let getheaders = match message {
+                NetworkMessage::GetHeaders(getheaders) => getheaders,
+                _ => continue,
+            };
+            let start = getheaders
+                .locator()
+                .iter()
+                .find_map(|hash| hashes.iter().position(|known| known == hash))
+                .unwrap_or(0);
+            start_heights.push(start as u32);
+
+            let headers = chain.iter().skip(start + 1).take(MAX_HEADERS_RESULTS).cloned().collect();
+            write_message(&mut stream, NETWORK, &NetworkMessage::Headers(headers)).await.unwrap();
+        }
+        start_heights
+    }
+
+    async fn handshake(stream: &mut TcpStream, start_height: i32) {
+        read_message(stream, NETWORK).await.unwrap();
+        let version = VersionMessage::new("127.0.0.1:18444".parse().unwrap(), start_height);
+        write_message(stream, NETWORK, &NetworkMessage::Version(version)).await.unwrap();
+        write_message(stream, NETWORK, &NetworkMessage::Verack).await.unwrap();
+        while read_message(stream, NETWORK).await.unwrap() != NetworkMessage::Verack {}
+    }
+
+    #[tokio::test]
+    async fn test_sync_headers() {
+        let headers = mine_chain(2500);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = tokio::spawn(serve_headers(listener, headers.clone()));
+
+        let mut chain = HeaderChain::new(NETWORK);
+        let mut peer = Peer::connect(addr, NETWORK, 0).await.unwrap();
+        assert_eq!(sync_headers(&mut peer, &mut chain).await.unwrap(), 2500);
+        assert_eq!(chain.height(), 2500);
+        assert_eq!(chain.tip(), headers.last().unwrap());
+
+        // already at the peer's tip, a new request gets nothing back
+        assert_eq!(sync_headers(&mut peer, &mut chain).await.unwrap(), 0);
+        drop(peer);
+        assert_eq!(node.await.unwrap(), vec![0, 2000, 2500]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_resumes_from_persisted_headers() {
+        let path = temp_path("header-sync-resume");
+        let headers = mine_chain(2100);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = tokio::spawn(serve_headers(listener, headers[..1501].to_vec()));
+        let chain = sync_from_peer(addr, NETWORK, &path).await.unwrap();
+        assert_eq!(chain.height(), 1500);
+        drop(chain);
+        assert_eq!(node.await.unwrap(), vec![0]);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = tokio::spawn(serve_headers(listener, headers.clone()));
+        let chain = sync_from_peer(addr, NETWORK, &path).await.unwrap();
+        assert_eq!(chain.height(), 2100);
+        assert_eq!(chain.tip(), headers.last().unwrap());
+        drop(chain);
+        assert_eq!(node.await.unwrap(), vec![1500]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_follows_a_heavier_fork_across_batches() {
+        let ours = mine_chain(2100);
+        let mut chain = HeaderChain::new(NETWORK);
+        chain.accept_headers(&ours[1..]).unwrap();
+
+        // the first 2000 headers of the fork are lighter than our chain and only the second
+        // batch makes it the heaviest
+        let genesis = NETWORK.genesis_header();
+        let mut fork = mine_branch(&genesis, 2500, 128);
+        fork.insert(0, genesis);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = tokio::spawn(serve_headers(listener, fork.clone()));
+
+        let mut peer = Peer::connect(addr, NETWORK, 2100).await.unwrap();
+        assert_eq!(sync_headers(&mut peer, &mut chain).await.unwrap(), 2500);
+        assert_eq!(chain.height(), 2500);
+        assert_eq!(chain.tip(), fork.last().unwrap());
+        assert!(!chain.is_active(&ours[1].hash()));
+        drop(peer);
+        assert_eq!(node.await.unwrap(), vec![0, 2000]);
+    }
+
+    #[tokio::test]
+    async fn test_sync_rejects_invalid_headers() {
+        let mut headers = mine_chain(10);
+        headers[5] = BlockHeader::new(0x20000000, headers[4].hash(), [0u8; 32], 0, 0x1d00ffff, 0);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let node = tokio::spawn(serve_headers(listener, headers));
+
+        let mut chain = HeaderChain::new(NETWORK);
+        let mut peer = Peer::connect(addr, NETWORK, 0).await.unwrap();
+        assert!(sync_headers(&mut peer, &mut chain).await.is_err());
+        assert_eq!(chain.height(), 4);
+        drop(peer);
+        node.await.unwrap();
+    }
+}
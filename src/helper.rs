@@ -0,0 +1,298 @@
+use std::io::Read;
+
+use num_bigint::BigUint;
+
+use crate::cripto::hash256;
+
+// Decodes the compact "nBits" representation used in block headers: the high byte is a
+// base-256 exponent and the low 23 bits are the mantissa. Bit 24 is a sign flag, which is
+// never valid for a proof of work target.
+pub fn bits_to_target(bits: u32) -> Result<BigUint, &'static str> {
+    let size = bits >> 24;
+    let mut word = bits & 0x007f_ffff;
+    let target = if size <= 3 {
+        word >>= 8 * (3 - size);
+        BigUint::from(word)
+    } else {
+        BigUint::from(word) << (8 * (size - 3))
+    };
+
+    if word != 0 && bits & 0x0080_0000 != 0 {
+        return Err("compact target is negative");
+    }
+    if word != 0 && (size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32)) {
+        return Err("compact target overflows 256 bits");
+    }
+    Ok(target)
+}
+
+pub fn target_to_bits(target: &BigUint) -> u32 {
+    let mut size = target.bits().div_ceil(8) as u32;
+    let mut compact = if size <= 3 {
+        let low = target.iter_u32_digits().next().unwrap_or(0);
+        low << (8 * (3 - size))
+    } else {
+        let shifted: BigUint = target >> (8 * (size - 3));
+        shifted.iter_u32_digits().next().unwrap_or(0)
+    };
+
+    // the mantissa is signed, so if its top bit would be set we move one byte into the exponent
+    if compact & 0x0080_0000 != 0 {
+        compact >>= 8;
+        size += 1;
+    }
+
+    compact | (size << 24)
+}
+
+pub fn read_varint<R: Read>(reader: &mut R) -> Result<u64, &'static str> {
+    let prefix = read_bytes::<R, 1>(reader)?[0];
+    let (value, min) = match prefix {
+        0xfd => (u16::from_le_bytes(read_bytes(reader)?) as u64, 0xfd),
+        0xfe => (u32::from_le_bytes(read_bytes(reader)?) as u64, 0x10000),
+        0xff => (u64::from_le_bytes(read_bytes(reader)?), 0x100000000),
+        _ => return Ok(prefix as u64),
+    };
+    if value < min {
+        return Err("non-canonical varint");
+    }
+    Ok(value)
+}
+
+pub fn encode_varint(value: u64) -> Vec<u8> {
+    if value < 0xfd {
+        vec![value as u8]
+    } else if value <= 0xffff {
+        let mut result = vec![0xfd];
+        result.extend_from_slice(&(value as u16).to_le_bytes());
+        result
+    } else if value <= 0xffffffff {
+        let mut result = vec![0xfe];
+        result.extend_from_slice(&(value as u32).to_le_bytes());
+        result
+    } else {
+        let mut result = vec![0xff];
+        result.extend_from_slice(&value.to_le_bytes());
+        result
+    }
+}
+
+pub fn read_bytes<R: Read, const N: usize>(reader: &mut R) -> Result<[u8; N], &'static str> {
+    let mut buf = [0u8; N];
+    reader.read_exact(&mut buf).map_err(|_| "unexpected end of data")?;
+    Ok(buf)
+}
+
+// Reads a varint length followed by that many bytes. The buffer grows as data arrives, so a
+// bogus length can't make us allocate more than the input actually holds.
+pub fn read_var_bytes<R: Read>(reader: &mut R) -> Result<Vec<u8>, &'static str> {
+    let len = read_varint(reader)?;
+    let mut result = Vec::new();
+    reader.take(len).read_to_end(&mut result).map_err(|_| "unexpected end of data")?;
+    if result.len() as u64 != len {
+        return Err("unexpected end of data");
+    }
+    Ok(result)
+}
+
+pub fn encode_var_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut result = encode_varint(bytes.len() as u64);
+    result.extend_from_slice(bytes);
+    result
+}
+
+// MurmurHash3 (x86, 32 bit), the hash BIP 37 uses for the bloom filter functions
+pub fn murmur3(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e2d51;
+    const C2: u32 = 0x1b873593;
+
+    let mut h1 = seed;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let k1 = u32::from_le_bytes(chunk.try_into().unwrap());
+        h1 ^= k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h1 = h1.rotate_left(13).wrapping_mul(5).wrapping_add(0xe6546b64);
+    }
+
+    if !tail.is_empty() {
+        let k1 = tail.iter().rev().fold(0u32, |acc, byte| (acc << 8) | *byte as u32);
+        h1 ^= k1.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u32;
+    h1 ^= h1 >> 16;
+    h1 = h1.wrapping_mul(0x85ebca6b);
+    h1 ^= h1 >> 13;
+    h1 = h1.wrapping_mul(0xc2b2ae35);
+    h1 ^ (h1 >> 16)
+}
+
+// The merkle helpers work on hashes in the order they are serialized (little endian), not
+// the reversed order txids and block hashes are displayed in.
+pub fn merkle_parent(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = left.to_vec();
+    data.extend_from_slice(right);
+    hash256(&data).try_into().unwrap()
+}
+
+// an odd level is padded by pairing its last hash with itself
+pub fn merkle_parent_level(hashes: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    hashes
+        .chunks(2)
+        .map(|pair| merkle_parent(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+pub fn merkle_root(hashes: &[[u8; 32]]) -> Result<[u8; 32], &'static str> {
+    if hashes.is_empty() {
+        return Err("no hashes to build a merkle tree from");
+    }
+    let mut level = hashes.to_vec();
+    while level.len() > 1 {
+        level = merkle_parent_level(&level);
+    }
+    Ok(level[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_bits_to_target() {
+        let target = bits_to_target(0x1d00ffff).unwrap();
+        let expected = BigUint::from(0xffffu32) << (8 * (0x1d - 3));
+        assert_eq!(target, expected);
+
+        let target = bits_to_target(0x18013ce9).unwrap();
+        assert_eq!(
+            hex::encode(target.to_bytes_be()),
+            "013ce9000000000000000000000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_bits_to_target_edge_cases() {
+        for bits in [0u32, 0x00123456, 0x01003456, 0x02000056, 0x03000000, 0x04000000] {
+            assert_eq!(bits_to_target(bits).unwrap(), BigUint::ZERO);
+        }
+        // a set sign bit with a zero mantissa is still zero
+        for bits in [0x00923456u32, 0x01803456, 0x02800056, 0x03800000, 0x04800000] {
+            assert_eq!(bits_to_target(bits).unwrap(), BigUint::ZERO);
+        }
+
+        assert_eq!(bits_to_target(0x01123456).unwrap(), BigUint::from(0x12u32));
+        assert_eq!(bits_to_target(0x02123456).unwrap(), BigUint::from(0x1234u32));
+        assert_eq!(bits_to_target(0x03123456).unwrap(), BigUint::from(0x123456u32));
+        assert_eq!(bits_to_target(0x04123456).unwrap(), BigUint::from(0x12345600u32));
+        assert_eq!(bits_to_target(0x05009234).unwrap(), BigUint::from(0x92340000u32));
+        assert_eq!(
+            bits_to_target(0x20123456).unwrap(),
+            BigUint::from(0x123456u32) << (8 * 29)
+        );
+
+        assert!(bits_to_target(0x01fedcba).is_err());
+        assert!(bits_to_target(0x04923456).is_err());
+        assert!(bits_to_target(0xff123456).is_err());
+        assert!(bits_to_target(0x21010000).is_err());
+    }
+
+    #[test]
+    fn test_target_to_bits() {
+        assert_eq!(target_to_bits(&BigUint::ZERO), 0);
+        assert_eq!(target_to_bits(&BigUint::from(0x12u32)), 0x01120000);
+        assert_eq!(target_to_bits(&BigUint::from(0x80u32)), 0x02008000);
+        assert_eq!(target_to_bits(&BigUint::from(0x1234u32)), 0x02123400);
+        assert_eq!(target_to_bits(&BigUint::from(0x123456u32)), 0x03123456);
+        assert_eq!(target_to_bits(&BigUint::from(0x12345600u32)), 0x04123456);
+        assert_eq!(target_to_bits(&BigUint::from(0x92340000u32)), 0x05009234);
+        assert_eq!(target_to_bits(&(BigUint::from(0x123456u32) << (8 * 29))), 0x20123456);
+
+        let target = bits_to_target(0x1d00ffff).unwrap();
+        assert_eq!(target_to_bits(&target), 0x1d00ffff);
+    }
+
+    #[test]
+    fn test_varint() {
+        for (value, encoded) in [
+            (0u64, "00"),
+            (0xfc, "fc"),
+            (0xfd, "fdfd00"),
+            (0xffff, "fdffff"),
+            (0x10000, "fe00000100"),
+            (0xffffffff, "feffffffff"),
+            (0x100000000, "ff0000000001000000"),
+        ] {
+            assert_eq!(hex::encode(encode_varint(value)), encoded);
+            let bytes = hex::decode(encoded).unwrap();
+            assert_eq!(read_varint(&mut Cursor::new(bytes)).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_read_varint_invalid() {
+        for encoded in ["fdfc00", "feffff0000", "ffffffffff00000000", "fd00"] {
+            let bytes = hex::decode(encoded).unwrap();
+            assert!(read_varint(&mut Cursor::new(bytes)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_var_bytes() {
+        let encoded = encode_var_bytes(b"hello");
+        assert_eq!(hex::encode(&encoded), "0568656c6c6f");
+        assert_eq!(read_var_bytes(&mut Cursor::new(encoded)).unwrap(), b"hello");
+        assert!(read_var_bytes(&mut Cursor::new(hex::decode("0668656c6c6f").unwrap())).is_err());
+    }
+
+    #[test]
+    fn test_murmur3() {
+        for (expected, seed, data) in [
+            (0x00000000u32, 0x00000000u32, ""),
+            (0x6a396f08, 0xfba4c795, ""),
+            (0x81f16f39, 0xffffffff, ""),
+            (0x514e28b7, 0x00000000, "00"),
+            (0xea3f0b17, 0xfba4c795, "00"),
+            (0xfd6cf10d, 0x00000000, "ff"),
+            (0x16c6b7ab, 0x00000000, "0011"),
+            (0x8eb51c3d, 0x00000000, "001122"),
+            (0xb4471bf8, 0x00000000, "00112233"),
+            (0xe2301fa8, 0x00000000, "0011223344"),
+            (0xfc2e4a15, 0x00000000, "001122334455"),
+            (0xb074502c, 0x00000000, "00112233445566"),
+            (0x8034d2a0, 0x00000000, "0011223344556677"),
+            (0xb4698def, 0x00000000, "001122334455667788"),
+        ] {
+            assert_eq!(murmur3(&hex::decode(data).unwrap(), seed), expected);
+        }
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        // the four transactions of block 100000
+        let hashes: Vec<[u8; 32]> = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .iter()
+        .map(|txid| {
+            let mut hash: [u8; 32] = hex::decode(txid).unwrap().try_into().unwrap();
+            hash.reverse();
+            hash
+        })
+        .collect();
+
+        let mut root = merkle_root(&hashes).unwrap();
+        root.reverse();
+        assert_eq!(hex::encode(root), "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766");
+
+        assert_eq!(merkle_root(&hashes[..1]).unwrap(), hashes[0]);
+        assert_eq!(merkle_root(&hashes[..3]).unwrap(), merkle_parent(&merkle_parent(&hashes[0], &hashes[1]), &merkle_parent(&hashes[2], &hashes[2])));
+        assert!(merkle_root(&[]).is_err());
+    }
+}
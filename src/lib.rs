@@ -0,0 +1,23 @@
+pub mod error;
+pub mod finite_field;
+pub mod elliptic_curve;
+pub mod discrete_log;
+pub mod named_curve;
+pub mod p256;
+pub mod scalar;
+pub mod secp256k1;
+pub mod cripto;
+pub mod helper;
+pub mod block;
+pub mod network;
+pub mod difficulty;
+pub mod header_chain;
+pub mod tx;
+pub mod peer;
+pub mod header_sync;
+pub mod bloom_filter;
+pub mod merkle_block;
+pub mod block_filter;
+
+#[cfg(test)]
+mod test_helpers;
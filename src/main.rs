@@ -1,11 +1,4 @@
-mod finite_field;
-mod elliptic_curve;
-mod secp256k1;
-mod cripto;
-
 // use elliptic_curve::{S256Point, N_S256};
-use num_bigint::BigUint;
-use sha2::{Sha256, Digest};
 
 fn main() {
     // let prime = BigUint::from(13u32);
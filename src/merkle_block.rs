@@ -0,0 +1,364 @@
+use std::io::Read;
+
+use crate::block::{Block, BlockHeader};
+use crate::header_chain::HeaderChain;
+use crate::helper::{encode_var_bytes, encode_varint, merkle_parent, read_bytes, read_var_bytes, read_varint};
+use crate::tx::Txid;
+
+// a block can't hold more transactions than fit in 4M weight units at 240 units each
+pub const MAX_MERKLE_BLOCK_TXS: u32 = 4_000_000 / 240;
+
+// A block header together with a partial merkle tree: the hashes and flag bits of a depth
+// first walk of the tree, which are enough to recompute the merkle root while revealing
+// only the matched txids.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBlock {
+    header: BlockHeader,
+    total_txs: u32,
+    hashes: Vec<[u8; 32]>,
+    flags: Vec<u8>,
+}
+
+impl MerkleBlock {
+    pub fn new(header: BlockHeader, total_txs: u32, hashes: Vec<[u8; 32]>, flags: Vec<u8>) -> Self {
+        MerkleBlock { header, total_txs, hashes, flags }
+    }
+
+    // what a full node sends back for a filtered block, with `matches` being the txids that
+    // passed the peer's filter. A block always has a coinbase, so one without transactions is
+    // refused rather than given a tree with no root.
+    pub fn from_block(block: &Block, matches: &[Txid]) -> Result<Self, &'static str> {
+        if block.txs().is_empty() {
+            return Err("block has no transactions");
+        }
+        let txids: Vec<[u8; 32]> = block.txs().iter().map(|tx| reversed(tx.id())).collect();
+        let matched: Vec<bool> = block.txs().iter().map(|tx| matches.contains(&tx.id())).collect();
+
+        let mut height = 0;
+        while tree_width(txids.len(), height) > 1 {
+            height += 1;
+        }
+        let mut bits = Vec::new();
+        let mut hashes = Vec::new();
+        build(height, 0, &txids, &matched, &mut bits, &mut hashes);
+
+        let mut flags = vec![0u8; bits.len().div_ceil(8)];
+        for (i, bit) in bits.iter().enumerate() {
+            flags[i / 8] |= (*bit as u8) << (i % 8);
+        }
+        let hashes = hashes.into_iter().map(reversed).collect();
+        Ok(MerkleBlock::new(block.header().clone(), txids.len() as u32, hashes, flags))
+    }
+
+    // hashes are kept in display order, like txids
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let header = BlockHeader::parse(reader)?;
+        let total_txs = u32::from_le_bytes(read_bytes(reader)?);
+        if total_txs > MAX_MERKLE_BLOCK_TXS {
+            return Err("too many transactions for a block");
+        }
+        let num_hashes = read_varint(reader)?;
+        if num_hashes > total_txs as u64 {
+            return Err("more hashes than transactions");
+        }
+        // the count comes from the peer, so nothing is reserved up front
+        let mut hashes = Vec::new();
+        for _ in 0..num_hashes {
+            hashes.push(reversed(read_bytes(reader)?));
+        }
+        let flags = read_var_bytes(reader)?;
+        Ok(MerkleBlock { header, total_txs, hashes, flags })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.header.serialize();
+        result.extend_from_slice(&self.total_txs.to_le_bytes());
+        result.extend(encode_varint(self.hashes.len() as u64));
+        for hash in &self.hashes {
+            result.extend(hash.iter().rev());
+        }
+        result.extend(encode_var_bytes(&self.flags));
+        result
+    }
+
+    // Walks the partial tree and checks that it hashes up to the header's merkle root, using
+    // every hash and flag bit. Returns the matched txids in block order.
+    pub fn extract_matches(&self) -> Result<Vec<Txid>, &'static str> {
+        if self.total_txs == 0 {
+            return Err("merkle block without transactions");
+        }
+        if self.total_txs > MAX_MERKLE_BLOCK_TXS {
+            return Err("too many transactions for a block");
+        }
+        if self.hashes.len() > self.total_txs as usize {
+            return Err("more hashes than transactions");
+        }
+        if self.flags.len() * 8 < self.hashes.len() {
+            return Err("fewer flag bits than hashes");
+        }
+
+        let hashes: Vec<[u8; 32]> = self.hashes.iter().copied().map(reversed).collect();
+        let mut tree = PartialTree {
+            total_txs: self.total_txs as usize,
+            hashes: &hashes,
+            flags: &self.flags,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: Vec::new(),
+        };
+        let mut height = 0;
+        while tree_width(tree.total_txs, height) > 1 {
+            height += 1;
+        }
+        let root = tree.traverse(height, 0)?;
+
+        if tree.bits_used.div_ceil(8) != self.flags.len() {
+            return Err("not all flag bits were used");
+        }
+        if tree.hashes_used != hashes.len() {
+            return Err("not all hashes were used");
+        }
+        if reversed(root) != *self.header.merkle_root() {
+            return Err("merkle root does not match the header");
+        }
+        Ok(tree.matches.into_iter().map(reversed).collect())
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.extract_matches().is_ok()
+    }
+
+    // Confirms that `txid` is in a block on the active branch of `chain`, whose headers have
+    // already been checked, and returns that block's height.
+    pub fn verify_inclusion(&self, txid: &Txid, chain: &HeaderChain) -> Result<u32, &'static str> {
+        let hash = self.header.hash();
+        if !chain.is_active(&hash) {
+            return Err("block is not on the active chain");
+        }
+        if !self.extract_matches()?.contains(txid) {
+            return Err("transaction is not in the merkle block");
+        }
+        chain.height_of(&hash).ok_or("block is not on the active chain")
+    }
+
+    pub fn header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn total_txs(&self) -> u32 {
+        self.total_txs
+    }
+
+    pub fn hashes(&self) -> &[[u8; 32]] {
+        &self.hashes
+    }
+
+    pub fn flags(&self) -> &[u8] {
+        &self.flags
+    }
+}
+
+struct PartialTree<'a> {
+    total_txs: usize,
+    hashes: &'a [[u8; 32]],
+    flags: &'a [u8],
+    bits_used: usize,
+    hashes_used: usize,
+    matches: Vec<[u8; 32]>,
+}
+
+impl PartialTree<'_> {
+    fn traverse(&mut self, height: u32, pos: usize) -> Result<[u8; 32], &'static str> {
+        if self.bits_used >= self.flags.len() * 8 {
+            return Err("ran out of flag bits");
+        }
+        let parent_of_match = (self.flags[self.bits_used / 8] >> (self.bits_used % 8)) & 1 == 1;
+        self.bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self.hashes.get(self.hashes_used).ok_or("ran out of hashes")?;
+            self.hashes_used += 1;
+            if height == 0 && parent_of_match {
+                self.matches.push(hash);
+            }
+            return Ok(hash);
+        }
+
+        let left = self.traverse(height - 1, pos * 2)?;
+        let right = if pos * 2 + 1 < tree_width(self.total_txs, height - 1) {
+            let right = self.traverse(height - 1, pos * 2 + 1)?;
+            // two equal children only come from duplicating the last hash of a level, a proof
+            // that spells them out could pass off a different transaction count (CVE-2012-2459)
+            if right == left {
+                return Err("duplicate hashes in merkle tree");
+            }
+            right
+        } else {
+            left
+        };
+        Ok(merkle_parent(&left, &right))
+    }
+}
+
+// number of nodes at `height` above the leaves
+fn tree_width(total_txs: usize, height: u32) -> usize {
+    (total_txs + (1 << height) - 1) >> height
+}
+
+fn tree_hash(height: u32, pos: usize, txids: &[[u8; 32]]) -> [u8; 32] {
+    if height == 0 {
+        return txids[pos];
+    }
+    let left = tree_hash(height - 1, pos * 2, txids);
+    let right = if pos * 2 + 1 < tree_width(txids.len(), height - 1) {
+        tree_hash(height - 1, pos * 2 + 1, txids)
+    } else {
+        left
+    };
+    merkle_parent(&left, &right)
+}
+
+// Only descends into subtrees holding a match, everything else is summarized by one hash.
+fn build(height: u32, pos: usize, txids: &[[u8; 32]], matched: &[bool], bits: &mut Vec<bool>, hashes: &mut Vec<[u8; 32]>) {
+    let start = pos << height;
+    let end = ((pos + 1) << height).min(txids.len());
+    let parent_of_match = matched[start..end].iter().any(|m| *m);
+    bits.push(parent_of_match);
+
+    if height == 0 || !parent_of_match {
+        hashes.push(tree_hash(height, pos, txids));
+    } else {
+        build(height - 1, pos * 2, txids, matched, bits, hashes);
+        if pos * 2 + 1 < tree_width(txids.len(), height - 1) {
+            build(height - 1, pos * 2 + 1, txids, matched, bits, hashes);
+        }
+    }
+}
+
+fn reversed(mut hash: [u8; 32]) -> [u8; 32] {
+    hash.reverse();
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::helper::merkle_root;
+    use crate::network::Network;
+    use crate::test_helpers;
+    use crate::tx::{Tx, TxIn, TxOut};
+
+    const NETWORK: Network = Network::Regtest;
+
+    fn tx(n: u8) -> Tx {
+        Tx::new(1, vec![TxIn::new([n; 32], 0, Vec::new(), 0xffffffff)], vec![TxOut::new(n as u64, Vec::new())], 0)
+    }
+
+    // mines a block with `count` transactions on top of the regtest genesis block
+    fn mine_block(count: u8) -> Block {
+        test_helpers::mine_block(&NETWORK.genesis_header(), (0..count).map(tx).collect())
+    }
+
+    #[test]
+    fn test_from_block() {
+        for count in 1..=9 {
+            let block = mine_block(count);
+            let txids: Vec<Txid> = block.txs().iter().map(|tx| tx.id()).collect();
+            for matches in [vec![], vec![0], vec![count as usize - 1], (0..count as usize).step_by(3).collect()] {
+                let matched: Vec<Txid> = matches.iter().map(|i| txids[*i]).collect();
+                let merkle_block = MerkleBlock::from_block(&block, &matched).unwrap();
+                assert_eq!(merkle_block.total_txs(), count as u32);
+                assert_eq!(merkle_block.extract_matches().unwrap(), matched);
+            }
+        }
+
+        // matching everything spells out every txid
+        let block = mine_block(7);
+        let txids: Vec<Txid> = block.txs().iter().map(|tx| tx.id()).collect();
+        let merkle_block = MerkleBlock::from_block(&block, &txids).unwrap();
+        assert_eq!(merkle_block.hashes(), &txids[..]);
+
+        let empty = Block::new(NETWORK.genesis_header(), vec![]);
+        assert_eq!(MerkleBlock::from_block(&empty, &[]), Err("block has no transactions"));
+    }
+
+    #[test]
+    fn test_parse() {
+        let block = mine_block(7);
+        let merkle_block = MerkleBlock::from_block(&block, &[block.txs()[1].id(), block.txs()[4].id()]).unwrap();
+        let serialized = merkle_block.serialize();
+        let parsed = MerkleBlock::parse(&mut Cursor::new(&serialized)).unwrap();
+        assert_eq!(parsed, merkle_block);
+        assert!(parsed.is_valid());
+        assert!(MerkleBlock::parse(&mut Cursor::new(&serialized[..serialized.len() - 1])).is_err());
+    }
+
+    #[test]
+    fn test_parse_huge_counts() {
+        // a short payload claiming 2^32 - 1 transactions and hashes must fail before allocating
+        let mut payload = NETWORK.genesis_header().serialize();
+        payload.extend_from_slice(&u32::MAX.to_le_bytes());
+        payload.extend(encode_varint(u32::MAX as u64));
+        assert_eq!(MerkleBlock::parse(&mut Cursor::new(&payload)), Err("too many transactions for a block"));
+
+        let mut payload = NETWORK.genesis_header().serialize();
+        payload.extend_from_slice(&MAX_MERKLE_BLOCK_TXS.to_le_bytes());
+        payload.extend(encode_varint(MAX_MERKLE_BLOCK_TXS as u64));
+        assert_eq!(MerkleBlock::parse(&mut Cursor::new(&payload)), Err("unexpected end of data"));
+    }
+
+    #[test]
+    fn test_invalid_proofs() {
+        let block = mine_block(7);
+        let merkle_block = MerkleBlock::from_block(&block, &[block.txs()[1].id(), block.txs()[4].id()]).unwrap();
+        assert!(merkle_block.is_valid());
+
+        let mut hashes = merkle_block.hashes().to_vec();
+        hashes[1][0] ^= 1;
+        let tampered = MerkleBlock::new(block.header().clone(), 7, hashes, merkle_block.flags().to_vec());
+        assert!(!tampered.is_valid());
+
+        let mut flags = merkle_block.flags().to_vec();
+        flags.push(0);
+        let extra_flags = MerkleBlock::new(block.header().clone(), 7, merkle_block.hashes().to_vec(), flags);
+        assert!(!extra_flags.is_valid());
+
+        let mut hashes = merkle_block.hashes().to_vec();
+        hashes.push([0u8; 32]);
+        let extra_hash = MerkleBlock::new(block.header().clone(), 7, hashes, merkle_block.flags().to_vec());
+        assert!(!extra_hash.is_valid());
+
+        let wrong_count = MerkleBlock::new(block.header().clone(), 4, merkle_block.hashes().to_vec(), merkle_block.flags().to_vec());
+        assert!(!wrong_count.is_valid());
+
+        let other_block = mine_block(6);
+        let wrong_header = MerkleBlock::new(other_block.header().clone(), 7, merkle_block.hashes().to_vec(), merkle_block.flags().to_vec());
+        assert!(!wrong_header.is_valid());
+    }
+
+    #[test]
+    fn test_duplicate_hashes() {
+        // both leaves of a two transaction tree spelled out with the same hash
+        let txid = reversed(tx(0).id());
+        let root = reversed(merkle_root(&[txid, txid]).unwrap());
+        let header = BlockHeader::new(1, [0u8; 32], root, 0, 0x207fffff, 0);
+        let merkle_block = MerkleBlock::new(header, 2, vec![tx(0).id(), tx(0).id()], vec![0b111]);
+        assert_eq!(merkle_block.extract_matches(), Err("duplicate hashes in merkle tree"));
+    }
+
+    #[test]
+    fn test_verify_inclusion() {
+        let block = mine_block(5);
+        let txid = block.txs()[3].id();
+        let merkle_block = MerkleBlock::from_block(&block, &[txid]).unwrap();
+
+        let mut chain = HeaderChain::new(NETWORK);
+        assert!(merkle_block.verify_inclusion(&txid, &chain).is_err());
+
+        chain.accept(block.header().clone()).unwrap();
+        assert_eq!(merkle_block.verify_inclusion(&txid, &chain), Ok(1));
+        assert!(merkle_block.verify_inclusion(&block.txs()[2].id(), &chain).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use num_bigint::BigUint;
+
+use crate::elliptic_curve::{EllipticCurve, Endomorphism, Point};
+use crate::error::CurveError;
+use crate::finite_field::{FieldRef, PrimeField, P};
+use crate::secp256k1::{glv_decompose, BETA};
+
+// field types for the named curves, to use as FieldElement<P256Prime> and so on
+crate::modulus!(pub Secp256k1Prime = P.clone());
+crate::modulus!(pub P256Prime = hex_num(P256_P));
+crate::modulus!(pub F223 = 223u32);
+
+const P256_P: &str = "ffffffff00000001000000000000000000000000ffffffffffffffffffffffff";
+
+// The domain parameters of a curve: y² = x³ + a·x + b over F_p, a generator G of prime order n
+// and the cofactor h, the number of points divided by n. They are kept as plain numbers so any
+// field type with the right prime can use them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurveParams {
+    name: &'static str,
+    p: BigUint,
+    a: BigUint,
+    b: BigUint,
+    gx: BigUint,
+    gy: BigUint,
+    n: BigUint,
+    h: BigUint,
+}
+
+impl CurveParams {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn p(&self) -> &BigUint {
+        &self.p
+    }
+
+    pub fn a(&self) -> &BigUint {
+        &self.a
+    }
+
+    pub fn b(&self) -> &BigUint {
+        &self.b
+    }
+
+    pub fn gx(&self) -> &BigUint {
+        &self.gx
+    }
+
+    pub fn gy(&self) -> &BigUint {
+        &self.gy
+    }
+
+    pub fn n(&self) -> &BigUint {
+        &self.n
+    }
+
+    pub fn h(&self) -> &BigUint {
+        &self.h
+    }
+}
+
+lazy_static! {
+    static ref REGISTRY: Vec<CurveParams> = vec![
+        // SEC 2, section 2.4.1
+        CurveParams {
+            name: "secp256k1",
+            p: P.clone(),
+            a: BigUint::ZERO,
+            b: BigUint::from(7u32),
+            gx: hex_num("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"),
+            gy: hex_num("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8"),
+            n: hex_num("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141"),
+            h: BigUint::from(1u32),
+        },
+        // SEC 2, section 2.4.2, also known as NIST P-256 and prime256v1
+        CurveParams {
+            name: "secp256r1",
+            p: hex_num(P256_P),
+            a: hex_num("ffffffff00000001000000000000000000000000fffffffffffffffffffffffc"),
+            b: hex_num("5ac635d8aa3a93e7b3ebbd55769886bc651d06b0cc53b0f63bce3c3e27d2604b"),
+            gx: hex_num("6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296"),
+            gy: hex_num("4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5"),
+            n: hex_num("ffffffff00000000ffffffffffffffffbce6faada7179e84f3b9cac2fc632551"),
+            h: BigUint::from(1u32),
+        },
+        // the book's y² = x³ + 7 over F_223, whose 252 points hold (47, 71) of order 21
+        CurveParams {
+            name: "f223",
+            p: BigUint::from(223u32),
+            a: BigUint::ZERO,
+            b: BigUint::from(7u32),
+            gx: BigUint::from(47u32),
+            gy: BigUint::from(71u32),
+            n: BigUint::from(21u32),
+            h: BigUint::from(12u32),
+        },
+    ];
+}
+
+fn hex_num(hex: &str) -> BigUint {
+    BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+}
+
+pub fn curve_params(name: &str) -> Option<&'static CurveParams> {
+    REGISTRY.iter().find(|params| params.name == name)
+}
+
+pub fn curve_names() -> Vec<&'static str> {
+    REGISTRY.iter().map(|params| params.name).collect()
+}
+
+// the GLV endomorphism of the registered curves that have one
+fn endomorphism<F: PrimeField>(name: &str) -> Option<Endomorphism<F>> {
+    match name {
+        "secp256k1" => Some(Endomorphism::new(F::from_biguint(&BETA.num()), glv_decompose)),
+        _ => None,
+    }
+}
+
+// A registered curve built over the field type F, which has to have the curve's prime
+#[derive(Debug, Clone)]
+pub struct NamedCurve<F> {
+    params: &'static CurveParams,
+    curve: Arc<EllipticCurve<F>>,
+    generator: Point<F>,
+}
+
+impl<F: PrimeField> NamedCurve<F> where for<'a> &'a F: FieldRef<F> {
+    pub fn by_name(name: &str) -> Result<Self, CurveError> {
+        let params = match curve_params(name) {
+            Some(params) if params.p == *F::modulus() => params,
+            _ => return Err(CurveError::UnknownCurve(name.to_string())),
+        };
+        let mut curve = EllipticCurve::try_new(F::from_biguint(&params.a), F::from_biguint(&params.b))?;
+        if let Some(endomorphism) = endomorphism(name) {
+            curve = curve.with_endomorphism(endomorphism);
+        }
+        curve = curve.with_known_order(&params.n * &params.h);
+        let curve = Arc::new(curve);
+        let generator = Point::from(Arc::clone(&curve), F::from_biguint(&params.gx), F::from_biguint(&params.gy))?;
+        Ok(NamedCurve { params, curve, generator })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.params.name
+    }
+
+    pub fn curve(&self) -> &Arc<EllipticCurve<F>> {
+        &self.curve
+    }
+
+    pub fn generator(&self) -> &Point<F> {
+        &self.generator
+    }
+
+    pub fn order(&self) -> &BigUint {
+        &self.params.n
+    }
+
+    pub fn cofactor(&self) -> &BigUint {
+        &self.params.h
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finite_field::FieldElement;
+    use crate::secp256k1::{S256Field, S256Point, N_S256};
+
+    #[test]
+    fn test_registry() {
+        assert_eq!(curve_names(), vec!["secp256k1", "secp256r1", "f223"]);
+        for name in curve_names() {
+            let params = curve_params(name).unwrap();
+            assert_eq!(params.name(), name);
+            assert!(params.a() < params.p() && params.b() < params.p());
+        }
+        assert!(curve_params("secp384r1").is_none());
+    }
+
+    #[test]
+    fn test_generators_have_the_registered_order() {
+        let secp256k1 = NamedCurve::<FieldElement<Secp256k1Prime>>::by_name("secp256k1").unwrap();
+        assert!((secp256k1.generator() * secp256k1.order().clone()).is_infinity());
+        assert_eq!(*secp256k1.order(), *N_S256);
+
+        let p256 = NamedCurve::<FieldElement<P256Prime>>::by_name("secp256r1").unwrap();
+        assert!((p256.generator() * p256.order().clone()).is_infinity());
+        assert!(!(p256.generator() * (p256.order() - 1u32)).is_infinity());
+
+        // small enough to count, so the cofactor can be checked too
+        let toy = NamedCurve::<FieldElement<F223>>::by_name("f223").unwrap();
+        assert_eq!(toy.generator().order().as_ref(), Some(toy.order()));
+        assert_eq!(toy.curve().order(), Some(toy.order() * toy.cofactor()));
+    }
+
+    #[test]
+    fn test_secp256k1_over_s256_field() {
+        let named = NamedCurve::<S256Field>::by_name("secp256k1").unwrap();
+        assert_eq!(named.name(), "secp256k1");
+        assert_eq!(named.generator().to_compressed(), S256Point::mul_generator(&BigUint::from(1u32)).compressed_sec());
+    }
+
+    #[test]
+    fn test_unknown_curve() {
+        assert_eq!(NamedCurve::<FieldElement<F223>>::by_name("f224").unwrap_err(), CurveError::UnknownCurve("f224".to_string()));
+        // there is a secp256r1, but not over the secp256k1 prime
+        let err = NamedCurve::<S256Field>::by_name("secp256r1").unwrap_err();
+        assert_eq!(err.to_string(), "no curve named secp256r1 over this field");
+    }
+}
@@ -0,0 +1,964 @@
+use std::io::{Cursor, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use num_bigint::BigUint;
+
+use crate::block::{Block, BlockHash, BlockHeader};
+use crate::block_filter::{BlockFilter, FilterHash, FilterHeader, BASIC_FILTER_TYPE};
+use crate::bloom_filter::{BloomFilter, MAX_FILTER_ADD_SIZE};
+use crate::cripto::hash256;
+use crate::helper::{encode_var_bytes, encode_varint, read_bytes, read_var_bytes, read_varint};
+use crate::merkle_block::MerkleBlock;
+use crate::tx::Tx;
+
+use lazy_static::lazy_static;
+
+lazy_static! {
+    static ref MAINNET_POW_LIMIT: BigUint = BigUint::from_bytes_be(&hex::decode("00000000ffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap());
+    static ref REGTEST_POW_LIMIT: BigUint = BigUint::from_bytes_be(&hex::decode("7fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap());
+    static ref GENESIS_MERKLE_ROOT: [u8; 32] = hex::decode("4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b").unwrap().try_into().unwrap();
+}
+
+pub const PROTOCOL_VERSION: i32 = 70016;
+pub const USER_AGENT: &str = "/rust-bitcoin:0.1.0/";
+pub const MAX_PAYLOAD_SIZE: usize = 32 * 1024 * 1024;
+pub const MAX_HEADERS_RESULTS: usize = 2000;
+pub const MAX_INV_SIZE: usize = 50000;
+pub const MAX_LOCATOR_SIZE: usize = 101;
+pub const MAX_GETCFILTERS_SIZE: u32 = 1000;
+pub const MAX_GETCFHEADERS_SIZE: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    pub fn pow_limit(&self) -> &'static BigUint {
+        match self {
+            Network::Mainnet | Network::Testnet => &MAINNET_POW_LIMIT,
+            Network::Regtest => &REGTEST_POW_LIMIT,
+        }
+    }
+
+    // testnet and regtest accept a block at the minimum difficulty when it comes more than
+    // 20 minutes after its parent
+    pub fn allow_min_difficulty_blocks(&self) -> bool {
+        match self {
+            Network::Mainnet => false,
+            Network::Testnet | Network::Regtest => true,
+        }
+    }
+
+    pub fn no_retargeting(&self) -> bool {
+        *self == Network::Regtest
+    }
+
+    pub fn magic(&self) -> [u8; 4] {
+        match self {
+            Network::Mainnet => [0xf9, 0xbe, 0xb4, 0xd9],
+            Network::Testnet => [0x0b, 0x11, 0x09, 0x07],
+            Network::Regtest => [0xfa, 0xbf, 0xb5, 0xda],
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Network::Mainnet => 8333,
+            Network::Testnet => 18333,
+            Network::Regtest => 18444,
+        }
+    }
+
+    pub fn genesis_header(&self) -> BlockHeader {
+        let (timestamp, bits, nonce) = match self {
+            Network::Mainnet => (1231006505, 0x1d00ffff, 2083236893),
+            Network::Testnet => (1296688602, 0x1d00ffff, 414098458),
+            Network::Regtest => (1296688602, 0x207fffff, 2),
+        };
+        BlockHeader::new(1, [0u8; 32], *GENESIS_MERKLE_ROOT, timestamp, bits, nonce)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkEnvelope {
+    network: Network,
+    command: String,
+    payload: Vec<u8>,
+}
+
+impl NetworkEnvelope {
+    // the command has to fit the 12 byte field as printable ASCII, the same rule parse applies
+    pub fn new(network: Network, command: &str, payload: Vec<u8>) -> Result<Self, &'static str> {
+        if !valid_command(command.as_bytes()) {
+            return Err("invalid command");
+        }
+        Ok(NetworkEnvelope { network, command: command.to_string(), payload })
+    }
+
+    pub fn parse<R: Read>(reader: &mut R, network: Network) -> Result<Self, &'static str> {
+        let magic: [u8; 4] = read_bytes(reader)?;
+        if magic != network.magic() {
+            return Err("magic is not right");
+        }
+
+        // the command is NUL padded ASCII, anything else after the padding starts is invalid
+        let command_bytes: [u8; 12] = read_bytes(reader)?;
+        let len = command_bytes.iter().position(|byte| *byte == 0).unwrap_or(12);
+        if command_bytes[len..].iter().any(|byte| *byte != 0) || !valid_command(&command_bytes[..len]) {
+            return Err("invalid command");
+        }
+        let command = String::from_utf8(command_bytes[..len].to_vec()).unwrap();
+
+        let length = u32::from_le_bytes(read_bytes(reader)?) as usize;
+        if length > MAX_PAYLOAD_SIZE {
+            return Err("payload is too large");
+        }
+        let checksum: [u8; 4] = read_bytes(reader)?;
+
+        let mut payload = vec![0u8; length];
+        reader.read_exact(&mut payload).map_err(|_| "unexpected end of data")?;
+        if hash256(&payload)[..4] != checksum {
+            return Err("checksum does not match");
+        }
+
+        Ok(NetworkEnvelope { network, command, payload })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.network.magic().to_vec();
+        let mut command = [0u8; 12];
+        command[..self.command.len()].copy_from_slice(self.command.as_bytes());
+        result.extend_from_slice(&command);
+        result.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        result.extend_from_slice(&hash256(&self.payload)[..4]);
+        result.extend_from_slice(&self.payload);
+        result
+    }
+
+    pub fn message(&self) -> Result<NetworkMessage, &'static str> {
+        NetworkMessage::parse(&self.command, &self.payload)
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetAddress {
+    services: u64,
+    addr: SocketAddr,
+}
+
+impl NetAddress {
+    pub fn new(services: u64, addr: SocketAddr) -> Self {
+        NetAddress { services, addr }
+    }
+
+    // IPv4 addresses travel as IPv4-mapped IPv6 addresses and the port is big endian
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let services = u64::from_le_bytes(read_bytes(reader)?);
+        let ip = Ipv6Addr::from(read_bytes::<R, 16>(reader)?);
+        let port = u16::from_be_bytes(read_bytes(reader)?);
+        let ip = match ip.to_ipv4_mapped() {
+            Some(ipv4) => IpAddr::V4(ipv4),
+            None => IpAddr::V6(ip),
+        };
+        Ok(NetAddress { services, addr: SocketAddr::new(ip, port) })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let ip = match self.addr.ip() {
+            IpAddr::V4(ipv4) => ipv4.to_ipv6_mapped(),
+            IpAddr::V6(ipv6) => ipv6,
+        };
+        let mut result = self.services.to_le_bytes().to_vec();
+        result.extend_from_slice(&ip.octets());
+        result.extend_from_slice(&self.addr.port().to_be_bytes());
+        result
+    }
+
+    pub fn services(&self) -> u64 {
+        self.services
+    }
+
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMessage {
+    version: i32,
+    services: u64,
+    timestamp: i64,
+    receiver: NetAddress,
+    sender: NetAddress,
+    nonce: u64,
+    user_agent: String,
+    start_height: i32,
+    relay: bool,
+}
+
+impl VersionMessage {
+    pub fn new(receiver: SocketAddr, start_height: i32) -> Self {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+        let unspecified = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+        VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: 0,
+            timestamp,
+            receiver: NetAddress::new(0, receiver),
+            sender: NetAddress::new(0, unspecified),
+            nonce: rand::random(),
+            user_agent: USER_AGENT.to_string(),
+            start_height,
+            relay: false,
+        }
+    }
+
+    pub fn with_version(mut self, version: i32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn with_services(mut self, services: u64) -> Self {
+        self.services = services;
+        self.sender.services = services;
+        self
+    }
+
+    pub fn with_timestamp(mut self, timestamp: i64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_relay(mut self, relay: bool) -> Self {
+        self.relay = relay;
+        self
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let version = i32::from_le_bytes(read_bytes(reader)?);
+        let services = u64::from_le_bytes(read_bytes(reader)?);
+        let timestamp = i64::from_le_bytes(read_bytes(reader)?);
+        let receiver = NetAddress::parse(reader)?;
+        let sender = NetAddress::parse(reader)?;
+        let nonce = u64::from_le_bytes(read_bytes(reader)?);
+        let user_agent = String::from_utf8(read_var_bytes(reader)?).map_err(|_| "invalid user agent")?;
+        let start_height = i32::from_le_bytes(read_bytes(reader)?);
+        // peers that predate BIP 37 don't send the relay flag, which means they want transactions
+        let relay = match read_bytes::<R, 1>(reader) {
+            Ok(flag) => flag[0] != 0,
+            Err(_) => true,
+        };
+        Ok(VersionMessage { version, services, timestamp, receiver, sender, nonce, user_agent, start_height, relay })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend_from_slice(&self.services.to_le_bytes());
+        result.extend_from_slice(&self.timestamp.to_le_bytes());
+        result.extend(self.receiver.serialize());
+        result.extend(self.sender.serialize());
+        result.extend_from_slice(&self.nonce.to_le_bytes());
+        result.extend(encode_var_bytes(self.user_agent.as_bytes()));
+        result.extend_from_slice(&self.start_height.to_le_bytes());
+        result.push(self.relay as u8);
+        result
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn services(&self) -> u64 {
+        self.services
+    }
+
+    pub fn timestamp(&self) -> i64 {
+        self.timestamp
+    }
+
+    pub fn receiver(&self) -> &NetAddress {
+        &self.receiver
+    }
+
+    pub fn sender(&self) -> &NetAddress {
+        &self.sender
+    }
+
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn start_height(&self) -> i32 {
+        self.start_height
+    }
+
+    pub fn relay(&self) -> bool {
+        self.relay
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetHeadersMessage {
+    version: u32,
+    locator: Vec<BlockHash>,
+    hash_stop: BlockHash,
+}
+
+impl GetHeadersMessage {
+    pub fn new(locator: Vec<BlockHash>, hash_stop: BlockHash) -> Self {
+        GetHeadersMessage { version: PROTOCOL_VERSION as u32, locator, hash_stop }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let version = u32::from_le_bytes(read_bytes(reader)?);
+        let count = read_varint(reader)? as usize;
+        if count > MAX_LOCATOR_SIZE {
+            return Err("too many locator hashes");
+        }
+        let mut locator = Vec::with_capacity(count);
+        for _ in 0..count {
+            locator.push(read_hash(reader)?);
+        }
+        let hash_stop = read_hash(reader)?;
+        Ok(GetHeadersMessage { version, locator, hash_stop })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend(encode_varint(self.locator.len() as u64));
+        for hash in &self.locator {
+            result.extend(hash.iter().rev());
+        }
+        result.extend(self.hash_stop.iter().rev());
+        result
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn locator(&self) -> &[BlockHash] {
+        &self.locator
+    }
+
+    pub fn hash_stop(&self) -> &BlockHash {
+        &self.hash_stop
+    }
+}
+
+// the payload of both getcfilters and getcfheaders, asking for the range of blocks from
+// start_height up to stop_hash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetCFiltersMessage {
+    filter_type: u8,
+    start_height: u32,
+    stop_hash: BlockHash,
+}
+
+impl GetCFiltersMessage {
+    pub fn new(filter_type: u8, start_height: u32, stop_hash: BlockHash) -> Self {
+        GetCFiltersMessage { filter_type, start_height, stop_hash }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let filter_type = read_bytes::<R, 1>(reader)?[0];
+        let start_height = u32::from_le_bytes(read_bytes(reader)?);
+        let stop_hash = read_hash(reader)?;
+        Ok(GetCFiltersMessage { filter_type, start_height, stop_hash })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.filter_type];
+        result.extend_from_slice(&self.start_height.to_le_bytes());
+        result.extend(self.stop_hash.iter().rev());
+        result
+    }
+
+    pub fn filter_type(&self) -> u8 {
+        self.filter_type
+    }
+
+    pub fn start_height(&self) -> u32 {
+        self.start_height
+    }
+
+    pub fn stop_hash(&self) -> &BlockHash {
+        &self.stop_hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CFHeadersMessage {
+    filter_type: u8,
+    stop_hash: BlockHash,
+    prev_filter_header: FilterHeader,
+    filter_hashes: Vec<FilterHash>,
+}
+
+impl CFHeadersMessage {
+    pub fn new(stop_hash: BlockHash, prev_filter_header: FilterHeader, filter_hashes: Vec<FilterHash>) -> Self {
+        CFHeadersMessage { filter_type: BASIC_FILTER_TYPE, stop_hash, prev_filter_header, filter_hashes }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let filter_type = read_bytes::<R, 1>(reader)?[0];
+        let stop_hash = read_hash(reader)?;
+        let prev_filter_header = read_hash(reader)?;
+        let count = read_varint(reader)? as usize;
+        if count > MAX_GETCFHEADERS_SIZE {
+            return Err("too many filter hashes");
+        }
+        let mut filter_hashes = Vec::with_capacity(count);
+        for _ in 0..count {
+            filter_hashes.push(read_hash(reader)?);
+        }
+        Ok(CFHeadersMessage { filter_type, stop_hash, prev_filter_header, filter_hashes })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = vec![self.filter_type];
+        result.extend(self.stop_hash.iter().rev());
+        result.extend(self.prev_filter_header.iter().rev());
+        result.extend(encode_varint(self.filter_hashes.len() as u64));
+        for hash in &self.filter_hashes {
+            result.extend(hash.iter().rev());
+        }
+        result
+    }
+
+    pub fn filter_type(&self) -> u8 {
+        self.filter_type
+    }
+
+    pub fn stop_hash(&self) -> &BlockHash {
+        &self.stop_hash
+    }
+
+    pub fn prev_filter_header(&self) -> &FilterHeader {
+        &self.prev_filter_header
+    }
+
+    pub fn filter_hashes(&self) -> &[FilterHash] {
+        &self.filter_hashes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvType {
+    Error,
+    Tx,
+    Block,
+    FilteredBlock,
+    CompactBlock,
+    WitnessTx,
+    WitnessBlock,
+    Other(u32),
+}
+
+impl From<u32> for InvType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => InvType::Error,
+            1 => InvType::Tx,
+            2 => InvType::Block,
+            3 => InvType::FilteredBlock,
+            4 => InvType::CompactBlock,
+            0x40000001 => InvType::WitnessTx,
+            0x40000002 => InvType::WitnessBlock,
+            other => InvType::Other(other),
+        }
+    }
+}
+
+impl From<InvType> for u32 {
+    fn from(value: InvType) -> Self {
+        match value {
+            InvType::Error => 0,
+            InvType::Tx => 1,
+            InvType::Block => 2,
+            InvType::FilteredBlock => 3,
+            InvType::CompactBlock => 4,
+            InvType::WitnessTx => 0x40000001,
+            InvType::WitnessBlock => 0x40000002,
+            InvType::Other(other) => other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inventory {
+    inv_type: InvType,
+    hash: [u8; 32],
+}
+
+impl Inventory {
+    pub fn new(inv_type: InvType, hash: [u8; 32]) -> Self {
+        Inventory { inv_type, hash }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let inv_type = InvType::from(u32::from_le_bytes(read_bytes(reader)?));
+        let hash = read_hash(reader)?;
+        Ok(Inventory { inv_type, hash })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = u32::from(self.inv_type).to_le_bytes().to_vec();
+        result.extend(self.hash.iter().rev());
+        result
+    }
+
+    pub fn inv_type(&self) -> InvType {
+        self.inv_type
+    }
+
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkMessage {
+    Version(VersionMessage),
+    Verack,
+    WtxidRelay,
+    SendAddrV2,
+    SendHeaders,
+    Ping(u64),
+    Pong(u64),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<BlockHeader>),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    Tx(Tx),
+    Block(Block),
+    FilterLoad(BloomFilter),
+    FilterAdd(Vec<u8>),
+    FilterClear,
+    MerkleBlock(MerkleBlock),
+    GetCFilters(GetCFiltersMessage),
+    CFilter(BlockFilter),
+    GetCFHeaders(GetCFiltersMessage),
+    CFHeaders(CFHeadersMessage),
+    Unknown { command: String, payload: Vec<u8> },
+}
+
+impl NetworkMessage {
+    pub fn command(&self) -> &str {
+        match self {
+            NetworkMessage::Version(_) => "version",
+            NetworkMessage::Verack => "verack",
+            NetworkMessage::WtxidRelay => "wtxidrelay",
+            NetworkMessage::SendAddrV2 => "sendaddrv2",
+            NetworkMessage::SendHeaders => "sendheaders",
+            NetworkMessage::Ping(_) => "ping",
+            NetworkMessage::Pong(_) => "pong",
+            NetworkMessage::GetHeaders(_) => "getheaders",
+            NetworkMessage::Headers(_) => "headers",
+            NetworkMessage::Inv(_) => "inv",
+            NetworkMessage::GetData(_) => "getdata",
+            NetworkMessage::Tx(_) => "tx",
+            NetworkMessage::Block(_) => "block",
+            NetworkMessage::FilterLoad(_) => "filterload",
+            NetworkMessage::FilterAdd(_) => "filteradd",
+            NetworkMessage::FilterClear => "filterclear",
+            NetworkMessage::MerkleBlock(_) => "merkleblock",
+            NetworkMessage::GetCFilters(_) => "getcfilters",
+            NetworkMessage::CFilter(_) => "cfilter",
+            NetworkMessage::GetCFHeaders(_) => "getcfheaders",
+            NetworkMessage::CFHeaders(_) => "cfheaders",
+            NetworkMessage::Unknown { command, .. } => command,
+        }
+    }
+
+    pub fn parse(command: &str, payload: &[u8]) -> Result<Self, &'static str> {
+        let reader = &mut Cursor::new(payload);
+        let message = match command {
+            "version" => NetworkMessage::Version(VersionMessage::parse(reader)?),
+            "verack" => NetworkMessage::Verack,
+            "wtxidrelay" => NetworkMessage::WtxidRelay,
+            "sendaddrv2" => NetworkMessage::SendAddrV2,
+            "sendheaders" => NetworkMessage::SendHeaders,
+            "ping" => NetworkMessage::Ping(u64::from_le_bytes(read_bytes(reader)?)),
+            "pong" => NetworkMessage::Pong(u64::from_le_bytes(read_bytes(reader)?)),
+            "getheaders" => NetworkMessage::GetHeaders(GetHeadersMessage::parse(reader)?),
+            "headers" => {
+                let count = read_varint(reader)? as usize;
+                if count > MAX_HEADERS_RESULTS {
+                    return Err("too many headers");
+                }
+                let mut headers = Vec::with_capacity(count);
+                for _ in 0..count {
+                    headers.push(BlockHeader::parse(reader)?);
+                    // every header is followed by a transaction count, which is always zero
+                    if read_varint(reader)? != 0 {
+                        return Err("headers message with transactions");
+                    }
+                }
+                NetworkMessage::Headers(headers)
+            }
+            "inv" => NetworkMessage::Inv(parse_inventory(reader)?),
+            "getdata" => NetworkMessage::GetData(parse_inventory(reader)?),
+            "tx" => NetworkMessage::Tx(Tx::parse(reader)?),
+            "block" => NetworkMessage::Block(Block::parse(reader)?),
+            "filterload" => NetworkMessage::FilterLoad(BloomFilter::parse(reader)?),
+            "filteradd" => {
+                let item = read_var_bytes(reader)?;
+                if item.len() > MAX_FILTER_ADD_SIZE {
+                    return Err("filteradd item is too large");
+                }
+                NetworkMessage::FilterAdd(item)
+            }
+            "filterclear" => NetworkMessage::FilterClear,
+            "merkleblock" => NetworkMessage::MerkleBlock(MerkleBlock::parse(reader)?),
+            "getcfilters" => NetworkMessage::GetCFilters(GetCFiltersMessage::parse(reader)?),
+            "cfilter" => NetworkMessage::CFilter(BlockFilter::parse(reader)?),
+            "getcfheaders" => NetworkMessage::GetCFHeaders(GetCFiltersMessage::parse(reader)?),
+            "cfheaders" => NetworkMessage::CFHeaders(CFHeadersMessage::parse(reader)?),
+            _ => NetworkMessage::Unknown { command: command.to_string(), payload: payload.to_vec() },
+        };
+        Ok(message)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        match self {
+            NetworkMessage::Version(version) => version.serialize(),
+            NetworkMessage::Verack
+            | NetworkMessage::WtxidRelay
+            | NetworkMessage::SendAddrV2
+            | NetworkMessage::SendHeaders
+            | NetworkMessage::FilterClear => Vec::new(),
+            NetworkMessage::Ping(nonce) | NetworkMessage::Pong(nonce) => nonce.to_le_bytes().to_vec(),
+            NetworkMessage::GetHeaders(getheaders) => getheaders.serialize(),
+            NetworkMessage::Headers(headers) => {
+                let mut result = encode_varint(headers.len() as u64);
+                for header in headers {
+                    result.extend(header.serialize());
+                    result.push(0);
+                }
+                result
+            }
+            NetworkMessage::Inv(inventory) | NetworkMessage::GetData(inventory) => {
+                let mut result = encode_varint(inventory.len() as u64);
+                for item in inventory {
+                    result.extend(item.serialize());
+                }
+                result
+            }
+            NetworkMessage::Tx(tx) => tx.serialize(),
+            NetworkMessage::Block(block) => block.serialize(),
+            NetworkMessage::FilterLoad(filter) => filter.serialize(),
+            NetworkMessage::FilterAdd(item) => encode_var_bytes(item),
+            NetworkMessage::MerkleBlock(merkle_block) => merkle_block.serialize(),
+            NetworkMessage::GetCFilters(getcfilters) | NetworkMessage::GetCFHeaders(getcfilters) => getcfilters.serialize(),
+            NetworkMessage::CFilter(filter) => filter.serialize(),
+            NetworkMessage::CFHeaders(cfheaders) => cfheaders.serialize(),
+            NetworkMessage::Unknown { payload, .. } => payload.clone(),
+        }
+    }
+
+    pub fn to_envelope(&self, network: Network) -> Result<NetworkEnvelope, &'static str> {
+        NetworkEnvelope::new(network, self.command(), self.serialize())
+    }
+}
+
+fn valid_command(command: &[u8]) -> bool {
+    command.len() <= 12 && command.iter().all(u8::is_ascii_graphic)
+}
+
+fn read_hash<R: Read>(reader: &mut R) -> Result<[u8; 32], &'static str> {
+    let mut hash: [u8; 32] = read_bytes(reader)?;
+    hash.reverse();
+    Ok(hash)
+}
+
+fn parse_inventory<R: Read>(reader: &mut R) -> Result<Vec<Inventory>, &'static str> {
+    let count = read_varint(reader)? as usize;
+    if count > MAX_INV_SIZE {
+        return Err("too many inventory items");
+    }
+    let mut inventory = Vec::with_capacity(count);
+    for _ in 0..count {
+        inventory.push(Inventory::parse(reader)?);
+    }
+    Ok(inventory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_genesis_header() {
+        assert_eq!(
+            hex::encode(Network::Mainnet.genesis_header().hash()),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(
+            hex::encode(Network::Testnet.genesis_header().hash()),
+            "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+        );
+        assert_eq!(
+            hex::encode(Network::Regtest.genesis_header().hash()),
+            "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206"
+        );
+    }
+
+    #[test]
+    fn test_genesis_header_pow() {
+        for network in [Network::Mainnet, Network::Testnet, Network::Regtest] {
+            assert!(network.genesis_header().check_pow(network.pow_limit()));
+        }
+    }
+
+    #[test]
+    fn test_parse_envelope() {
+        let msg = hex::decode("f9beb4d976657261636b000000000000000000005df6e0e2").unwrap();
+        let envelope = NetworkEnvelope::parse(&mut Cursor::new(&msg), Network::Mainnet).unwrap();
+        assert_eq!(envelope.command(), "verack");
+        assert!(envelope.payload().is_empty());
+        assert_eq!(envelope.message().unwrap(), NetworkMessage::Verack);
+        assert_eq!(envelope.serialize(), msg);
+
+        let msg = hex::decode("f9beb4d976657273696f6e0000000000650000005f1a69d2721101000100000000000000bc8f5e5400000000010000000000000000000000000000000000ffffc61b6409208d010000000000000000000000000000000000ffffcb0071c0208d128035cbc97953f80f2f5361746f7368693a302e392e332fcf05050001").unwrap();
+        let envelope = NetworkEnvelope::parse(&mut Cursor::new(&msg), Network::Mainnet).unwrap();
+        assert_eq!(envelope.command(), "version");
+        assert_eq!(envelope.payload(), &msg[24..]);
+        assert_eq!(envelope.serialize(), msg);
+
+        let version = match envelope.message().unwrap() {
+            NetworkMessage::Version(version) => version,
+            _ => panic!("expected a version message"),
+        };
+        assert_eq!(version.version(), 70002);
+        assert_eq!(version.user_agent(), "/Satoshi:0.9.3/");
+        assert_eq!(version.start_height(), 329167);
+        assert_eq!(*version.receiver().addr(), "198.27.100.9:8333".parse().unwrap());
+        assert!(version.relay());
+    }
+
+    #[test]
+    fn test_parse_envelope_stream() {
+        let mut stream = NetworkMessage::Ping(7).to_envelope(Network::Regtest).unwrap().serialize();
+        stream.extend(NetworkMessage::Verack.to_envelope(Network::Regtest).unwrap().serialize());
+        let reader = &mut Cursor::new(stream);
+        let first = NetworkEnvelope::parse(reader, Network::Regtest).unwrap();
+        let second = NetworkEnvelope::parse(reader, Network::Regtest).unwrap();
+        assert_eq!(first.message().unwrap(), NetworkMessage::Ping(7));
+        assert_eq!(second.message().unwrap(), NetworkMessage::Verack);
+        assert!(NetworkEnvelope::parse(reader, Network::Regtest).is_err());
+    }
+
+    #[test]
+    fn test_parse_envelope_invalid() {
+        let msg = hex::decode("f9beb4d976657261636b000000000000000000005df6e0e2").unwrap();
+        assert!(NetworkEnvelope::parse(&mut Cursor::new(&msg), Network::Testnet).is_err());
+
+        let mut bad_checksum = NetworkMessage::Ping(7).to_envelope(Network::Mainnet).unwrap().serialize();
+        bad_checksum[20] ^= 1;
+        assert!(NetworkEnvelope::parse(&mut Cursor::new(&bad_checksum), Network::Mainnet).is_err());
+
+        let mut bad_command = msg.clone();
+        bad_command[11] = b'x';
+        assert!(NetworkEnvelope::parse(&mut Cursor::new(&bad_command), Network::Mainnet).is_err());
+
+        let mut too_large = msg.clone();
+        too_large[16..20].copy_from_slice(&(MAX_PAYLOAD_SIZE as u32 + 1).to_le_bytes());
+        assert!(NetworkEnvelope::parse(&mut Cursor::new(&too_large), Network::Mainnet).is_err());
+
+        let truncated = NetworkMessage::Ping(7).to_envelope(Network::Mainnet).unwrap().serialize();
+        assert!(NetworkEnvelope::parse(&mut Cursor::new(&truncated[..30]), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_new_envelope_invalid_command() {
+        assert!(NetworkEnvelope::new(Network::Mainnet, "sendheaders1", vec![]).is_ok());
+        assert_eq!(NetworkEnvelope::new(Network::Mainnet, "sendheaders12", vec![]), Err("invalid command"));
+        assert_eq!(NetworkEnvelope::new(Network::Mainnet, "ver ack", vec![]), Err("invalid command"));
+        assert_eq!(NetworkEnvelope::new(Network::Mainnet, "vérack", vec![]), Err("invalid command"));
+
+        let unknown = NetworkMessage::Unknown { command: "thirteenchars".to_string(), payload: vec![] };
+        assert_eq!(unknown.to_envelope(Network::Mainnet), Err("invalid command"));
+    }
+
+    #[test]
+    fn test_serialize_version() {
+        let receiver = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8333);
+        let mut version = VersionMessage::new(receiver, 0)
+            .with_timestamp(0)
+            .with_nonce(0)
+            .with_user_agent("/programmingblockchain:0.1/");
+        version.version = 70015;
+        version.sender = NetAddress::new(0, receiver);
+        assert_eq!(
+            hex::encode(version.serialize()),
+            "7f11010000000000000000000000000000000000000000000000000000000000000000000000ffff00000000208d000000000000000000000000000000000000ffff00000000208d00000000000000001b2f70726f6772616d6d696e67626c6f636b636861696e3a302e312f0000000000"
+        );
+        let parsed = VersionMessage::parse(&mut Cursor::new(version.serialize())).unwrap();
+        assert_eq!(parsed, version);
+    }
+
+    #[test]
+    fn test_serialize_getheaders() {
+        let mut start_block = [0u8; 32];
+        start_block.copy_from_slice(&hex::decode("0000000000000000001237f46acddf58578a37e213d2a6edc4884a2fcad05ba3").unwrap());
+        let mut getheaders = GetHeadersMessage::new(vec![start_block], [0u8; 32]);
+        getheaders.version = 70015;
+        assert_eq!(
+            hex::encode(getheaders.serialize()),
+            "7f11010001a35bd0ca2f4a88c4eda6d213e2378a5758dfcd6af437120000000000000000000000000000000000000000000000000000000000000000000000000000000000"
+        );
+        let message = NetworkMessage::GetHeaders(getheaders);
+        assert_eq!(NetworkMessage::parse("getheaders", &message.serialize()).unwrap(), message);
+    }
+
+    #[test]
+    fn test_headers_message() {
+        let genesis = Network::Mainnet.genesis_header();
+        let message = NetworkMessage::Headers(vec![genesis.clone(), Network::Testnet.genesis_header()]);
+        let payload = message.serialize();
+        assert_eq!(payload.len(), 1 + 2 * 81);
+        assert_eq!(NetworkMessage::parse("headers", &payload).unwrap(), message);
+
+        let mut with_txs = payload.clone();
+        with_txs[81] = 1;
+        assert!(NetworkMessage::parse("headers", &with_txs).is_err());
+
+        let mut too_many = encode_varint(MAX_HEADERS_RESULTS as u64 + 1);
+        too_many.extend(&payload[1..]);
+        assert!(NetworkMessage::parse("headers", &too_many).is_err());
+    }
+
+    #[test]
+    fn test_inventory_messages() {
+        let block_hash = Network::Mainnet.genesis_header().hash();
+        let inventory = vec![Inventory::new(InvType::Block, block_hash), Inventory::new(InvType::WitnessTx, [1u8; 32])];
+        for message in [NetworkMessage::Inv(inventory.clone()), NetworkMessage::GetData(inventory)] {
+            let payload = message.serialize();
+            assert_eq!(hex::encode(&payload[..5]), "0202000000");
+            assert_eq!(payload[5..37].iter().rev().copied().collect::<Vec<u8>>(), block_hash);
+            assert_eq!(NetworkMessage::parse(message.command(), &payload).unwrap(), message);
+        }
+        assert_eq!(InvType::from(0x40000003), InvType::Other(0x40000003));
+    }
+
+    #[test]
+    fn test_tx_and_block_messages() {
+        let block_hex = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c0101000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+        let payload = hex::decode(block_hex).unwrap();
+        let block = match NetworkMessage::parse("block", &payload).unwrap() {
+            NetworkMessage::Block(block) => block,
+            _ => panic!("expected a block message"),
+        };
+        assert_eq!(*block.header(), Network::Mainnet.genesis_header());
+
+        let message = NetworkMessage::Tx(block.txs()[0].clone());
+        let envelope = NetworkEnvelope::parse(&mut Cursor::new(message.to_envelope(Network::Mainnet).unwrap().serialize()), Network::Mainnet).unwrap();
+        assert_eq!(envelope.command(), "tx");
+        assert_eq!(envelope.message().unwrap(), message);
+    }
+
+    #[test]
+    fn test_feature_negotiation_messages() {
+        for message in [NetworkMessage::WtxidRelay, NetworkMessage::SendAddrV2, NetworkMessage::SendHeaders] {
+            let envelope = message.to_envelope(Network::Regtest).unwrap();
+            assert!(envelope.payload().is_empty());
+            assert_eq!(envelope.message().unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn test_bloom_filter_messages() {
+        let mut filter = BloomFilter::new(10, 5, 99).with_flags(crate::bloom_filter::BLOOM_UPDATE_ALL);
+        filter.add(b"Hello World");
+        filter.add(b"Goodbye!");
+        let message = NetworkMessage::FilterLoad(filter);
+        assert_eq!(hex::encode(message.serialize()), "0a4000600a080000010940050000006300000001");
+        assert_eq!(NetworkMessage::parse("filterload", &message.serialize()).unwrap(), message);
+
+        let message = NetworkMessage::FilterAdd(b"Hello World".to_vec());
+        assert_eq!(message.to_envelope(Network::Mainnet).unwrap().message().unwrap(), message);
+        let too_large = NetworkMessage::FilterAdd(vec![0u8; MAX_FILTER_ADD_SIZE + 1]);
+        assert!(NetworkMessage::parse("filteradd", &too_large.serialize()).is_err());
+
+        let envelope = NetworkMessage::FilterClear.to_envelope(Network::Mainnet).unwrap();
+        assert!(envelope.payload().is_empty());
+        assert_eq!(envelope.message().unwrap(), NetworkMessage::FilterClear);
+    }
+
+    #[test]
+    fn test_merkleblock_message() {
+        let genesis = Network::Mainnet.genesis_header();
+        let txid = *genesis.merkle_root();
+        let merkle_block = MerkleBlock::new(genesis, 1, vec![txid], vec![1]);
+        let message = NetworkMessage::MerkleBlock(merkle_block);
+        let parsed = NetworkMessage::parse("merkleblock", &message.serialize()).unwrap();
+        assert_eq!(parsed, message);
+        match parsed {
+            NetworkMessage::MerkleBlock(merkle_block) => assert_eq!(merkle_block.extract_matches().unwrap(), vec![txid]),
+            _ => panic!("expected a merkleblock message"),
+        }
+    }
+
+    #[test]
+    fn test_compact_filter_messages() {
+        let stop_hash = Network::Mainnet.genesis_header().hash();
+        let getcfilters = GetCFiltersMessage::new(0, 0, stop_hash);
+        assert_eq!(
+            hex::encode(getcfilters.serialize()),
+            "00000000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000"
+        );
+
+        let filter = BlockFilter::new(stop_hash, vec![0x01, 0x9d, 0xfc, 0xa8]);
+        let cfheaders = CFHeadersMessage::new(stop_hash, [0u8; 32], vec![filter.filter_hash()]);
+        for message in [
+            NetworkMessage::GetCFilters(getcfilters.clone()),
+            NetworkMessage::GetCFHeaders(getcfilters),
+            NetworkMessage::CFilter(filter),
+            NetworkMessage::CFHeaders(cfheaders),
+        ] {
+            assert_eq!(message.to_envelope(Network::Mainnet).unwrap().message().unwrap(), message);
+        }
+
+        let too_many = CFHeadersMessage::new(stop_hash, [0u8; 32], vec![[0u8; 32]; MAX_GETCFHEADERS_SIZE + 1]);
+        assert!(NetworkMessage::parse("cfheaders", &too_many.serialize()).is_err());
+    }
+
+    #[test]
+    fn test_unknown_message() {
+        let message = NetworkMessage::parse("feefilter", &[1, 2, 3]).unwrap();
+        assert_eq!(message, NetworkMessage::Unknown { command: "feefilter".to_string(), payload: vec![1, 2, 3] });
+        assert_eq!(message.command(), "feefilter");
+        assert_eq!(message.serialize(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,569 @@
+use std::fmt;
+use std::ops::Mul;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use num_bigint::BigUint;
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::elliptic_curve::Point;
+use crate::error::{CurveError, EncodingError};
+use crate::finite_field::{FieldElement, PrimeField};
+use crate::named_curve::{NamedCurve, P256Prime};
+
+// NIST P-256 (secp256r1), y² = x³ - 3x + b, on top of the generic curve code. Keys and
+// nonces are multiplied with Point::mul_ct, a fixed window over complete formulas, and the
+// nonce is inverted as k^(N-2), so the steps taken don't depend on the secret. The field
+// arithmetic is BigUint based though, whose timing does depend on the values, so this is not
+// as hardened as S256Point::mul_ct over fixed limbs. Verification uses the faster variable
+// time code, which is fine for public values.
+pub type P256Field = FieldElement<P256Prime>;
+
+lazy_static! {
+    static ref P256: NamedCurve<P256Field> = NamedCurve::by_name("secp256r1").unwrap();
+
+    pub static ref N_P256: BigUint = P256.order().clone();
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct P256Point {
+    point: Point<P256Field>,
+}
+
+impl P256Point {
+    pub fn new(x: P256Field, y: P256Field) -> Result<Self, CurveError> {
+        Ok(P256Point { point: Point::from(Arc::clone(P256.curve()), x, y)? })
+    }
+
+    pub fn infinity() -> Self {
+        P256Point { point: P256.generator() * BigUint::ZERO }
+    }
+
+    pub fn generator() -> Self {
+        P256Point { point: P256.generator().clone() }
+    }
+
+    pub fn xy(&self) -> Option<(&P256Field, &P256Field)> {
+        self.point.xy()
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.point.is_infinity()
+    }
+
+    // the same check as S256Point::verify: the x coordinate of z/s·G + r/s·P has to be r
+    pub fn verify(&self, z: &BigUint, sig: &P256Signature) -> bool {
+        let (r, s) = (sig.r(), sig.s());
+        // both are in 1..N, which P256Signature checks
+        let s_inv = s.modinv(&N_P256).unwrap();
+        let u = (z * &s_inv) % &*N_P256;
+        let v = (r * &s_inv) % &*N_P256;
+        let sum = Point::multi_mul(Arc::clone(P256.curve()), &[(P256.generator(), &u), (&self.point, &v)]);
+        match sum.as_ref().ok().and_then(|point| point.xy()) {
+            Some((x, _)) => x.to_biguint() % &*N_P256 == *r,
+            None => false,
+        }
+    }
+
+    pub fn uncompressed_sec(&self) -> Vec<u8> {
+        assert!(!self.is_infinity(), "the point at infinity has no SEC encoding");
+        self.point.to_uncompressed()
+    }
+
+    pub fn compressed_sec(&self) -> Vec<u8> {
+        assert!(!self.is_infinity(), "the point at infinity has no SEC encoding");
+        self.point.to_compressed()
+    }
+
+    pub fn parse(sec: &[u8]) -> Result<Self, EncodingError> {
+        if sec.first() == Some(&0x00) {
+            return Err(EncodingError::InvalidPrefix(0x00));
+        }
+        Ok(P256Point { point: Point::from_sec(Arc::clone(P256.curve()), sec)? })
+    }
+}
+
+impl P256Point {
+    // for secret scalars, see Point::mul_ct
+    pub fn mul_ct(&self, scalar: &BigUint) -> Self {
+        P256Point { point: self.point.mul_ct(&(scalar % &*N_P256)).expect("P-256 has prime order") }
+    }
+}
+
+impl<'a> Mul<&'a BigUint> for &P256Point {
+    type Output = P256Point;
+
+    fn mul(self, scalar: &'a BigUint) -> P256Point {
+        P256Point { point: &self.point * (scalar % &*N_P256) }
+    }
+}
+
+// An ECDSA signature over P-256, with r and s in 1..N of this curve. cripto::Signature is for
+// secp256k1 and holds its scalars, which are reduced by the other order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct P256Signature {
+    r: BigUint,
+    s: BigUint,
+}
+
+impl P256Signature {
+    pub fn new(r: BigUint, s: BigUint) -> Self {
+        match P256Signature::try_new(r, s) {
+            Ok(sig) => sig,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_new(r: BigUint, s: BigUint) -> Result<Self, EncodingError> {
+        for num in [&r, &s] {
+            if *num == BigUint::ZERO || num >= &N_P256 {
+                return Err(EncodingError::ScalarOutOfRange);
+            }
+        }
+        Ok(P256Signature { r, s })
+    }
+
+    pub fn r(&self) -> &BigUint {
+        &self.r
+    }
+
+    pub fn s(&self) -> &BigUint {
+        &self.s
+    }
+}
+
+// The secret is kept as bytes that are wiped on drop, like scalar::SecretScalar does for
+// secp256k1 keys, and Debug leaves it out
+pub struct P256PrivateKey {
+    secret: Zeroizing<[u8; 32]>,
+    point: P256Point,
+}
+
+impl P256PrivateKey {
+    // 32 bytes big endian, in 1..N
+    pub fn from_bytes(secret: &[u8; 32]) -> Result<Self, EncodingError> {
+        if !in_range(secret) {
+            return Err(EncodingError::ScalarOutOfRange);
+        }
+        let secret = Zeroizing::new(*secret);
+        let point = P256Point::generator().mul_ct(&BigUint::from_bytes_be(&*secret));
+        Ok(P256PrivateKey { secret, point })
+    }
+
+    pub fn random() -> Self {
+        let secret = random_secret();
+        P256PrivateKey::from_bytes(&secret).expect("secret in range")
+    }
+
+    pub fn point(&self) -> &P256Point {
+        &self.point
+    }
+
+    // ECDSA with a random nonce. Unlike on secp256k1 a high s is left as it is, P-256 has no
+    // low s rule.
+    pub fn sign(&self, z: &BigUint) -> P256Signature {
+        self.sign_with_nonce(z, &random_secret())
+    }
+
+    // the signing itself, with the nonce given so that tests can check known signatures
+    fn sign_with_nonce(&self, z: &BigUint, nonce: &[u8; 32]) -> P256Signature {
+        let k = BigUint::from_bytes_be(nonce);
+        let r = match P256Point::generator().mul_ct(&k).xy() {
+            Some((x, _)) => x.to_biguint() % &*N_P256,
+            None => panic!("infinity"),
+        };
+        // Fermat's little theorem, as N is prime; the exponent is public
+        let k_inv = k.modpow(&(&*N_P256 - 2u32), &N_P256);
+        let s = ((z + &r * BigUint::from_bytes_be(&*self.secret)) * k_inv) % &*N_P256;
+        P256Signature::new(r, s)
+    }
+}
+
+impl fmt::Debug for P256PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("P256PrivateKey").field("secret", &"<redacted>").field("point", &self.point).finish()
+    }
+}
+
+fn in_range(secret: &[u8; 32]) -> bool {
+    *secret != [0u8; 32] && secret.as_slice() < N_P256.to_bytes_be().as_slice()
+}
+
+// uniform in 1..N, for keys and nonces
+fn random_secret() -> Zeroizing<[u8; 32]> {
+    let mut rng = rand::thread_rng();
+    let mut secret = Zeroizing::new([0u8; 32]);
+    loop {
+        rng.fill_bytes(&mut *secret);
+        if in_range(&secret) {
+            return secret;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+
+    fn num(hex: &str) -> BigUint {
+        BigUint::parse_bytes(hex.as_bytes(), 16).unwrap()
+    }
+
+    fn key(hex: &str) -> P256PrivateKey {
+        P256PrivateKey::from_bytes(&hex::decode(hex).unwrap().try_into().unwrap()).unwrap()
+    }
+
+    // RFC 6979, A.2.5: the P-256 key and its deterministic signatures with SHA-256
+    const SECRET: &str = "c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721";
+    const UX: &str = "60fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6";
+    const UY: &str = "7903fe1008b8bc99a41ae9e95628bc64f2f1b20c2d7e9f5177a3c294d4462299";
+
+    #[test]
+    fn test_public_key() {
+        let pk = key(SECRET);
+        let (x, y) = pk.point().xy().unwrap();
+        assert_eq!((x.num(), y.num()), (&num(UX), &num(UY)));
+        assert!(!format!("{:?}", pk).contains(SECRET));
+
+        let n: [u8; 32] = N_P256.to_bytes_be().try_into().unwrap();
+        assert_eq!(P256PrivateKey::from_bytes(&n).unwrap_err(), EncodingError::ScalarOutOfRange);
+        assert_eq!(P256PrivateKey::from_bytes(&[0u8; 32]).unwrap_err(), EncodingError::ScalarOutOfRange);
+    }
+
+    #[test]
+    fn test_verify_rfc6979_vectors() {
+        let point = P256Point::new(P256Field::new(num(UX)), P256Field::new(num(UY))).unwrap();
+        let vectors = [
+            (
+                "sample",
+                "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+                "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+            ),
+            (
+                "test",
+                "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367",
+                "019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+            ),
+        ];
+        for (msg, r, s) in vectors {
+            let z = BigUint::from_bytes_be(&Sha256::digest(msg.as_bytes()));
+            let sig = P256Signature::new(num(r), num(s));
+            assert!(point.verify(&z, &sig));
+            assert!(!point.verify(&(z + 1u32), &sig));
+            assert!(!P256Point::generator().verify(&BigUint::from_bytes_be(&Sha256::digest(msg.as_bytes())), &sig));
+        }
+    }
+
+    #[test]
+    fn test_sign() {
+        let pk = P256PrivateKey::random();
+        let z = BigUint::from_bytes_be(&Sha256::digest(b"p256"));
+        let sig = pk.sign(&z);
+        assert!(pk.point().verify(&z, &sig));
+        assert!(!pk.point().verify(&z, &P256Signature::new(sig.r() % (&*N_P256 - 1u32) + 1u32, sig.s().clone())));
+    }
+
+    #[test]
+    fn test_signature_range() {
+        let one = BigUint::from(1u32);
+        assert!(P256Signature::try_new(one.clone(), &*N_P256 - 1u32).is_ok());
+        assert_eq!(P256Signature::try_new(BigUint::ZERO, one.clone()), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(P256Signature::try_new(one.clone(), N_P256.clone()), Err(EncodingError::ScalarOutOfRange));
+        // below the secp256k1 order but not below this one
+        assert_eq!(P256Signature::try_new(&*N_P256 + 1u32, one), Err(EncodingError::ScalarOutOfRange));
+    }
+
+    // NIST CAVP, 186-3 ECDSA SigVer.rsp, [P-256,SHA-256]: every case, the passing ones and
+    // the ones with the message, R, S or Q changed
+    #[test]
+    fn test_cavp_sigver() {
+        let vectors = [
+            // F (3 - S changed)
+            (
+                "e4796db5f785f207aa30d311693b3702821dff1168fd2e04c0836825aefd850d9aa60326d88cde1a23c7745351392ca2288d632c264f197d05cd424a30336c19fd09bb229654f0222fcb881a4b35c290a093ac159ce13409111ff0358411133c24f5b8e2090d6db6558afc36f06ca1f6ef779785adba68db27a409859fc4c4a0",
+                "87f8f2b218f49845f6f10eec3877136269f5c1a54736dbdf69f89940cad41555",
+                "e15f369036f49842fac7a86c8a2b0557609776814448b8f5e84aa9f4395205e9",
+                "d19ff48b324915576416097d2544f7cbdf8768b1454ad20e0baac50e211f23b0",
+                "a3e81e59311cdfff2d4784949f7a2cb50ba6c3a91fa54710568e61aca3e847c6",
+                false,
+            ),
+            // F (2 - R changed)
+            (
+                "069a6e6b93dfee6df6ef6997cd80dd2182c36653cef10c655d524585655462d683877f95ecc6d6c81623d8fac4e900ed0019964094e7de91f1481989ae1873004565789cbf5dc56c62aedc63f62f3b894c9c6f7788c8ecaadc9bd0e81ad91b2b3569ea12260e93924fdddd3972af5273198f5efda0746219475017557616170e",
+                "5cf02a00d205bdfee2016f7421807fc38ae69e6b7ccd064ee689fc1a94a9f7d2",
+                "ec530ce3cc5c9d1af463f264d685afe2b4db4b5828d7e61b748930f3ce622a85",
+                "dc23d130c6117fb5751201455e99f36f59aba1a6a21cf2d0e7481a97451d6693",
+                "d6ce7708c18dbf35d4f8aa7240922dc6823f2e7058cbc1484fcad1599db5018c",
+                false,
+            ),
+            // F (4 - Q changed)
+            (
+                "df04a346cf4d0e331a6db78cca2d456d31b0a000aa51441defdb97bbeb20b94d8d746429a393ba88840d661615e07def615a342abedfa4ce912e562af714959896858af817317a840dcff85a057bb91a3c2bf90105500362754a6dd321cdd86128cfc5f04667b57aa78c112411e42da304f1012d48cd6a7052d7de44ebcc01de",
+                "2ddfd145767883ffbb0ac003ab4a44346d08fa2570b3120dcce94562422244cb",
+                "5f70c7d11ac2b7a435ccfbbae02c3df1ea6b532cc0e9db74f93fffca7c6f9a64",
+                "9913111cff6f20c5bf453a99cd2c2019a4e749a49724a08774d14e4c113edda8",
+                "9467cd4cd21ecb56b0cab0a9a453b43386845459127a952421f5c6382866c5cc",
+                false,
+            ),
+            // P (0 )
+            (
+                "e1130af6a38ccb412a9c8d13e15dbfc9e69a16385af3c3f1e5da954fd5e7c45fd75e2b8c36699228e92840c0562fbf3772f07e17f1add56588dd45f7450e1217ad239922dd9c32695dc71ff2424ca0dec1321aa47064a044b7fe3c2b97d03ce470a592304c5ef21eed9f93da56bb232d1eeb0035f9bf0dfafdcc4606272b20a3",
+                "e424dc61d4bb3cb7ef4344a7f8957a0c5134e16f7a67c074f82e6e12f49abf3c",
+                "970eed7aa2bc48651545949de1dddaf0127e5965ac85d1243d6f60e7dfaee927",
+                "bf96b99aa49c705c910be33142017c642ff540c76349b9dab72f981fd9347f4f",
+                "17c55095819089c2e03b9cd415abdf12444e323075d98f31920b9e0f57ec871c",
+                true,
+            ),
+            // P (0 )
+            (
+                "73c5f6a67456ae48209b5f85d1e7de7758bf235300c6ae2bdceb1dcb27a7730fb68c950b7fcada0ecc4661d3578230f225a875e69aaa17f1e71c6be5c831f22663bac63d0c7a9635edb0043ff8c6f26470f02a7bc56556f1437f06dfa27b487a6c4290d8bad38d4879b334e341ba092dde4e4ae694a9c09302e2dbf443581c08",
+                "e0fc6a6f50e1c57475673ee54e3a57f9a49f3328e743bf52f335e3eeaa3d2864",
+                "7f59d689c91e463607d9194d99faf316e25432870816dde63f5d4b373f12f22a",
+                "1d75830cd36f4c9aa181b2c4221e87f176b7f05b7c87824e82e396c88315c407",
+                "cb2acb01dac96efc53a32d4a0d85d0c2e48955214783ecf50a4f0414a319c05a",
+                true,
+            ),
+            // F (2 - R changed)
+            (
+                "666036d9b4a2426ed6585a4e0fd931a8761451d29ab04bd7dc6d0c5b9e38e6c2b263ff6cb837bd04399de3d757c6c7005f6d7a987063cf6d7e8cb38a4bf0d74a282572bd01d0f41e3fd066e3021575f0fa04f27b700d5b7ddddf50965993c3f9c7118ed78888da7cb221849b3260592b8e632d7c51e935a0ceae15207bedd548",
+                "a849bef575cac3c6920fbce675c3b787136209f855de19ffe2e8d29b31a5ad86",
+                "bf5fe4f7858f9b805bd8dcc05ad5e7fb889de2f822f3d8b41694e6c55c16b471",
+                "25acc3aa9d9e84c7abf08f73fa4195acc506491d6fc37cb9074528a7db87b9d6",
+                "9b21d5b5259ed3f2ef07dfec6cc90d3a37855d1ce122a85ba6a333f307d31537",
+                false,
+            ),
+            // F (4 - Q changed)
+            (
+                "7e80436bce57339ce8da1b5660149a20240b146d108deef3ec5da4ae256f8f894edcbbc57b34ce37089c0daa17f0c46cd82b5a1599314fd79d2fd2f446bd5a25b8e32fcf05b76d644573a6df4ad1dfea707b479d97237a346f1ec632ea5660efb57e8717a8628d7f82af50a4e84b11f21bdff6839196a880ae20b2a0918d58cd",
+                "3dfb6f40f2471b29b77fdccba72d37c21bba019efa40c1c8f91ec405d7dcc5df",
+                "f22f953f1e395a52ead7f3ae3fc47451b438117b1e04d613bc8555b7d6e6d1bb",
+                "548886278e5ec26bed811dbb72db1e154b6f17be70deb1b210107decb1ec2a5a",
+                "e93bfebd2f14f3d827ca32b464be6e69187f5edbd52def4f96599c37d58eee75",
+                false,
+            ),
+            // F (1 - Message changed)
+            (
+                "1669bfb657fdc62c3ddd63269787fc1c969f1850fb04c933dda063ef74a56ce13e3a649700820f0061efabf849a85d474326c8a541d99830eea8131eaea584f22d88c353965dabcdc4bf6b55949fd529507dfb803ab6b480cd73ca0ba00ca19c438849e2cea262a1c57d8f81cd257fb58e19dec7904da97d8386e87b84948169",
+                "69b7667056e1e11d6caf6e45643f8b21e7a4bebda463c7fdbc13bc98efbd0214",
+                "d3f9b12eb46c7c6fda0da3fc85bc1fd831557f9abc902a3be3cb3e8be7d1aa2f",
+                "288f7a1cd391842cce21f00e6f15471c04dc182fe4b14d92dc18910879799790",
+                "247b3c4e89a3bcadfea73c7bfd361def43715fa382b8c3edf4ae15d6e55e9979",
+                false,
+            ),
+            // F (3 - S changed)
+            (
+                "3fe60dd9ad6caccf5a6f583b3ae65953563446c4510b70da115ffaa0ba04c076115c7043ab8733403cd69c7d14c212c655c07b43a7c71b9a4cffe22c2684788ec6870dc2013f269172c822256f9e7cc674791bf2d8486c0f5684283e1649576efc982ede17c7b74b214754d70402fb4bb45ad086cf2cf76b3d63f7fce39ac970",
+                "bf02cbcf6d8cc26e91766d8af0b164fc5968535e84c158eb3bc4e2d79c3cc682",
+                "069ba6cb06b49d60812066afa16ecf7b51352f2c03bd93ec220822b1f3dfba03",
+                "f5acb06c59c2b4927fb852faa07faf4b1852bbb5d06840935e849c4d293d1bad",
+                "049dab79c89cc02f1484c437f523e080a75f134917fda752f2d5ca397addfe5d",
+                false,
+            ),
+            // F (2 - R changed)
+            (
+                "983a71b9994d95e876d84d28946a041f8f0a3f544cfcc055496580f1dfd4e312a2ad418fe69dbc61db230cc0c0ed97e360abab7d6ff4b81ee970a7e97466acfd9644f828ffec538abc383d0e92326d1c88c55e1f46a668a039beaa1be631a89129938c00a81a3ae46d4aecbf9707f764dbaccea3ef7665e4c4307fa0b0a3075c",
+                "224a4d65b958f6d6afb2904863efd2a734b31798884801fcab5a590f4d6da9de",
+                "178d51fddada62806f097aa615d33b8f2404e6b1479f5fd4859d595734d6d2b9",
+                "87b93ee2fecfda54deb8dff8e426f3c72c8864991f8ec2b3205bb3b416de93d2",
+                "4044a24df85be0cc76f21a4430b75b8e77b932a87f51e4eccbc45c263ebf8f66",
+                false,
+            ),
+            // F (3 - S changed)
+            (
+                "4a8c071ac4fd0d52faa407b0fe5dab759f7394a5832127f2a3498f34aac287339e043b4ffa79528faf199dc917f7b066ad65505dab0e11e6948515052ce20cfdb892ffb8aa9bf3f1aa5be30a5bbe85823bddf70b39fd7ebd4a93a2f75472c1d4f606247a9821f1a8c45a6cb80545de2e0c6c0174e2392088c754e9c8443eb5af",
+                "43691c7795a57ead8c5c68536fe934538d46f12889680a9cb6d055a066228369",
+                "f8790110b3c3b281aa1eae037d4f1234aff587d903d93ba3af225c27ddc9ccac",
+                "8acd62e8c262fa50dd9840480969f4ef70f218ebf8ef9584f199031132c6b1ce",
+                "cfca7ed3d4347fb2a29e526b43c348ae1ce6c60d44f3191b6d8ea3a2d9c92154",
+                false,
+            ),
+            // F (1 - Message changed)
+            (
+                "0a3a12c3084c865daf1d302c78215d39bfe0b8bf28272b3c0b74beb4b7409db0718239de700785581514321c6440a4bbaea4c76fa47401e151e68cb6c29017f0bce4631290af5ea5e2bf3ed742ae110b04ade83a5dbd7358f29a85938e23d87ac8233072b79c94670ff0959f9c7f4517862ff829452096c78f5f2e9a7e4e9216",
+                "9157dbfcf8cf385f5bb1568ad5c6e2a8652ba6dfc63bc1753edf5268cb7eb596",
+                "972570f4313d47fc96f7c02d5594d77d46f91e949808825b3d31f029e8296405",
+                "dfaea6f297fa320b707866125c2a7d5d515b51a503bee817de9faa343cc48eeb",
+                "8f780ad713f9c3e5a4f7fa4c519833dfefc6a7432389b1e4af463961f09764f2",
+                false,
+            ),
+            // F (4 - Q changed)
+            (
+                "785d07a3c54f63dca11f5d1a5f496ee2c2f9288e55007e666c78b007d95cc28581dce51f490b30fa73dc9e2d45d075d7e3a95fb8a9e1465ad191904124160b7c60fa720ef4ef1c5d2998f40570ae2a870ef3e894c2bc617d8a1dc85c3c55774928c38789b4e661349d3f84d2441a3b856a76949b9f1f80bc161648a1cad5588e",
+                "072b10c081a4c1713a294f248aef850e297991aca47fa96a7470abe3b8acfdda",
+                "9581145cca04a0fb94cedce752c8f0370861916d2a94e7c647c5373ce6a4c8f5",
+                "09f5483eccec80f9d104815a1be9cc1a8e5b12b6eb482a65c6907b7480cf4f19",
+                "a4f90e560c5e4eb8696cb276e5165b6a9d486345dedfb094a76e8442d026378d",
+                false,
+            ),
+            // F (1 - Message changed)
+            (
+                "76f987ec5448dd72219bd30bf6b66b0775c80b394851a43ff1f537f140a6e7229ef8cd72ad58b1d2d20298539d6347dd5598812bc65323aceaf05228f738b5ad3e8d9fe4100fd767c2f098c77cb99c2992843ba3eed91d32444f3b6db6cd212dd4e5609548f4bb62812a920f6e2bf1581be1ebeebdd06ec4e971862cc42055ca",
+                "09308ea5bfad6e5adf408634b3d5ce9240d35442f7fe116452aaec0d25be8c24",
+                "f40c93e023ef494b1c3079b2d10ef67f3170740495ce2cc57f8ee4b0618b8ee5",
+                "5cc8aa7c35743ec0c23dde88dabd5e4fcd0192d2116f6926fef788cddb754e73",
+                "9c9c045ebaa1b828c32f82ace0d18daebf5e156eb7cbfdc1eff4399a8a900ae7",
+                false,
+            ),
+            // P (0 )
+            (
+                "60cd64b2cd2be6c33859b94875120361a24085f3765cb8b2bf11e026fa9d8855dbe435acf7882e84f3c7857f96e2baab4d9afe4588e4a82e17a78827bfdb5ddbd1c211fbc2e6d884cddd7cb9d90d5bf4a7311b83f352508033812c776a0e00c003c7e0d628e50736c7512df0acfa9f2320bd102229f46495ae6d0857cc452a84",
+                "2d98ea01f754d34bbc3003df5050200abf445ec728556d7ed7d5c54c55552b6d",
+                "9b52672742d637a32add056dfd6d8792f2a33c2e69dafabea09b960bc61e230a",
+                "06108e525f845d0155bf60193222b3219c98e3d49424c2fb2a0987f825c17959",
+                "62b5cdd591e5b507e560167ba8f6f7cda74673eb315680cb89ccbc4eec477dce",
+                true,
+            ),
+        ];
+        for (msg, qx, qy, r, s, result) in vectors {
+            let point = P256Point::new(P256Field::new(num(qx)), P256Field::new(num(qy))).unwrap();
+            let z = BigUint::from_bytes_be(&Sha256::digest(hex::decode(msg).unwrap()));
+            assert_eq!(point.verify(&z, &P256Signature::new(num(r), num(s))), result, "R = {}", r);
+        }
+    }
+
+    // NIST CAVP, 186-3 ECDSA KeyPair.rsp, [P-256]
+    #[test]
+    fn test_cavp_key_pair() {
+        let vectors = [
+            (
+                "c9806898a0334916c860748880a541f093b579a9b1f32934d86c363c39800357",
+                "d0720dc691aa80096ba32fed1cb97c2b620690d06de0317b8618d5ce65eb728f",
+                "9681b517b1cda17d0d83d335d9c4a8a9a9b0b1b3c7106d8f3c72bc5093dc275f",
+            ),
+            (
+                "710735c8388f48c684a97bd66751cc5f5a122d6b9a96a2dbe73662f78217446d",
+                "f6836a8add91cb182d8d258dda6680690eb724a66dc3bb60d2322565c39e4ab9",
+                "1f837aa32864870cb8e8d0ac2ff31f824e7beddc4bb7ad72c173ad974b289dc2",
+            ),
+            (
+                "78d5d8b7b3e2c16b3e37e7e63becd8ceff61e2ce618757f514620ada8a11f6e4",
+                "76711126cbb2af4f6a5fe5665dad4c88d27b6cb018879e03e54f779f203a854e",
+                "a26df39960ab5248fd3620fd018398e788bd89a3cea509b352452b69811e6856",
+            ),
+            (
+                "2a61a0703860585fe17420c244e1de5a6ac8c25146b208ef88ad51ae34c8cb8c",
+                "e1aa7196ceeac088aaddeeba037abb18f67e1b55c0a5c4e71ec70ad666fcddc8",
+                "d7d35bdce6dedc5de98a7ecb27a9cd066a08f586a733b59f5a2cdb54f971d5c8",
+            ),
+            (
+                "01b965b45ff386f28c121c077f1d7b2710acc6b0cb58d8662d549391dcf5a883",
+                "1f038c5422e88eec9e88b815e8f6b3e50852333fc423134348fc7d79ef8e8a10",
+                "43a047cb20e94b4ffb361ef68952b004c0700b2962e0c0635a70269bc789b849",
+            ),
+        ];
+        for (d, qx, qy) in vectors {
+            let (x, y) = key(d).point().xy().map(|(x, y)| (x.to_biguint(), y.to_biguint())).unwrap();
+            assert_eq!((x, y), (num(qx), num(qy)));
+        }
+    }
+
+    // NIST CAVP, 186-3 ECDSA PKV.rsp, [P-256]: public key validation, where a coordinate of p
+    // or more is refused even when it is on the curve modulo p
+    #[test]
+    fn test_cavp_public_key_validation() {
+        let vectors = [
+            // P (0 )
+            (
+                "e0f7449c5588f24492c338f2bc8f7865f755b958d48edb0f2d0056e50c3fd5b7",
+                "86d7e9255d0f4b6f44fa2cd6f8ba3c0aa828321d6d8cc430ca6284ce1d5b43a0",
+                true,
+            ),
+            // F (1 - Q_x or Q_y out of range)
+            (
+                "d17c446237d9df87266ba3a91ff27f45abfdcb77bfd83536e92903efb861a9a9",
+                "1eabb6a349ce2cd447d777b6739c5fc066add2002d2029052c408d0701066231c",
+                false,
+            ),
+            // F (1 - Q_x or Q_y out of range)
+            (
+                "17875397ae87369365656d490e8ce956911bd97607f2aff41b56f6f3a61989826",
+                "980a3c4f61b9692633fbba5ef04c9cb546dd05cdec9fa8428b8849670e2fba92",
+                false,
+            ),
+            // F (2 - Point not on curve)
+            (
+                "f2d1c0dc0852c3d8a2a2500a23a44813ccce1ac4e58444175b440469ffc12273",
+                "32bfe992831b305d8c37b9672df5d29fcb5c29b4a40534683e3ace23d24647dd",
+                false,
+            ),
+        ];
+        for (qx, qy, result) in vectors {
+            let point = match (P256Field::try_new(num(qx)), P256Field::try_new(num(qy))) {
+                (Ok(x), Ok(y)) => P256Point::new(x, y).ok(),
+                _ => None,
+            };
+            assert_eq!(point.is_some(), result, "Qx = {}", qx);
+        }
+    }
+
+    // signing with the nonce fixed has to give exactly the published signatures: the CAVP
+    // SigGen case, which lists its k, and the RFC 6979 ones with the k the RFC derives
+    #[test]
+    fn test_sign_known_answers() {
+        let pk = key("519b423d715f8b581f4fa8ee59f4771a5b44c8130b4e3eacca54a56dda72b464");
+        let msg = hex::decode(
+            "5905238877c77421f73e43ee3da6f2d9e2ccad5fc942dcec0cbd25482935faaf416983fe165b1a045ee2bcd2e6dca3bdf46c4310a7461f9a37960ca672d3feb5473e253605fb1ddfd28065b53cb5858a8ad28175bf9bd386a5e471ea7a65c17cc934a9d791e91491eb3754d03799790fe2d308d16146d5c9b0d0debd97d79ce8",
+        )
+        .unwrap();
+        let z = BigUint::from_bytes_be(&Sha256::digest(&msg));
+        let k = hex::decode("94a1bbb14b906a61a280f245f9e93c7f3b4a6247824f5d33b9670787642a68de").unwrap();
+        let sig = pk.sign_with_nonce(&z, &k.try_into().unwrap());
+        assert_eq!(sig.r(), &num("f3ac8061b514795b8843e3d6629527ed2afd6b1f6a555a7acabb5e6f79c8c2ac"));
+        assert_eq!(sig.s(), &num("8bf77819ca05a6b2786c76262bf7371cef97b218e96f175a3ccdda2acc058903"));
+
+        let pk = key(SECRET);
+        let vectors = [
+            (
+                "sample",
+                "a6e3c57dd01abe90086538398355dd4c3b17aa873382b0f24d6129493d8aad60",
+                "efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716",
+                "f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8",
+            ),
+            (
+                "test",
+                "d16b6ae827f17175e040871a1c7ec3500192c4c92677336ec2537acaee0008e0",
+                "f1abb023518351cd71d881567b1ea663ed3efcf6c5132b354f28d3b0b7d38367",
+                "019f4113742a2b14bd25926b49c649155f267e60d3814b4c0cc84250e46f0083",
+            ),
+        ];
+        for (msg, k, r, s) in vectors {
+            let z = BigUint::from_bytes_be(&Sha256::digest(msg.as_bytes()));
+            let sig = pk.sign_with_nonce(&z, &hex::decode(k).unwrap().try_into().unwrap());
+            assert_eq!((sig.r(), sig.s()), (&num(r), &num(s)));
+        }
+    }
+
+    #[test]
+    fn test_mul_ct_matches_mul() {
+        let mut rng = rand::thread_rng();
+        let g = P256Point::generator();
+        let mut scalars: Vec<BigUint> = (0..5).map(|_| BigUint::from_bytes_be(&*random_secret())).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), &*N_P256 - 1u32, N_P256.clone()]);
+        for scalar in scalars {
+            assert_eq!(g.mul_ct(&scalar), &g * &scalar);
+            let point = &g * &BigUint::from(rng.next_u64());
+            assert_eq!(point.mul_ct(&scalar), &point * &scalar);
+        }
+    }
+
+    #[test]
+    fn test_sec() {
+        let point = key(SECRET).point().clone();
+        assert_eq!(hex::encode(point.uncompressed_sec()), format!("04{}{}", UX, UY));
+        // Uy is odd
+        assert_eq!(hex::encode(point.compressed_sec()), format!("03{}", UX));
+        assert_eq!(P256Point::parse(&point.uncompressed_sec()).unwrap(), point);
+        assert_eq!(P256Point::parse(&point.compressed_sec()).unwrap(), point);
+
+        assert_eq!(P256Point::parse(&[0x00]), Err(EncodingError::InvalidPrefix(0x00)));
+        assert_eq!(P256Point::parse(&[0x02; 32]), Err(EncodingError::InvalidLength { expected: 33, found: 32 }));
+        let mut off_curve = point.uncompressed_sec();
+        off_curve[64] ^= 1;
+        assert_eq!(P256Point::parse(&off_curve), Err(EncodingError::Curve(CurveError::NotOnCurve)));
+        assert!((&P256Point::generator() * &N_P256).is_infinity());
+        assert_eq!(P256Point::infinity(), &P256Point::generator() * &BigUint::ZERO);
+    }
+}
@@ -0,0 +1,408 @@
+use std::io::Cursor;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use crate::network::{Network, NetworkEnvelope, NetworkMessage, VersionMessage, MAX_PAYLOAD_SIZE};
+
+pub const MIN_PEER_PROTOCOL_VERSION: i32 = 31800;
+pub const SEND_HEADERS_VERSION: i32 = 70012;
+pub const WTXID_RELAY_VERSION: i32 = 70016;
+pub const SEND_ADDRV2_VERSION: i32 = 70016;
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeState {
+    AwaitingVersion,
+    AwaitingVerack,
+    Established,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Features {
+    wtxid_relay: bool,
+    addrv2: bool,
+    send_headers: bool,
+}
+
+impl Features {
+    // both sides announced BIP 339, so transactions are announced and requested by wtxid
+    pub fn wtxid_relay(&self) -> bool {
+        self.wtxid_relay
+    }
+
+    // the peer wants addresses relayed with BIP 155 addrv2 messages
+    pub fn addrv2(&self) -> bool {
+        self.addrv2
+    }
+
+    // the peer wants new blocks announced with headers instead of inv (BIP 130)
+    pub fn send_headers(&self) -> bool {
+        self.send_headers
+    }
+}
+
+// The version/verack exchange without any IO: `handle` takes each message received from the
+// peer and returns the messages that have to be sent back.
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    state: HandshakeState,
+    local_version: VersionMessage,
+    remote_version: Option<VersionMessage>,
+    features: Features,
+}
+
+impl Handshake {
+    pub fn new(local_version: VersionMessage) -> Self {
+        Handshake {
+            state: HandshakeState::AwaitingVersion,
+            local_version,
+            remote_version: None,
+            features: Features::default(),
+        }
+    }
+
+    pub fn start(&self) -> Vec<NetworkMessage> {
+        vec![NetworkMessage::Version(self.local_version.clone())]
+    }
+
+    pub fn handle(&mut self, message: &NetworkMessage) -> Result<Vec<NetworkMessage>, &'static str> {
+        match message {
+            NetworkMessage::Ping(nonce) => Ok(vec![NetworkMessage::Pong(*nonce)]),
+            NetworkMessage::Version(version) => {
+                if self.state != HandshakeState::AwaitingVersion {
+                    return Err("duplicate version message");
+                }
+                if version.version() < MIN_PEER_PROTOCOL_VERSION {
+                    return Err("peer protocol version is too old");
+                }
+                if version.nonce() == self.local_version.nonce() {
+                    return Err("connected to self");
+                }
+
+                // wtxidrelay and sendaddrv2 are only valid between version and verack
+                let mut replies = Vec::new();
+                if version.version() >= WTXID_RELAY_VERSION {
+                    replies.push(NetworkMessage::WtxidRelay);
+                }
+                // BIP 155 allows sendaddrv2 at any version, but older implementations may drop
+                // the connection on a message they don't know, so like Bitcoin Core only peers
+                // at 70016 or later get it
+                if version.version() >= SEND_ADDRV2_VERSION {
+                    replies.push(NetworkMessage::SendAddrV2);
+                }
+                replies.push(NetworkMessage::Verack);
+
+                self.remote_version = Some(version.clone());
+                self.state = HandshakeState::AwaitingVerack;
+                Ok(replies)
+            }
+            NetworkMessage::WtxidRelay => {
+                if self.state != HandshakeState::AwaitingVerack {
+                    return Err("wtxidrelay received outside of the handshake");
+                }
+                self.features.wtxid_relay = self.remote_protocol_version() >= WTXID_RELAY_VERSION;
+                Ok(Vec::new())
+            }
+            NetworkMessage::SendAddrV2 => {
+                if self.state != HandshakeState::AwaitingVerack {
+                    return Err("sendaddrv2 received outside of the handshake");
+                }
+                self.features.addrv2 = true;
+                Ok(Vec::new())
+            }
+            NetworkMessage::Verack => match self.state {
+                HandshakeState::AwaitingVersion => Err("verack received before version"),
+                HandshakeState::AwaitingVerack => {
+                    self.state = HandshakeState::Established;
+                    if self.remote_protocol_version() >= SEND_HEADERS_VERSION {
+                        return Ok(vec![NetworkMessage::SendHeaders]);
+                    }
+                    Ok(Vec::new())
+                }
+                HandshakeState::Established => Ok(Vec::new()),
+            },
+            NetworkMessage::SendHeaders => {
+                if self.state == HandshakeState::Established {
+                    self.features.send_headers = true;
+                }
+                Ok(Vec::new())
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn state(&self) -> HandshakeState {
+        self.state
+    }
+
+    pub fn features(&self) -> &Features {
+        &self.features
+    }
+
+    pub fn remote_version(&self) -> Option<&VersionMessage> {
+        self.remote_version.as_ref()
+    }
+
+    fn remote_protocol_version(&self) -> i32 {
+        self.remote_version.as_ref().map(|version| version.version()).unwrap_or(0)
+    }
+}
+
+#[derive(Debug)]
+pub struct Peer {
+    network: Network,
+    addr: SocketAddr,
+    stream: TcpStream,
+    handshake: Handshake,
+}
+
+impl Peer {
+    pub async fn connect(addr: SocketAddr, network: Network, start_height: i32) -> Result<Self, &'static str> {
+        let stream = TcpStream::connect(addr).await.map_err(|_| "can't connect to peer")?;
+        Peer::handshake(stream, network, VersionMessage::new(addr, start_height)).await
+    }
+
+    pub async fn handshake(stream: TcpStream, network: Network, version: VersionMessage) -> Result<Self, &'static str> {
+        let addr = stream.peer_addr().map_err(|_| "peer is not connected")?;
+        let mut peer = Peer { network, addr, stream, handshake: Handshake::new(version) };
+        timeout(HANDSHAKE_TIMEOUT, peer.complete_handshake()).await.map_err(|_| "handshake timed out")??;
+        Ok(peer)
+    }
+
+    async fn complete_handshake(&mut self) -> Result<(), &'static str> {
+        for message in self.handshake.start() {
+            self.send(&message).await?;
+        }
+        while self.handshake.state() != HandshakeState::Established {
+            let message = read_message(&mut self.stream, self.network).await?;
+            for reply in self.handshake.handle(&message)? {
+                self.send(&reply).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn send(&mut self, message: &NetworkMessage) -> Result<(), &'static str> {
+        write_message(&mut self.stream, self.network, message).await
+    }
+
+    // The stream of messages coming from the peer. Pings and feature announcements are dealt
+    // with here, so callers only see the rest.
+    pub async fn receive(&mut self) -> Result<NetworkMessage, &'static str> {
+        loop {
+            let message = read_message(&mut self.stream, self.network).await?;
+            for reply in self.handshake.handle(&message)? {
+                self.send(&reply).await?;
+            }
+            match message {
+                NetworkMessage::Ping(_)
+                | NetworkMessage::SendHeaders
+                | NetworkMessage::WtxidRelay
+                | NetworkMessage::SendAddrV2
+                | NetworkMessage::Verack => continue,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn addr(&self) -> &SocketAddr {
+        &self.addr
+    }
+
+    pub fn remote_version(&self) -> &VersionMessage {
+        self.handshake.remote_version().unwrap()
+    }
+
+    pub fn features(&self) -> &Features {
+        self.handshake.features()
+    }
+}
+
+pub async fn read_envelope<R: AsyncRead + Unpin>(reader: &mut R, network: Network) -> Result<NetworkEnvelope, &'static str> {
+    let mut bytes = vec![0u8; 24];
+    reader.read_exact(&mut bytes).await.map_err(|_| "connection closed")?;
+    let length = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+    if length > MAX_PAYLOAD_SIZE {
+        return Err("payload is too large");
+    }
+    bytes.resize(24 + length, 0);
+    reader.read_exact(&mut bytes[24..]).await.map_err(|_| "connection closed")?;
+    NetworkEnvelope::parse(&mut Cursor::new(bytes), network)
+}
+
+pub async fn read_message<R: AsyncRead + Unpin>(reader: &mut R, network: Network) -> Result<NetworkMessage, &'static str> {
+    read_envelope(reader, network).await?.message()
+}
+
+pub async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, network: Network, message: &NetworkMessage) -> Result<(), &'static str> {
+    let bytes = message.to_envelope(network)?.serialize();
+    writer.write_all(&bytes).await.map_err(|_| "connection closed")?;
+    writer.flush().await.map_err(|_| "connection closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    fn local_version() -> VersionMessage {
+        VersionMessage::new("127.0.0.1:18444".parse().unwrap(), 0).with_nonce(1)
+    }
+
+    fn remote_version(version: i32) -> VersionMessage {
+        VersionMessage::new("127.0.0.1:18444".parse().unwrap(), 100).with_nonce(2).with_version(version)
+    }
+
+    #[test]
+    fn test_handshake() {
+        let mut handshake = Handshake::new(local_version());
+        assert_eq!(handshake.start(), vec![NetworkMessage::Version(local_version())]);
+        assert_eq!(handshake.state(), HandshakeState::AwaitingVersion);
+
+        let replies = handshake.handle(&NetworkMessage::Version(remote_version(70016))).unwrap();
+        assert_eq!(replies, vec![NetworkMessage::WtxidRelay, NetworkMessage::SendAddrV2, NetworkMessage::Verack]);
+        assert_eq!(handshake.state(), HandshakeState::AwaitingVerack);
+
+        assert!(handshake.handle(&NetworkMessage::WtxidRelay).unwrap().is_empty());
+        assert!(handshake.handle(&NetworkMessage::SendAddrV2).unwrap().is_empty());
+        assert_eq!(handshake.handle(&NetworkMessage::Verack).unwrap(), vec![NetworkMessage::SendHeaders]);
+        assert_eq!(handshake.state(), HandshakeState::Established);
+        assert!(handshake.features().wtxid_relay());
+        assert!(handshake.features().addrv2());
+        assert!(!handshake.features().send_headers());
+
+        handshake.handle(&NetworkMessage::SendHeaders).unwrap();
+        assert!(handshake.features().send_headers());
+        assert_eq!(handshake.remote_version().unwrap().start_height(), 100);
+    }
+
+    #[test]
+    fn test_handshake_with_older_peer() {
+        let mut handshake = Handshake::new(local_version());
+        let replies = handshake.handle(&NetworkMessage::Version(remote_version(70015))).unwrap();
+        assert_eq!(replies, vec![NetworkMessage::Verack]);
+        assert!(!replies.contains(&NetworkMessage::SendAddrV2));
+        handshake.handle(&NetworkMessage::WtxidRelay).unwrap();
+        handshake.handle(&NetworkMessage::Verack).unwrap();
+        assert!(!handshake.features().wtxid_relay());
+
+        let mut handshake = Handshake::new(local_version());
+        assert!(handshake.handle(&NetworkMessage::Version(remote_version(209))).is_err());
+    }
+
+    #[test]
+    fn test_handshake_protocol_violations() {
+        let mut handshake = Handshake::new(local_version());
+        assert!(handshake.handle(&NetworkMessage::Verack).is_err());
+        assert!(handshake.handle(&NetworkMessage::WtxidRelay).is_err());
+
+        let mut handshake = Handshake::new(local_version());
+        handshake.handle(&NetworkMessage::Version(remote_version(70016))).unwrap();
+        assert!(handshake.handle(&NetworkMessage::Version(remote_version(70016))).is_err());
+        handshake.handle(&NetworkMessage::Verack).unwrap();
+        assert!(handshake.handle(&NetworkMessage::SendAddrV2).is_err());
+
+        let mut handshake = Handshake::new(local_version());
+        assert!(handshake.handle(&NetworkMessage::Version(local_version())).is_err());
+    }
+
+    #[test]
+    fn test_handshake_answers_pings_and_ignores_early_messages() {
+        let mut handshake = Handshake::new(local_version());
+        assert_eq!(handshake.handle(&NetworkMessage::Ping(5)).unwrap(), vec![NetworkMessage::Pong(5)]);
+        assert!(handshake.handle(&NetworkMessage::SendHeaders).unwrap().is_empty());
+        assert!(handshake.handle(&NetworkMessage::Inv(Vec::new())).unwrap().is_empty());
+        assert!(!handshake.features().send_headers());
+    }
+
+    #[tokio::test]
+    async fn test_peer_over_loopback() {
+        let network = Network::Regtest;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let node = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let version = match read_message(&mut stream, network).await.unwrap() {
+                NetworkMessage::Version(version) => version,
+                _ => panic!("expected a version message"),
+            };
+            assert_eq!(version.start_height(), 7);
+
+            for message in [
+                NetworkMessage::Version(remote_version(70016)),
+                NetworkMessage::WtxidRelay,
+                NetworkMessage::SendAddrV2,
+                NetworkMessage::Verack,
+            ] {
+                write_message(&mut stream, network, &message).await.unwrap();
+            }
+
+            let mut received = Vec::new();
+            loop {
+                let message = read_message(&mut stream, network).await.unwrap();
+                received.push(message.command().to_string());
+                if message == NetworkMessage::SendHeaders {
+                    break;
+                }
+            }
+            assert_eq!(received, vec!["wtxidrelay", "sendaddrv2", "verack", "sendheaders"]);
+
+            write_message(&mut stream, network, &NetworkMessage::Ping(42)).await.unwrap();
+            write_message(&mut stream, network, &NetworkMessage::SendHeaders).await.unwrap();
+            let headers = NetworkMessage::Headers(vec![network.genesis_header()]);
+            write_message(&mut stream, network, &headers).await.unwrap();
+            assert_eq!(read_message(&mut stream, network).await.unwrap(), NetworkMessage::Pong(42));
+        });
+
+        let mut peer = Peer::connect(addr, network, 7).await.unwrap();
+        assert_eq!(peer.remote_version().start_height(), 100);
+        assert!(peer.features().wtxid_relay());
+        assert!(peer.features().addrv2());
+
+        let message = peer.receive().await.unwrap();
+        assert_eq!(message, NetworkMessage::Headers(vec![network.genesis_header()]));
+        assert!(peer.features().send_headers());
+        node.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_rejects_wrong_network() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let node = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_message(&mut stream, Network::Regtest).await.unwrap();
+            let version = NetworkMessage::Version(remote_version(70016));
+            write_message(&mut stream, Network::Mainnet, &version).await.unwrap();
+        });
+
+        assert!(Peer::connect(addr, Network::Regtest, 0).await.is_err());
+        node.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_peer_rejects_verack_before_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let node = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_message(&mut stream, Network::Regtest).await.unwrap();
+            write_message(&mut stream, Network::Regtest, &NetworkMessage::Verack).await.unwrap();
+        });
+
+        assert!(Peer::connect(addr, Network::Regtest, 0).await.is_err());
+        node.await.unwrap();
+    }
+}
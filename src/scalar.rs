@@ -0,0 +1,248 @@
+use std::fmt;
+use std::ops::{Add, Mul, Neg, Sub};
+
+use num_bigint::{BigUint, RandBigInt};
+use rand::RngCore;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::EncodingError;
+use crate::secp256k1::N_S256;
+
+// An integer modulo N, the order of the secp256k1 group, as private keys, nonces, challenges
+// and signature values are. Always kept below N, so equal scalars have equal encodings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scalar {
+    num: BigUint,
+}
+
+impl Scalar {
+    pub const ZERO: Scalar = Scalar { num: BigUint::ZERO };
+
+    pub fn new(num: BigUint) -> Self {
+        match Scalar::try_new(num) {
+            Ok(scalar) => scalar,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_new(num: BigUint) -> Result<Self, EncodingError> {
+        if num >= *N_S256 {
+            return Err(EncodingError::ScalarOutOfRange);
+        }
+        Ok(Scalar { num })
+    }
+
+    // reduces modulo N, for hashes and other numbers of any size
+    pub fn reduce(num: &BigUint) -> Self {
+        Scalar { num: num % &*N_S256 }
+    }
+
+    pub fn from_u64(num: u64) -> Self {
+        Scalar::reduce(&BigUint::from(num))
+    }
+
+    // uniform in 1..N, for new keys and nonces
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        Scalar { num: rng.gen_biguint_range(&BigUint::from(1u32), &N_S256) }
+    }
+
+    // 32 bytes big endian, which have to be below N
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, EncodingError> {
+        Scalar::try_new(BigUint::from_bytes_be(bytes))
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let bytes = self.num.to_bytes_be();
+        let mut result = [0u8; 32];
+        result[32 - bytes.len()..].copy_from_slice(&bytes);
+        result
+    }
+
+    pub fn num(&self) -> &BigUint {
+        &self.num
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.num == BigUint::ZERO
+    }
+
+    // above N/2, where an ECDSA s is the malleable twin of the low s form
+    pub fn is_high(&self) -> bool {
+        self.num > &*N_S256 / 2u32
+    }
+
+    // None for zero. Computed as self^(N-2), by Fermat's little theorem as N is prime: the
+    // exponent is public, so the steps don't depend on the value the way the extended
+    // Euclidean algorithm's do, which matters for nonces.
+    pub fn inverse(&self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        Some(Scalar { num: self.num.modpow(&(&*N_S256 - 2u32), &N_S256) })
+    }
+}
+
+impl<'a> Add<&'a Scalar> for &Scalar {
+    type Output = Scalar;
+
+    fn add(self, other: &'a Scalar) -> Scalar {
+        Scalar::reduce(&(&self.num + &other.num))
+    }
+}
+
+impl<'a> Sub<&'a Scalar> for &Scalar {
+    type Output = Scalar;
+
+    fn sub(self, other: &'a Scalar) -> Scalar {
+        Scalar::reduce(&(&self.num + &*N_S256 - &other.num))
+    }
+}
+
+impl<'a> Mul<&'a Scalar> for &Scalar {
+    type Output = Scalar;
+
+    fn mul(self, other: &'a Scalar) -> Scalar {
+        Scalar::reduce(&(&self.num * &other.num))
+    }
+}
+
+impl Neg for &Scalar {
+    type Output = Scalar;
+
+    fn neg(self) -> Scalar {
+        Scalar::reduce(&(&*N_S256 - &self.num))
+    }
+}
+
+// A secret scalar: a private key or a nonce. It is kept as 32 bytes, which are wiped when it
+// is dropped, rather than as a BigUint, whose digits can't be, and Debug doesn't show it. The
+// BigUint copies that arithmetic on it needs are still left behind in freed memory, so this
+// narrows what lingers rather than ruling it out. It is never zero, so a private key made
+// from one always has a public point.
+#[derive(Clone)]
+pub struct SecretScalar {
+    bytes: [u8; 32],
+}
+
+impl SecretScalar {
+    pub fn new(scalar: Scalar) -> Self {
+        match SecretScalar::try_new(scalar) {
+            Ok(secret) => secret,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    pub fn try_new(scalar: Scalar) -> Result<Self, EncodingError> {
+        if scalar.is_zero() {
+            return Err(EncodingError::ScalarOutOfRange);
+        }
+        Ok(SecretScalar { bytes: scalar.to_bytes() })
+    }
+
+    // uniform in 1..N, drawn straight into bytes
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut secret = SecretScalar { bytes: [0u8; 32] };
+        loop {
+            rng.fill_bytes(&mut secret.bytes);
+            if secret.bytes != [0u8; 32] && below_n(&secret.bytes) {
+                return secret;
+            }
+        }
+    }
+
+    // 32 bytes big endian, in 1..N: zero is no key and no nonce
+    pub fn from_bytes(bytes: &[u8; 32]) -> Result<Self, EncodingError> {
+        if *bytes == [0u8; 32] || !below_n(bytes) {
+            return Err(EncodingError::ScalarOutOfRange);
+        }
+        Ok(SecretScalar { bytes: *bytes })
+    }
+
+    // a short lived copy for arithmetic
+    pub fn scalar(&self) -> Scalar {
+        Scalar { num: BigUint::from_bytes_be(&self.bytes) }
+    }
+
+    pub fn to_bytes(&self) -> Zeroizing<[u8; 32]> {
+        Zeroizing::new(self.bytes)
+    }
+}
+
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretScalar(<redacted>)")
+    }
+}
+
+// compares big endian bytes, without making a BigUint of a secret
+fn below_n(bytes: &[u8; 32]) -> bool {
+    bytes.as_slice() < N_S256.to_bytes_be().as_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let n_minus_one = Scalar::new(&*N_S256 - 1u32);
+        let one = Scalar::from_u64(1);
+        assert_eq!(&n_minus_one + &one, Scalar::ZERO);
+        assert_eq!(&Scalar::ZERO - &one, n_minus_one);
+        assert_eq!(-&one, n_minus_one);
+        assert_eq!(-&Scalar::ZERO, Scalar::ZERO);
+        assert_eq!(&n_minus_one * &n_minus_one, one);
+
+        let a = Scalar::random();
+        let b = Scalar::random();
+        assert_eq!(&(&a + &b) - &b, a);
+        assert_eq!(&a * &a.inverse().unwrap(), one);
+        assert_eq!(&(&a * &b) * &b.inverse().unwrap(), a);
+        assert!(Scalar::ZERO.inverse().is_none());
+        assert_eq!(a.inverse().unwrap().num(), &a.num().modinv(&N_S256).unwrap());
+        assert_eq!(one.inverse().unwrap(), one);
+        assert_eq!(n_minus_one.inverse().unwrap(), n_minus_one);
+        assert!(!a.is_zero());
+        assert!(n_minus_one.is_high() && !one.is_high());
+        assert!(a.is_high() != (-&a).is_high());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let a = Scalar::random();
+        assert_eq!(Scalar::from_bytes(&a.to_bytes()).unwrap(), a);
+        assert_eq!(Scalar::from_u64(1).to_bytes()[31], 1);
+
+        let n: [u8; 32] = N_S256.to_bytes_be().try_into().unwrap();
+        assert_eq!(Scalar::from_bytes(&n), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(Scalar::try_new(N_S256.clone()), Err(EncodingError::ScalarOutOfRange));
+        assert_eq!(Scalar::reduce(&BigUint::from_bytes_be(&n)), Scalar::ZERO);
+        assert_eq!(Scalar::reduce(&(&*N_S256 + 5u32)), Scalar::from_u64(5));
+    }
+
+    #[test]
+    fn test_secret_scalar() {
+        let secret = SecretScalar::random();
+        let scalar = secret.scalar();
+        assert!(!scalar.is_zero());
+        assert_eq!(*secret.to_bytes(), scalar.to_bytes());
+        assert_eq!(SecretScalar::new(scalar.clone()).scalar(), scalar);
+        assert_eq!(SecretScalar::try_new(Scalar::ZERO).unwrap_err(), EncodingError::ScalarOutOfRange);
+        assert_eq!(format!("{:?}", secret), "SecretScalar(<redacted>)");
+
+        let n: [u8; 32] = N_S256.to_bytes_be().try_into().unwrap();
+        assert_eq!(SecretScalar::from_bytes(&n).unwrap_err(), EncodingError::ScalarOutOfRange);
+        assert_eq!(SecretScalar::from_bytes(&[0u8; 32]).unwrap_err(), EncodingError::ScalarOutOfRange);
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(SecretScalar::from_bytes(&one).unwrap().scalar(), Scalar::from_u64(1));
+    }
+}
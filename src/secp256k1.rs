@@ -1,119 +1,1188 @@
-// use std::rc::Rc;
+use std::ops::{Add, Div, Mul, Neg, Sub};
 
-// use std::ops::{Add, Div, Mul, Sub};
+use num_bigint::{BigInt, BigUint, Sign};
+use lazy_static::lazy_static;
 
-// use num_bigint::{BigInt, BigUint, ToBigInt};
+use crate::cripto::{encode_base58_checksum, hash160, schnorr_challenge, SchnorrSignature, Signature};
+use crate::error::{CurveError, EncodingError, FieldError};
+use crate::elliptic_curve::{sum_of_products, JacobianGroup};
+use crate::finite_field::{FieldRef, PrimeField, P};
+use crate::scalar::Scalar;
 
-// use crate::finite_field::{FieldElement, P};
+lazy_static! {
+    pub static ref N_S256: BigUint = BigUint::from_bytes_be(&hex::decode("fffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141").unwrap());
 
-// #[derive(Debug, Clone)]
-// pub struct S256Field (pub FieldElement);
+    static ref G: S256Point = S256Point::new(
+        S256Field::from_bytes(&hex::decode("79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap().try_into().unwrap()).unwrap(),
+        S256Field::from_bytes(&hex::decode("483ada7726a3c4655da4fbfc0e1108a8fd17b448a68554199c47d08ffb10d4b8").unwrap().try_into().unwrap()).unwrap(),
+    ).unwrap();
 
-// impl S256Field {
-//     pub fn new(num: BigUint) -> Self {
-//         S256Field(FieldElement::new(num, Rc::new(&P)))
-//     }
+    // φ(x, y) = (β·x, y) is the same map as multiplying by λ, β and λ being cube roots of
+    // unity modulo P and N
+    pub static ref BETA: S256Field = S256Field::from_bytes(&hex::decode("7ae96a2b657c07106e64479eac3434e99cf0497512f58995c1396c28719501ee").unwrap().try_into().unwrap()).unwrap();
+    pub static ref LAMBDA: BigUint = BigUint::from_bytes_be(&hex::decode("5363ad4cc05c30e0a5261c028812645a122e22ea20816678df02967c1b23bd72").unwrap());
 
-//     fn from_field_element(field_element: FieldElement) -> Self {
-//         S256Field::new(field_element.num().clone())
-//     }
+    // a short basis (a1, b1), (a2, b2) of the lattice of (x, y) with x + y·λ = 0 mod N
+    static ref GLV_BASIS: [BigInt; 4] = {
+        let a1 = BigInt::from_bytes_be(Sign::Plus, &hex::decode("3086d221a7d46bcde86c90e49284eb15").unwrap());
+        let b1 = BigInt::from_bytes_be(Sign::Minus, &hex::decode("e4437ed6010e88286f547fa90abfe4c3").unwrap());
+        let a2 = BigInt::from_bytes_be(Sign::Plus, &hex::decode("0114ca50f7a8e2f3f657c1108d9d44cfd8").unwrap());
+        [a1.clone(), b1, a2, a1]
+    };
 
-//     pub fn from_S256_field(other: S256Field) -> Self {
-//         S256Field::new(other.num().clone())
-//     }
+    // GENERATOR_TABLE[i][j] = j·16^i·G, one row per 4 bit window of the scalar
+    static ref GENERATOR_TABLE: Vec<[Projective; 16]> = {
+        let mut base = Projective::from_affine(&G);
+        let mut rows = Vec::with_capacity(64);
+        for _ in 0..64 {
+            let mut row = [Projective::IDENTITY; 16];
+            for j in 1..16 {
+                row[j] = row[j - 1].add(&base);
+            }
+            base = row[15].add(&base);
+            rows.push(row);
+        }
+        rows
+    };
+}
 
-//     pub fn num(&self) -> &BigUint {
-//         self.0.num()
-//     }
+// P = 2^256 - 2^32 - 977, so 2^256 is congruent to this small constant modulo P
+const C: u64 = 0x1000003d1;
 
-//     pub fn sqrt(&self) -> Self {
-//         let exp = (&P.clone() + BigUint::from(1u32)) / BigUint::from(4u32);
-//         S256Field(self.0.pow(&exp.to_bigint().unwrap()))
-//     }
+const P_LIMBS: [u64; 4] = [0xfffffffefffffc2f, 0xffffffffffffffff, 0xffffffffffffffff, 0xffffffffffffffff];
+const P_MINUS_2: [u64; 4] = [0xfffffffefffffc2d, 0xffffffffffffffff, 0xffffffffffffffff, 0xffffffffffffffff];
+const P_PLUS_1_DIV_4: [u64; 4] = [0xffffffffbfffff0c, 0xffffffffffffffff, 0xffffffffffffffff, 0x3fffffffffffffff];
 
-//     pub fn pow(&self, exp: &BigInt) -> Self {
-//         S256Field(self.0.pow(&exp.to_bigint().unwrap()))
-//     }
-// }
+// An element of the secp256k1 base field held in four 64 bit limbs, least significant first
+// and always fully reduced. Reduction folds the high half of a product back in multiplied by
+// C instead of dividing by P.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct S256Field {
+    limbs: [u64; 4],
+}
 
-// impl PartialEq for S256Field {
-//     fn eq(&self, other: &Self) -> bool {
-//         self.0 == other.0
-//     }
-// }
+impl S256Field {
+    pub const ZERO: S256Field = S256Field { limbs: [0; 4] };
+    pub const ONE: S256Field = S256Field { limbs: [1, 0, 0, 0] };
 
-// impl Add for S256Field {
-//     type Output = Self;
+    pub fn new(num: BigUint) -> Self {
+        match S256Field::try_new(num) {
+            Ok(element) => element,
+            Err(err) => panic!("{}", err),
+        }
+    }
 
-//     fn add(self, other: Self) -> Self {
-//         S256Field::from_field_element(self.0 + other.0)
-//     }
-// }
+    pub fn try_new(num: BigUint) -> Result<Self, FieldError> {
+        if num >= *P {
+            return Err(FieldError::NotInField { num, prime: P.clone() });
+        }
+        let mut limbs = [0u64; 4];
+        for (limb, digit) in limbs.iter_mut().zip(num.iter_u64_digits()) {
+            *limb = digit;
+        }
+        Ok(S256Field { limbs })
+    }
 
-// impl<'a, 'b> Add<&'b S256Field> for &'a S256Field {
-//     type Output = S256Field;
+    pub fn from_u64(num: u64) -> Self {
+        S256Field { limbs: [num, 0, 0, 0] }
+    }
 
-//     fn add(self, other: &'b S256Field) -> S256Field {
-//         S256Field::from_field_element(&self.0 + &other.0)
-//     }
-// }
+    // big endian, like the coordinates in a SEC encoding; None when not below P
+    pub fn from_bytes(bytes: &[u8; 32]) -> Option<Self> {
+        let mut limbs = [0u64; 4];
+        for (i, limb) in limbs.iter_mut().enumerate() {
+            *limb = u64::from_be_bytes(bytes[24 - 8 * i..32 - 8 * i].try_into().unwrap());
+        }
+        let (_, borrow) = sub_limbs(&limbs, &P_LIMBS);
+        if borrow == 0 {
+            return None;
+        }
+        Some(S256Field { limbs })
+    }
 
-// impl Sub for S256Field {
-//     type Output = Self;
+    pub fn to_bytes(&self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, limb) in self.limbs.iter().enumerate() {
+            bytes[24 - 8 * i..32 - 8 * i].copy_from_slice(&limb.to_be_bytes());
+        }
+        bytes
+    }
 
-//     fn sub(self, other: Self) -> Self {
-//         S256Field::from_field_element(self.0 - other.0)
-//     }
-// }
+    pub fn num(&self) -> BigUint {
+        BigUint::from_bytes_be(&self.to_bytes())
+    }
 
-// impl<'a, 'b> Sub<&'b S256Field> for &'a S256Field {
-//     type Output = S256Field;
+    pub fn is_zero(&self) -> bool {
+        self.limbs == [0; 4]
+    }
 
-//     fn sub(self, other: &'b S256Field) -> S256Field {
-//         S256Field::from_field_element(&self.0 - &other.0)
-//     }
-// }
+    pub fn is_even(&self) -> bool {
+        self.limbs[0] & 1 == 0
+    }
 
-// impl Mul for S256Field {
-//     type Output = Self;
+    pub fn square(&self) -> Self {
+        self * self
+    }
 
-//     fn mul(self, rhs: Self) -> Self {
-//         S256Field::from_field_element(self.0 * rhs.0)
-//     }
-// }
+    pub fn pow(&self, exp: &BigUint) -> Self {
+        let mut limbs = [0u64; 4];
+        for (limb, digit) in limbs.iter_mut().zip((exp % (&*P - 1u32)).iter_u64_digits()) {
+            *limb = digit;
+        }
+        self.pow_limbs(&limbs)
+    }
 
-// impl<'a, 'b> Mul<&'b S256Field> for &'a S256Field {
-//     type Output = S256Field;
+    fn pow_limbs(&self, exp: &[u64; 4]) -> Self {
+        let mut result = S256Field::ONE;
+        for limb in exp.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result.square();
+                if (limb >> bit) & 1 == 1 {
+                    result = &result * self;
+                }
+            }
+        }
+        result
+    }
 
-//     fn mul(self, other: &'b S256Field) -> S256Field {
-//         S256Field::from_field_element(&self.0 * &other.0)
-//     }
-// }
+    // by Fermat's little theorem, a^(P-2) = a^-1; zero has no inverse and maps to zero
+    pub fn inverse(&self) -> Self {
+        self.pow_limbs(&P_MINUS_2)
+    }
 
-// impl Div for S256Field {
-//     type Output = Self;
+    // P = 3 mod 4, so a square root, when there is one, is a^((P+1)/4)
+    pub fn sqrt(&self) -> Option<Self> {
+        let root = self.pow_limbs(&P_PLUS_1_DIV_4);
+        if root.square() == *self {
+            Some(root)
+        } else {
+            None
+        }
+    }
+}
 
-//     fn div(self, rhs: Self) -> Self::Output {
-//         S256Field::from_field_element(self.0 / rhs.0)
-//     }
-// }
+impl PrimeField for S256Field {
+    fn modulus() -> &'static BigUint {
+        &P
+    }
 
-// impl<'a, 'b> Div<&'b S256Field> for &'a S256Field {
-//     type Output = S256Field;
-
-//     fn div(self, other: &'b S256Field) -> S256Field {
-//         S256Field::from_field_element(&self.0 / &other.0)
-//     }
-// }
-
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-
-//     #[test]
-//     fn test_new_s256field() {
-//         let new_s256field = S256Field::new(BigUint::from(15u32));
-//         assert_eq!(*new_s256field.0.prime(), Rc::new(P.clone()))
-        
-//     }
-// }
\ No newline at end of file
+    fn from_biguint(num: &BigUint) -> Self {
+        S256Field::new(num % &*P)
+    }
+
+    fn to_biguint(&self) -> BigUint {
+        self.num()
+    }
+
+    fn is_zero(&self) -> bool {
+        S256Field::is_zero(self)
+    }
+
+    fn inverse(&self) -> Self {
+        S256Field::inverse(self)
+    }
+
+    fn sqrt(&self) -> Option<Self> {
+        S256Field::sqrt(self)
+    }
+
+    fn is_square(&self) -> bool {
+        S256Field::sqrt(self).is_some()
+    }
+
+    fn zero() -> Self {
+        S256Field::ZERO
+    }
+
+    fn one() -> Self {
+        S256Field::ONE
+    }
+}
+
+impl FieldRef<S256Field> for &S256Field {}
+
+impl<'a> Add<&'a S256Field> for &S256Field {
+    type Output = S256Field;
+
+    fn add(self, other: &'a S256Field) -> S256Field {
+        S256Field { limbs: add_mod_p(&self.limbs, &other.limbs) }
+    }
+}
+
+impl<'a> Sub<&'a S256Field> for &S256Field {
+    type Output = S256Field;
+
+    fn sub(self, other: &'a S256Field) -> S256Field {
+        let (diff, borrow) = sub_limbs(&self.limbs, &other.limbs);
+        // adding P back is the same as subtracting C modulo 2^256
+        let (wrapped, _) = sub_limbs(&diff, &[C, 0, 0, 0]);
+        S256Field { limbs: select(borrow, &wrapped, &diff) }
+    }
+}
+
+impl<'a> Mul<&'a S256Field> for &S256Field {
+    type Output = S256Field;
+
+    fn mul(self, other: &'a S256Field) -> S256Field {
+        let mut wide = [0u64; 8];
+        for i in 0..4 {
+            let mut carry = 0u128;
+            for j in 0..4 {
+                let v = wide[i + j] as u128 + self.limbs[i] as u128 * other.limbs[j] as u128 + carry;
+                wide[i + j] = v as u64;
+                carry = v >> 64;
+            }
+            wide[i + 4] = carry as u64;
+        }
+        S256Field { limbs: reduce_wide(&wide) }
+    }
+}
+
+impl<'a> Div<&'a S256Field> for &S256Field {
+    type Output = Result<S256Field, FieldError>;
+
+    fn div(self, other: &'a S256Field) -> Self::Output {
+        if other.is_zero() {
+            return Err(FieldError::DivisionByZero);
+        }
+        Ok(Mul::mul(self, &other.inverse()))
+    }
+}
+
+impl Neg for &S256Field {
+    type Output = S256Field;
+
+    fn neg(self) -> S256Field {
+        &S256Field::ZERO - self
+    }
+}
+
+fn add_mod_p(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let (sum, carry) = add_limbs(a, b);
+    // sum >= P exactly when sum + C overflows 2^256 (or the sum already did)
+    let (reduced, carry2) = add_limbs(&sum, &[C, 0, 0, 0]);
+    select(carry | carry2, &reduced, &sum)
+}
+
+fn add_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], u64) {
+    let mut result = [0u64; 4];
+    let mut carry = 0u64;
+    for i in 0..4 {
+        let (sum, c1) = a[i].overflowing_add(b[i]);
+        let (sum, c2) = sum.overflowing_add(carry);
+        result[i] = sum;
+        carry = (c1 | c2) as u64;
+    }
+    (result, carry)
+}
+
+fn sub_limbs(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], u64) {
+    let mut result = [0u64; 4];
+    let mut borrow = 0u64;
+    for i in 0..4 {
+        let (diff, b1) = a[i].overflowing_sub(b[i]);
+        let (diff, b2) = diff.overflowing_sub(borrow);
+        result[i] = diff;
+        borrow = (b1 | b2) as u64;
+    }
+    (result, borrow)
+}
+
+// picks `a` when flag is 1 and `b` when it is 0, without branching on it
+fn select(flag: u64, a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mask = 0u64.wrapping_sub(flag);
+    [0, 1, 2, 3].map(|i| (a[i] & mask) | (b[i] & !mask))
+}
+
+// Reduces a 512 bit product: hi·2^256 + lo = lo + hi·C (mod P). The first fold leaves at
+// most 35 bits above 2^256, the second at most a carry, and a final subtraction makes the
+// result canonical.
+fn reduce_wide(wide: &[u64; 8]) -> [u64; 4] {
+    let mut folded = [0u64; 4];
+    let mut carry = 0u128;
+    for i in 0..4 {
+        let v = wide[i] as u128 + wide[i + 4] as u128 * C as u128 + carry;
+        folded[i] = v as u64;
+        carry = v >> 64;
+    }
+
+    let mut carry = carry * C as u128;
+    for limb in folded.iter_mut() {
+        let v = *limb as u128 + carry;
+        *limb = v as u64;
+        carry = v >> 64;
+    }
+    let (folded, _) = add_limbs(&folded, &[(carry as u64) * C, 0, 0, 0]);
+
+    let (reduced, borrow) = sub_limbs(&folded, &P_LIMBS);
+    select(borrow, &folded, &reduced)
+}
+
+// Jacobian coordinates, see elliptic_curve::JacobianPoint; secp256k1 has a = 0, which
+// drops a term from doubling
+#[derive(Debug, Clone, Copy)]
+struct Jacobian {
+    x: S256Field,
+    y: S256Field,
+    z: S256Field,
+}
+
+impl Jacobian {
+    const INFINITY: Jacobian = Jacobian { x: S256Field::ONE, y: S256Field::ONE, z: S256Field::ZERO };
+
+    fn from_affine(point: &S256Point) -> Self {
+        match point.xy {
+            Some((x, y)) => Jacobian { x, y, z: S256Field::ONE },
+            None => Jacobian::INFINITY,
+        }
+    }
+
+    fn is_infinity(&self) -> bool {
+        self.z.is_zero()
+    }
+
+    fn to_affine(self) -> S256Point {
+        if self.is_infinity() {
+            return S256Point::infinity();
+        }
+        let z_inv = self.z.inverse();
+        let z_inv2 = z_inv.square();
+        let x = &self.x * &z_inv2;
+        let y = &(&self.y * &z_inv2) * &z_inv;
+        S256Point { xy: Some((x, y)) }
+    }
+
+    fn double(&self) -> Self {
+        if self.is_infinity() || self.y.is_zero() {
+            return Jacobian::INFINITY;
+        }
+        let xx = self.x.square();
+        let yy = self.y.square();
+        let xyy = &self.x * &yy;
+        let xyy2 = &xyy + &xyy;
+        let s = &xyy2 + &xyy2;
+        let m = &(&xx + &xx) + &xx;
+        let x = &m.square() - &(&s + &s);
+        let yyyy = yy.square();
+        let yyyy2 = &yyyy + &yyyy;
+        let yyyy4 = &yyyy2 + &yyyy2;
+        let y = &(&m * &(&s - &x)) - &(&yyyy4 + &yyyy4);
+        let yz = &self.y * &self.z;
+        let z = &yz + &yz;
+        Jacobian { x, y, z }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        if self.is_infinity() {
+            return *other;
+        }
+        if other.is_infinity() {
+            return *self;
+        }
+        let z1z1 = self.z.square();
+        let z2z2 = other.z.square();
+        let u1 = &self.x * &z2z2;
+        let u2 = &other.x * &z1z1;
+        let s1 = &(&self.y * &other.z) * &z2z2;
+        let s2 = &(&other.y * &self.z) * &z1z1;
+
+        if u1 == u2 {
+            if s1 != s2 {
+                return Jacobian::INFINITY;
+            }
+            return self.double();
+        }
+
+        let h = &u2 - &u1;
+        let r = &s2 - &s1;
+        let hh = h.square();
+        let hhh = &h * &hh;
+        let v = &u1 * &hh;
+        let x = &(&r.square() - &hhh) - &(&v + &v);
+        let y = &(&r * &(&v - &x)) - &(&s1 * &hhh);
+        let z = &(&self.z * &other.z) * &h;
+        Jacobian { x, y, z }
+    }
+
+    fn neg(&self) -> Self {
+        Jacobian { x: self.x, y: -&self.y, z: self.z }
+    }
+
+    // φ in Jacobian coordinates, x = X/Z² only picks up the factor β
+    fn endomorphism(&self) -> Self {
+        Jacobian { x: &self.x * &BETA, y: self.y, z: self.z }
+    }
+
+    fn mul(&self, scalar: &BigUint) -> Self {
+        let mut result = Jacobian::INFINITY;
+        for i in (0..scalar.bits()).rev() {
+            result = result.double();
+            if scalar.bit(i) {
+                result = result.add(self);
+            }
+        }
+        result
+    }
+}
+
+fn multi_mul(terms: &[(&S256Point, &BigUint)]) -> Jacobian {
+    let mut expanded = Vec::with_capacity(2 * terms.len());
+    for (point, scalar) in terms {
+        let point = Jacobian::from_affine(point);
+        if cfg!(feature = "glv") {
+            let (k1, k2) = glv_decompose(scalar);
+            expanded.push(signed_term(point, k1));
+            expanded.push(signed_term(point.endomorphism(), k2));
+        } else {
+            expanded.push((point, mod_n(scalar)));
+        }
+    }
+    sum_of_products(&(), &expanded)
+}
+
+// Splits k into k₁ + k₂·λ = k mod N with k₁ and k₂ of about 128 bits each (GLV), by
+// rounding k to the nearest point of the lattice spanned by GLV_BASIS
+pub fn glv_decompose(scalar: &BigUint) -> (BigInt, BigInt) {
+    let [a1, b1, a2, b2] = &*GLV_BASIS;
+    let n = BigInt::from(N_S256.clone());
+    let k = BigInt::from(mod_n(scalar));
+    // round(x / n) for x >= 0
+    let round = |x: BigInt| (2 * x + &n) / (2 * &n);
+    let c1: BigInt = round(b2 * &k);
+    let c2: BigInt = round(-b1 * &k);
+    let k1 = k - &c1 * a1 - &c2 * a2;
+    let k2 = -c1 * b1 - c2 * b2;
+    (k1, k2)
+}
+
+fn signed_term(point: Jacobian, scalar: BigInt) -> (Jacobian, BigUint) {
+    match scalar.sign() {
+        Sign::Minus => (point.neg(), scalar.magnitude().clone()),
+        _ => (point, scalar.magnitude().clone()),
+    }
+}
+
+impl JacobianGroup for Jacobian {
+    type Curve = ();
+
+    fn infinity() -> Self {
+        Jacobian::INFINITY
+    }
+
+    fn neg(&self) -> Self {
+        Jacobian::neg(self)
+    }
+
+    fn double(&self, _: &()) -> Self {
+        Jacobian::double(self)
+    }
+
+    fn add(&self, other: &Self, _: &()) -> Self {
+        Jacobian::add(self, other)
+    }
+}
+
+// Homogeneous projective coordinates (X : Y : Z) for the affine point (X/Z, Y/Z), with the
+// complete formulas of Renes, Costello and Batina (2015) for a = 0. Unlike the Jacobian ones
+// they have no special cases for doubling or infinity, so the same field operations run
+// whatever the inputs are, which is what the constant-time multiplication relies on.
+#[derive(Debug, Clone, Copy)]
+struct Projective {
+    x: S256Field,
+    y: S256Field,
+    z: S256Field,
+}
+
+// 3·b, with b = 7
+const B3: S256Field = S256Field { limbs: [21, 0, 0, 0] };
+
+impl Projective {
+    const IDENTITY: Projective = Projective { x: S256Field::ZERO, y: S256Field::ONE, z: S256Field::ZERO };
+
+    fn from_affine(point: &S256Point) -> Self {
+        match point.xy {
+            Some((x, y)) => Projective { x, y, z: S256Field::ONE },
+            None => Projective::IDENTITY,
+        }
+    }
+
+    fn to_affine(self) -> S256Point {
+        if self.z.is_zero() {
+            return S256Point::infinity();
+        }
+        let z_inv = self.z.inverse();
+        S256Point { xy: Some((&self.x * &z_inv, &self.y * &z_inv)) }
+    }
+
+    // algorithm 7 of the paper
+    fn add(&self, other: &Self) -> Self {
+        let t0 = &self.x * &other.x;
+        let t1 = &self.y * &other.y;
+        let t2 = &self.z * &other.z;
+        let t3 = &(&self.x + &self.y) * &(&other.x + &other.y);
+        let t3 = &t3 - &(&t0 + &t1);
+        let t4 = &(&self.y + &self.z) * &(&other.y + &other.z);
+        let t4 = &t4 - &(&t1 + &t2);
+        let y3 = &(&self.x + &self.z) * &(&other.x + &other.z);
+        let y3 = &y3 - &(&t0 + &t2);
+        let t0 = &(&t0 + &t0) + &t0;
+        let t2 = &B3 * &t2;
+        let z3 = &t1 + &t2;
+        let t1 = &t1 - &t2;
+        let y3 = &B3 * &y3;
+        let x3 = &(&t3 * &t1) - &(&t4 * &y3);
+        let y3 = &(&t1 * &z3) + &(&y3 * &t0);
+        let z3 = &(&z3 * &t4) + &(&t0 * &t3);
+        Projective { x: x3, y: y3, z: z3 }
+    }
+
+    // algorithm 9 of the paper
+    fn double(&self) -> Self {
+        let t0 = self.y.square();
+        let z3 = &t0 + &t0;
+        let z3 = &z3 + &z3;
+        let z3 = &z3 + &z3;
+        let t1 = &self.y * &self.z;
+        let t2 = &B3 * &self.z.square();
+        let x3 = &t2 * &z3;
+        let y3 = &t0 + &t2;
+        let z3 = &t1 * &z3;
+        let t2 = &(&t2 + &t2) + &t2;
+        let t0 = &t0 - &t2;
+        let y3 = &x3 + &(&t0 * &y3);
+        let t1 = &self.x * &self.y;
+        let x3 = &t0 * &t1;
+        let x3 = &x3 + &x3;
+        Projective { x: x3, y: y3, z: z3 }
+    }
+
+    fn select(flag: u64, a: &Self, b: &Self) -> Self {
+        Projective {
+            x: S256Field { limbs: select(flag, &a.x.limbs, &b.x.limbs) },
+            y: S256Field { limbs: select(flag, &a.y.limbs, &b.y.limbs) },
+            z: S256Field { limbs: select(flag, &a.z.limbs, &b.z.limbs) },
+        }
+    }
+
+    // Fixed window multiplication: 64 windows of 4 bits, each costing four doublings and an
+    // addition of a table entry read by scanning the whole table, so neither the sequence of
+    // operations nor the memory accessed depends on the scalar.
+    fn mul_ct(&self, scalar: &[u64; 4]) -> Self {
+        let mut table = [Projective::IDENTITY; 16];
+        for i in 1..16 {
+            table[i] = table[i - 1].add(self);
+        }
+
+        let mut result = Projective::IDENTITY;
+        for limb in scalar.iter().rev() {
+            for window in (0..16).rev() {
+                for _ in 0..4 {
+                    result = result.double();
+                }
+                let index = (limb >> (4 * window)) & 0xf;
+                result = result.add(&lookup(&table, index));
+            }
+        }
+        result
+    }
+}
+
+// k·G as a sum of one table entry per window, with no doublings at all
+fn mul_generator(scalar: &[u64; 4]) -> Projective {
+    let mut result = Projective::IDENTITY;
+    for (i, row) in GENERATOR_TABLE.iter().enumerate() {
+        let index = (scalar[i / 16] >> (4 * (i % 16))) & 0xf;
+        result = result.add(&lookup(row, index));
+    }
+    result
+}
+
+fn lookup(table: &[Projective; 16], index: u64) -> Projective {
+    let mut result = Projective::IDENTITY;
+    for (i, entry) in table.iter().enumerate() {
+        result = Projective::select(ct_eq(i as u64, index), entry, &result);
+    }
+    result
+}
+
+// 1 when a == b, 0 otherwise
+fn ct_eq(a: u64, b: u64) -> u64 {
+    let x = a ^ b;
+    ((x | x.wrapping_neg()) >> 63) ^ 1
+}
+
+fn scalar_limbs(scalar: &BigUint) -> [u64; 4] {
+    let mut limbs = [0u64; 4];
+    for (limb, digit) in limbs.iter_mut().zip(mod_n(scalar).iter_u64_digits()) {
+        *limb = digit;
+    }
+    limbs
+}
+
+// A point on secp256k1, y² = x³ + 7 over the field of order P, kept in affine coordinates.
+// None is the point at infinity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct S256Point {
+    xy: Option<(S256Field, S256Field)>,
+}
+
+impl S256Point {
+    pub fn new(x: S256Field, y: S256Field) -> Result<Self, CurveError> {
+        if y.square() != &(&x.square() * &x) + &S256Field::from_u64(7) {
+            return Err(CurveError::NotOnCurve);
+        }
+        Ok(S256Point { xy: Some((x, y)) })
+    }
+
+    pub fn infinity() -> Self {
+        S256Point { xy: None }
+    }
+
+    pub fn generator() -> Self {
+        G.clone()
+    }
+
+    // constant-time k·G using the precomputed generator table, much faster than mul_ct on
+    // the generator; this is what key derivation and signing use
+    pub fn mul_generator(scalar: &BigUint) -> Self {
+        mul_generator(&scalar_limbs(scalar)).to_affine()
+    }
+
+    pub fn xy(&self) -> Option<(&S256Field, &S256Field)> {
+        self.xy.as_ref().map(|(x, y)| (x, y))
+    }
+
+    pub fn is_infinity(&self) -> bool {
+        self.xy.is_none()
+    }
+
+    // Multiplication for secret scalars (private keys, nonces, ECDH), which takes the same
+    // time and touches the same memory whatever the scalar. The `*` operator is faster but
+    // branches on the scalar bits, so it is only meant for public values such as in verify.
+    pub fn mul_ct(&self, scalar: &BigUint) -> Self {
+        Projective::from_affine(self).mul_ct(&scalar_limbs(scalar)).to_affine()
+    }
+
+    // checks an ECDSA signature over the message hash z: with u = z/s and v = r/s, the x
+    // coordinate of u·G + v·P has to be r
+    pub fn verify(&self, z: &BigUint, sig: &Signature) -> bool {
+        let r = sig.r();
+        let s_inv = match sig.s().inverse() {
+            Some(s_inv) if !r.is_zero() => s_inv,
+            _ => return false,
+        };
+        let u = &Scalar::reduce(z) * &s_inv;
+        let v = r * &s_inv;
+        match S256Point::multi_mul(&[(&G, u.num()), (self, v.num())]).xy() {
+            Some((x, _)) => Scalar::reduce(&x.num()) == *r,
+            None => false,
+        }
+    }
+
+    // BIP 340: with e the challenge hash of (r, P, m), s·G - e·P has to be the point R with
+    // an even y and x coordinate r. Only the x coordinate of the key counts.
+    pub fn verify_schnorr(&self, msg: &[u8], sig: &SchnorrSignature) -> bool {
+        let pubkey = match self.xy() {
+            Some(_) => self.xonly(),
+            None => return false,
+        };
+        let point = S256Point::lift_x(&pubkey).expect("x coordinate of a curve point");
+        let r = sig.r().to_bytes();
+        let e = schnorr_challenge(&r, &pubkey, msg);
+        let minus_e = -&e;
+        match S256Point::multi_mul(&[(&G, sig.s().num()), (&point, minus_e.num())]).xy() {
+            Some((x, y)) => y.is_even() && x == sig.r(),
+            None => false,
+        }
+    }
+
+    // k₁·P₁ + k₂·P₂ + ... for public scalars, see elliptic_curve::Point::multi_mul
+    pub fn multi_mul(terms: &[(&S256Point, &BigUint)]) -> Self {
+        multi_mul(terms).to_affine()
+    }
+
+    // the point with this x coordinate and an even y, how BIP 340 reads 32 byte public keys
+    pub fn lift_x(x: &[u8; 32]) -> Result<Self, EncodingError> {
+        let x = S256Field::from_bytes(x).ok_or(EncodingError::NotAFieldElement)?;
+        let y = (&(&x.square() * &x) + &S256Field::from_u64(7)).sqrt().ok_or(CurveError::NotOnCurve)?;
+        let y = if y.is_even() { y } else { -&y };
+        Ok(S256Point { xy: Some((x, y)) })
+    }
+
+    // the 32 byte x-only public key of BIP 340
+    pub fn xonly(&self) -> [u8; 32] {
+        let (x, _) = self.xy.expect("the point at infinity has no x coordinate");
+        x.to_bytes()
+    }
+
+    pub fn uncompressed_sec(&self) -> Vec<u8> {
+        let (x, y) = self.xy.expect("the point at infinity has no SEC encoding");
+        let mut result = vec![0x04];
+        result.extend_from_slice(&x.to_bytes());
+        result.extend_from_slice(&y.to_bytes());
+        result
+    }
+
+    pub fn compressed_sec(&self) -> Vec<u8> {
+        let (x, y) = self.xy.expect("the point at infinity has no SEC encoding");
+        let mut result = vec![if y.is_even() { 0x02 } else { 0x03 }];
+        result.extend_from_slice(&x.to_bytes());
+        result
+    }
+
+    pub fn parse(sec: &[u8]) -> Result<Self, EncodingError> {
+        let coordinate = |bytes: &[u8]| S256Field::from_bytes(bytes.try_into().unwrap()).ok_or(EncodingError::NotAFieldElement);
+        match (sec.first(), sec.len()) {
+            (Some(0x04), 65) => Ok(S256Point::new(coordinate(&sec[1..33])?, coordinate(&sec[33..])?)?),
+            (Some(prefix @ (0x02 | 0x03)), 33) => {
+                let x = coordinate(&sec[1..])?;
+                let alpha = &(&x.square() * &x) + &S256Field::from_u64(7);
+                let beta = alpha.sqrt().ok_or(CurveError::NotOnCurve)?;
+                let y = if beta.is_even() == (*prefix == 0x02) { beta } else { -&beta };
+                Ok(S256Point { xy: Some((x, y)) })
+            }
+            (Some(0x04), found) => Err(EncodingError::InvalidLength { expected: 65, found }),
+            (Some(0x02 | 0x03), found) => Err(EncodingError::InvalidLength { expected: 33, found }),
+            (Some(prefix), _) => Err(EncodingError::InvalidPrefix(*prefix)),
+            (None, _) => Err(EncodingError::InvalidLength { expected: 33, found: 0 }),
+        }
+    }
+
+    pub fn hash160(&self, compressed: bool) -> Vec<u8> {
+        if compressed {
+            hash160(&self.compressed_sec())
+        } else {
+            hash160(&self.uncompressed_sec())
+        }
+    }
+
+    pub fn address(&self, compressed: bool, testnet: bool) -> String {
+        let mut bytes = vec![if testnet { 0x6f } else { 0x00 }];
+        bytes.extend(self.hash160(compressed));
+        encode_base58_checksum(&bytes)
+    }
+}
+
+impl<'a> Add<&'a S256Point> for &S256Point {
+    type Output = S256Point;
+
+    fn add(self, other: &'a S256Point) -> S256Point {
+        Jacobian::from_affine(self).add(&Jacobian::from_affine(other)).to_affine()
+    }
+}
+
+impl<'a> Mul<&'a BigUint> for &S256Point {
+    type Output = S256Point;
+
+    fn mul(self, scalar: &'a BigUint) -> S256Point {
+        if cfg!(feature = "glv") {
+            multi_mul(&[(self, scalar)]).to_affine()
+        } else {
+            Jacobian::from_affine(self).mul(&mod_n(scalar)).to_affine()
+        }
+    }
+}
+
+// Checks many BIP 340 signatures at once. Every equation s·G = R + e·P gets a random weight
+// a and the weighted sum (Σ a·s)·G - Σ a·R - Σ (a·e)·P = 0 is checked with a single
+// multi-scalar multiplication; the weights keep an invalid signature from being cancelled out
+// by another one. If the batch fails, the signatures are checked one by one and the indices of
+// the invalid ones are returned.
+pub fn verify_schnorr_batch(items: &[(&S256Point, &[u8], &SchnorrSignature)]) -> Result<(), Vec<usize>> {
+    if batch_holds(items).unwrap_or(false) {
+        return Ok(());
+    }
+    let invalid: Vec<usize> = items.iter().enumerate()
+        .filter(|(_, (pubkey, msg, sig))| !pubkey.verify_schnorr(msg, sig))
+        .map(|(i, _)| i)
+        .collect();
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid)
+    }
+}
+
+// None when some key or nonce isn't a valid x coordinate
+fn batch_holds(items: &[(&S256Point, &[u8], &SchnorrSignature)]) -> Option<bool> {
+    let mut points = Vec::with_capacity(2 * items.len());
+    let mut scalars = Vec::with_capacity(2 * items.len());
+    let mut s_total = Scalar::ZERO;
+    for (i, (pubkey, msg, sig)) in items.iter().enumerate() {
+        let pubkey = pubkey.xy().map(|_| pubkey.xonly())?;
+        let r = sig.r().to_bytes();
+        // the first weight can be 1 without weakening the check
+        let weight = if i == 0 { Scalar::from_u64(1) } else { Scalar::random() };
+        let e = schnorr_challenge(&r, &pubkey, msg);
+        s_total = &s_total + &(&weight * sig.s());
+        scalars.push(-&(&weight * &e));
+        scalars.push(-&weight);
+        points.push(S256Point::lift_x(&pubkey).ok()?);
+        points.push(S256Point::lift_x(&r).ok()?);
+    }
+    let mut terms: Vec<(&S256Point, &BigUint)> = points.iter().zip(scalars.iter().map(Scalar::num)).collect();
+    terms.push((&G, s_total.num()));
+    Some(S256Point::multi_mul(&terms).is_infinity())
+}
+
+fn mod_n(scalar: &BigUint) -> BigUint {
+    scalar % &*N_S256
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use num_bigint::RandBigInt;
+
+    use super::*;
+    use crate::elliptic_curve::{pippenger, strauss, EllipticCurve, Point};
+    use crate::finite_field::FieldElement;
+    use crate::named_curve::Secp256k1Prime;
+
+    #[test]
+    fn test_new_s256field() {
+        let new_s256field = S256Field::new(BigUint::from(15u32));
+        assert_eq!(new_s256field.num(), BigUint::from(15u32));
+        assert_eq!(S256Field::from_bytes(&(&*P - 1u32).to_bytes_be().try_into().unwrap()), Some(-&S256Field::ONE));
+        assert_eq!(S256Field::from_bytes(&P.to_bytes_be().try_into().unwrap()), None);
+    }
+
+    #[test]
+    fn test_try_new_s256field() {
+        assert_eq!(S256Field::try_new(BigUint::from(15u32)), Ok(S256Field::from_u64(15)));
+        assert_eq!(S256Field::try_new(P.clone()), Err(FieldError::NotInField { num: P.clone(), prime: P.clone() }));
+        assert_eq!(&S256Field::ONE / &S256Field::ZERO, Err(FieldError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_constants() {
+        let limbs = |limbs: &[u64; 4]| limbs.iter().rev().fold(BigUint::ZERO, |acc, limb| (acc << 64u32) + *limb);
+        assert_eq!(limbs(&P_LIMBS), *P);
+        assert_eq!(limbs(&P_MINUS_2), &*P - 2u32);
+        assert_eq!(limbs(&P_PLUS_1_DIV_4), (&*P + 1u32) / 4u32);
+        assert_eq!((BigUint::from(1u32) << 256u32) % &*P, BigUint::from(C));
+    }
+
+    // cross-checks the limb arithmetic against the generic BigUint field
+    #[test]
+    fn test_field_matches_field_element() {
+        let mut rng = rand::thread_rng();
+        let mut samples: Vec<BigUint> = (0..50).map(|_| rng.gen_biguint_below(&P)).collect();
+        samples.extend([BigUint::ZERO, BigUint::from(1u32), &*P - 1u32, &*P - 2u32, (BigUint::from(1u32) << 255u32) + 19u32]);
+
+        for pair in samples.windows(2) {
+            let (a, b) = (S256Field::new(pair[0].clone()), S256Field::new(pair[1].clone()));
+            let (fa, fb) = (FieldElement::<Secp256k1Prime>::new(pair[0].clone()), FieldElement::<Secp256k1Prime>::new(pair[1].clone()));
+            assert_eq!((&a + &b).num(), *(&fa + &fb).num());
+            assert_eq!((&a - &b).num(), *(&fa - &fb).num());
+            assert_eq!((&a * &b).num(), *(&fa * &fb).num());
+            assert_eq!((-&a).num(), *(-&fa).num());
+            match &a / &b {
+                Ok(quotient) => assert_eq!(quotient.num(), *(&fa / &fb).unwrap().num()),
+                Err(err) => assert_eq!(Err(err), &fa / &fb),
+            }
+            assert_eq!(a.pow(&BigUint::from(12345u32)).num(), *fa.pow(&BigInt::from(12345)).num());
+        }
+    }
+
+    #[test]
+    fn test_generic_point_over_s256field() {
+        let curve = Arc::new(EllipticCurve::new(S256Field::ZERO, S256Field::from_u64(7)));
+        let g = S256Point::generator();
+        let (x, y) = g.xy().unwrap();
+        let generic = Point::from(Arc::clone(&curve), *x, *y).unwrap();
+        let scalar = BigUint::from(0xdeadbeefu32);
+        let expected = &g * &scalar;
+        let (ex, ey) = expected.xy().unwrap();
+        assert_eq!(&generic * scalar, Point::from(Arc::clone(&curve), *ex, *ey).unwrap());
+        assert_eq!(generic.to_compressed(), g.compressed_sec());
+        assert_eq!(Point::from_compressed(curve, &g.compressed_sec()).unwrap(), generic);
+    }
+
+    #[test]
+    fn test_sqrt() {
+        let two = S256Field::from_u64(2);
+        let root = two.sqrt().unwrap();
+        assert_eq!(root.square(), two);
+        // -1 is not a square when P = 3 mod 4
+        assert_eq!((-&S256Field::ONE).sqrt(), None);
+    }
+
+    #[test]
+    fn test_s256_point_generator() {
+        let g = S256Point::generator();
+        assert!((&g * &N_S256).is_infinity());
+        assert!(Jacobian::from_affine(&g).mul(&N_S256).to_affine().is_infinity());
+        assert_eq!(&g * &(&*N_S256 + 5u32), &g * &BigUint::from(5u32));
+    }
+
+    #[test]
+    fn test_add_and_mul() {
+        let g = S256Point::generator();
+        let two_g = &g + &g;
+        let three_g = &two_g + &g;
+        assert_eq!(&g * &BigUint::from(2u32), two_g);
+        assert_eq!(&g * &BigUint::from(3u32), three_g);
+        assert_eq!(&(&g * &(&*N_S256 - 1u32)) + &g, S256Point::infinity());
+        assert_eq!(&S256Point::infinity() + &g, g);
+    }
+
+    #[test]
+    fn test_projective_formulas() {
+        let g = S256Point::generator();
+        let p = Projective::from_affine(&g);
+        let identity = Projective::IDENTITY;
+        assert_eq!(p.add(&identity).to_affine(), g);
+        assert_eq!(identity.add(&p).to_affine(), g);
+        assert!(identity.double().to_affine().is_infinity());
+        assert_eq!(p.add(&p).to_affine(), &g + &g);
+        assert_eq!(p.double().to_affine(), &g + &g);
+        let minus_g = S256Point { xy: Some((*g.xy().unwrap().0, -g.xy().unwrap().1)) };
+        assert!(p.add(&Projective::from_affine(&minus_g)).to_affine().is_infinity());
+    }
+
+    #[test]
+    fn test_mul_ct() {
+        let mut rng = rand::thread_rng();
+        let g = S256Point::generator();
+        let point = &g * &rng.gen_biguint_below(&N_S256);
+        let mut scalars: Vec<BigUint> = (0..10).map(|_| rng.gen_biguint_below(&N_S256)).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), BigUint::from(16u32), &*N_S256 - 1u32, N_S256.clone(), (BigUint::from(1u32) << 256u32) - 1u32]);
+        for scalar in &scalars {
+            assert_eq!(g.mul_ct(scalar), &g * scalar);
+            assert_eq!(point.mul_ct(scalar), &point * scalar);
+        }
+        assert!(S256Point::infinity().mul_ct(&scalars[0]).is_infinity());
+    }
+
+    #[test]
+    fn test_mul_generator() {
+        let mut rng = rand::thread_rng();
+        let g = S256Point::generator();
+        let mut scalars: Vec<BigUint> = (0..10).map(|_| rng.gen_biguint_below(&N_S256)).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), BigUint::from(15u32), BigUint::from(16u32), &*N_S256 - 1u32, N_S256.clone()]);
+        for scalar in &scalars {
+            assert_eq!(S256Point::mul_generator(scalar), &g * scalar);
+        }
+    }
+
+    #[test]
+    fn test_multi_mul() {
+        let mut rng = rand::thread_rng();
+        let g = S256Point::generator();
+        for count in [0, 1, 2, 3, 40] {
+            let points: Vec<S256Point> = (0..count).map(|_| &g * &rng.gen_biguint_below(&N_S256)).collect();
+            let mut scalars: Vec<BigUint> = (0..count).map(|_| rng.gen_biguint(256)).collect();
+            if count > 2 {
+                scalars[1] = BigUint::ZERO;
+                scalars[2] = &*N_S256 - 1u32;
+            }
+            let terms: Vec<(&S256Point, &BigUint)> = points.iter().zip(&scalars).collect();
+            let mut expected = S256Point::infinity();
+            for (point, scalar) in &terms {
+                expected = &expected + &(*point * *scalar);
+            }
+            assert_eq!(S256Point::multi_mul(&terms), expected);
+            let jacobian: Vec<(Jacobian, BigUint)> = terms.iter().map(|(p, k)| (Jacobian::from_affine(p), mod_n(k))).collect();
+            assert_eq!(strauss(&(), &jacobian).to_affine(), expected);
+            assert_eq!(pippenger(&(), &jacobian).to_affine(), expected);
+        }
+    }
+
+    #[test]
+    fn test_lift_x() {
+        let g = S256Point::generator();
+        let lifted = S256Point::lift_x(&g.xonly()).unwrap();
+        assert_eq!(lifted, g);
+        let odd = &g * &BigUint::from(3u32);
+        let lifted = S256Point::lift_x(&odd.xonly()).unwrap();
+        assert_eq!(lifted.xonly(), odd.xonly());
+        assert!(lifted.xy().unwrap().1.is_even());
+        // x = 5 gives 5³ + 7 = 132, which is not a square modulo P
+        let mut five = [0u8; 32];
+        five[31] = 5;
+        assert_eq!(S256Point::lift_x(&five), Err(EncodingError::Curve(CurveError::NotOnCurve)));
+        assert_eq!(S256Point::lift_x(&[0xff; 32]), Err(EncodingError::NotAFieldElement));
+    }
+
+    #[test]
+    fn test_glv_constants() {
+        let g = S256Point::generator();
+        let (x, y) = g.xy().unwrap();
+        let endo = S256Point::new(x * &BETA, *y).unwrap();
+        assert_eq!(Jacobian::from_affine(&g).mul(&LAMBDA).to_affine(), endo);
+        assert_eq!(BETA.pow(&BigUint::from(3u32)), S256Field::ONE);
+        assert_eq!(LAMBDA.modpow(&BigUint::from(3u32), &N_S256), BigUint::from(1u32));
+    }
+
+    #[test]
+    fn test_glv_decompose() {
+        let mut rng = rand::thread_rng();
+        let n = BigInt::from(N_S256.clone());
+        let mut scalars: Vec<BigUint> = (0..20).map(|_| rng.gen_biguint_below(&N_S256)).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), &*N_S256 - 1u32, LAMBDA.clone()]);
+        for scalar in scalars {
+            let (k1, k2) = glv_decompose(&scalar);
+            let total = (&k1 + &k2 * BigInt::from(LAMBDA.clone())) % &n;
+            assert_eq!((total + &n) % &n, BigInt::from(scalar));
+            assert!(k1.bits() <= 129 && k2.bits() <= 129);
+        }
+    }
+
+    #[test]
+    fn test_glv_mul_matches_double_and_add() {
+        let mut rng = rand::thread_rng();
+        let g = S256Point::generator();
+        let point = &g * &rng.gen_biguint_below(&N_S256);
+        let mut scalars: Vec<BigUint> = (0..10).map(|_| rng.gen_biguint(256)).collect();
+        scalars.extend([BigUint::ZERO, BigUint::from(1u32), &*N_S256 - 1u32, N_S256.clone(), LAMBDA.clone()]);
+        for scalar in &scalars {
+            let expected = Jacobian::from_affine(&point).mul(&mod_n(scalar)).to_affine();
+            assert_eq!(&point * scalar, expected);
+            assert_eq!(multi_mul(&[(&point, scalar)]).to_affine(), expected);
+        }
+    }
+
+    #[test]
+    fn test_verify_schnorr_batch() {
+        use crate::cripto::PrivateKey;
+        use crate::scalar::SecretScalar;
+
+        let keys: Vec<PrivateKey> = (0..20).map(|_| PrivateKey::new(SecretScalar::random())).collect();
+        let msgs: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; 32]).collect();
+        let mut sigs: Vec<SchnorrSignature> = keys.iter().zip(&msgs).map(|(key, msg)| key.sign_schnorr(msg, &[0u8; 32])).collect();
+
+        let batch = |sigs: &[SchnorrSignature]| {
+            let items: Vec<(&S256Point, &[u8], &SchnorrSignature)> = keys.iter().zip(&msgs).zip(sigs)
+                .map(|((key, msg), sig)| (key.point(), msg.as_slice(), sig))
+                .collect();
+            verify_schnorr_batch(&items)
+        };
+        assert_eq!(batch(&sigs), Ok(()));
+        assert_eq!(verify_schnorr_batch(&[]), Ok(()));
+
+        // s of one signature bumped, and r of another swapped for an x that isn't on the curve
+        sigs[3] = SchnorrSignature::new(*sigs[3].r(), sigs[3].s() + &Scalar::from_u64(1));
+        sigs[17] = SchnorrSignature::new(S256Field::from_u64(5), sigs[17].s().clone());
+        assert_eq!(batch(&sigs), Err(vec![3, 17]));
+
+        // two signatures swapped between messages
+        sigs[3] = keys[3].sign_schnorr(&msgs[3], &[0u8; 32]);
+        sigs[17] = keys[17].sign_schnorr(&msgs[17], &[0u8; 32]);
+        sigs.swap(5, 6);
+        assert_eq!(batch(&sigs), Err(vec![5, 6]));
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<S256Field>();
+        assert_send_sync::<S256Point>();
+        assert_send_sync::<crate::cripto::PrivateKey>();
+        assert_send_sync::<SchnorrSignature>();
+        assert_send_sync::<Signature>();
+    }
+
+    #[test]
+    fn test_ct_eq() {
+        assert_eq!(ct_eq(5, 5), 1);
+        assert_eq!(ct_eq(5, 6), 0);
+        assert_eq!(ct_eq(0, u64::MAX), 0);
+        assert_eq!(ct_eq(u64::MAX, u64::MAX), 1);
+    }
+
+    #[test]
+    fn test_s256_verify() {
+        let x = BigUint::from_bytes_be(&hex::decode("887387e452b8eacc4acfde10d9aaf7f6d9a0f975aabb10d006e4da568744d06c").unwrap());
+        let y = BigUint::from_bytes_be(&hex::decode("61de6d95231cd89026e286df3b6ae4a894a3378e393e93a0f45b666329a0ae34").unwrap());
+        let point = S256Point::new(S256Field::new(x), S256Field::new(y)).unwrap();
+        let z = BigUint::from_bytes_be(&hex::decode("ec208baa0fc1c19f708a9ca96fdeff3ac3f230bb4a7ba4aede4942ad003c0f60").unwrap());
+        let r = BigUint::from_bytes_be(&hex::decode("ac8d1c87e51d0d441be8b3dd5b05c8795b48875dffe00b7ffcfac23010d3a395").unwrap());
+        let s = BigUint::from_bytes_be(&hex::decode("068342ceff8935ededd102dd876ffd6ba72d6a427a3edb13d26eb0781cb423c4").unwrap());
+        let sig = Signature::try_new(r, s).unwrap();
+        assert!(point.verify(&z, &sig));
+        let z = BigUint::from_bytes_be(&hex::decode("7c076ff316692a3d7eb3c3bb0f8b1488cf72e1afcd929e29307032997a838a3d").unwrap());
+        let r = BigUint::from_bytes_be(&hex::decode("00eff69ef2b1bd93a66ed5219add4fb51e11a840f404876325a1e8ffe0529a2c").unwrap());
+        let s = BigUint::from_bytes_be(&hex::decode("c7207fee197d27c618aea621406f6bf5ef6fca38681d82b2f06fddbdce6feab6").unwrap());
+        let sig = Signature::try_new(r, s).unwrap();
+        assert!(point.verify(&z, &sig));
+        assert!(!point.verify(&(z + 1u32), &sig));
+    }
+
+    #[test]
+    fn test_s256_sec() {
+        let mut uncompressed = "049d5ca49670cbe4c3bfa84c96a8c87df086c6ea6a24ba6b809c9de234496808d56fa15cc7f3d38cda98dee2419f415b7513dde1301f8643cd9245aea7f3f911f9";
+        let mut compressed = "039d5ca49670cbe4c3bfa84c96a8c87df086c6ea6a24ba6b809c9de234496808d5";
+        let g = S256Point::generator();
+        let mut coef = BigUint::from(999u32).pow(3);
+        let mut point = &g * &coef;
+        assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
+        assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
+
+        coef = BigUint::from(123u32);
+        uncompressed = "04a598a8030da6d86c6bc7f2f5144ea549d28211ea58faa70ebf4c1e665c1fe9b5204b5d6f84822c307e4b4a7140737aec23fc63b65b35f86a10026dbd2d864e6b";
+        compressed = "03a598a8030da6d86c6bc7f2f5144ea549d28211ea58faa70ebf4c1e665c1fe9b5";
+        point = &g * &coef;
+        assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
+        assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
+
+        coef = BigUint::from(42424242u32);
+        uncompressed = "04aee2e7d843f7430097859e2bc603abcc3274ff8169c1a469fee0f20614066f8e21ec53f40efac47ac1c5211b2123527e0e9b57ede790c4da1e72c91fb7da54a3";
+        compressed = "03aee2e7d843f7430097859e2bc603abcc3274ff8169c1a469fee0f20614066f8e";
+        point = &g * &coef;
+        assert_eq!(point.uncompressed_sec(), hex::decode(uncompressed).unwrap());
+        assert_eq!(point.compressed_sec(), hex::decode(compressed).unwrap());
+    }
+
+    #[test]
+    fn test_s256_parse() {
+        let g = S256Point::generator();
+        let mut coef = BigUint::from(999u32).pow(3);
+        let mut point = &g * &coef;
+        let mut sec_bin = point.uncompressed_sec();
+        let mut parsed_point = S256Point::parse(&sec_bin).unwrap();
+        assert_eq!(parsed_point, point);
+
+        coef = BigUint::from(123u32);
+        point = &g * &coef;
+        sec_bin = point.compressed_sec();
+        parsed_point = S256Point::parse(&sec_bin).unwrap();
+        assert_eq!(parsed_point, point);
+
+        let even = &g * &BigUint::from(2u32);
+        assert_eq!(S256Point::parse(&even.compressed_sec()).unwrap(), even);
+
+        let mut bad = point.uncompressed_sec();
+        bad[64] ^= 1;
+        assert_eq!(S256Point::parse(&bad), Err(EncodingError::Curve(CurveError::NotOnCurve)));
+        assert_eq!(S256Point::parse(&sec_bin[..32]), Err(EncodingError::InvalidLength { expected: 33, found: 32 }));
+        assert_eq!(S256Point::parse(&[0x05; 33]), Err(EncodingError::InvalidPrefix(0x05)));
+        assert!(S256Point::parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_s256_address() {
+        let g = S256Point::generator();
+        let mut secret = BigUint::from(888u32).pow(3);
+        let mut point = &g * &secret;
+        let mut mainnet_address = "148dY81A9BmdpMhvYEVznrM45kWN32vSCN";
+        let mut testnet_address = "mieaqB68xDCtbUBYFoUNcmZNwk74xcBfTP";
+        assert_eq!(point.address(true, false), mainnet_address);
+        assert_eq!(point.address(true, true), testnet_address);
+
+        secret = BigUint::from(321u32);
+        point = &g * &secret;
+        mainnet_address = "1S6g2xBJSED7Qr9CYZib5f4PYVhHZiVfj";
+        testnet_address = "mfx3y63A7TfTtXKkv7Y6QzsPFY6QCBCXiP";
+        assert_eq!(point.address(false, false), mainnet_address);
+        assert_eq!(point.address(false, true), testnet_address);
+
+        secret = BigUint::from(4242424242u32);
+        point = &g * &secret;
+        mainnet_address = "1226JSptcStqn4Yq9aAmNXdwdc2ixuH9nb";
+        testnet_address = "mgY3bVusRUL6ZB2Ss999CSrGVbdRwVpM8s";
+        assert_eq!(point.address(false, false), mainnet_address);
+        assert_eq!(point.address(false, true), testnet_address);
+    }
+}
@@ -0,0 +1,44 @@
+// Fixtures shared by tests that need mined regtest headers or blocks, or scratch files
+
+use std::path::PathBuf;
+
+use crate::block::{Block, BlockHeader};
+use crate::network::Network;
+use crate::tx::Tx;
+
+// grinds the nonce until the header meets the regtest proof of work limit
+pub fn mine_header(prev: &BlockHeader, merkle_root: [u8; 32], timestamp: u32, bits: u32) -> BlockHeader {
+    let mut nonce = 0;
+    loop {
+        let header = BlockHeader::new(0x20000000, prev.hash(), merkle_root, timestamp, bits, nonce);
+        if header.check_pow(Network::Regtest.pow_limit()) {
+            return header;
+        }
+        nonce += 1;
+    }
+}
+
+// `len` headers on top of `prev`, ten minutes apart, with merkle roots tag, tag + 1, ... so
+// branches with different tags get different hashes
+pub fn mine_branch(prev: &BlockHeader, len: usize, tag: u8) -> Vec<BlockHeader> {
+    let mut headers: Vec<BlockHeader> = Vec::new();
+    for i in 0..len {
+        let parent = headers.last().unwrap_or(prev);
+        let header = mine_header(parent, [tag.wrapping_add(i as u8); 32], parent.timestamp() + 600, 0x207fffff);
+        headers.push(header);
+    }
+    headers
+}
+
+// a regtest block with these transactions on top of `prev`
+pub fn mine_block(prev: &BlockHeader, txs: Vec<Tx>) -> Block {
+    let root = Block::new(prev.clone(), txs.clone()).compute_merkle_root().unwrap();
+    Block::new(mine_header(prev, root, prev.timestamp() + 1, 0x207fffff), txs)
+}
+
+// a path in the temp directory that doesn't exist yet, unique to this test process
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.dat", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path
+}
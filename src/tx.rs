@@ -0,0 +1,288 @@
+use std::io::Read;
+
+use crate::cripto::hash256;
+use crate::helper::{encode_var_bytes, encode_varint, read_bytes, read_var_bytes, read_varint};
+
+pub type Txid = [u8; 32];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxIn {
+    prev_tx: Txid,
+    prev_index: u32,
+    script_sig: Vec<u8>,
+    sequence: u32,
+    witness: Vec<Vec<u8>>,
+}
+
+impl TxIn {
+    pub fn new(prev_tx: Txid, prev_index: u32, script_sig: Vec<u8>, sequence: u32) -> Self {
+        TxIn { prev_tx, prev_index, script_sig, sequence, witness: Vec::new() }
+    }
+
+    pub fn with_witness(mut self, witness: Vec<Vec<u8>>) -> Self {
+        self.witness = witness;
+        self
+    }
+
+    // prev_tx is kept in display order, like block hashes
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let mut prev_tx: Txid = read_bytes(reader)?;
+        prev_tx.reverse();
+        let prev_index = u32::from_le_bytes(read_bytes(reader)?);
+        let script_sig = read_var_bytes(reader)?;
+        let sequence = u32::from_le_bytes(read_bytes(reader)?);
+        Ok(TxIn::new(prev_tx, prev_index, script_sig, sequence))
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result: Vec<u8> = self.prev_tx.iter().rev().copied().collect();
+        result.extend_from_slice(&self.prev_index.to_le_bytes());
+        result.extend(encode_var_bytes(&self.script_sig));
+        result.extend_from_slice(&self.sequence.to_le_bytes());
+        result
+    }
+
+    pub fn prev_tx(&self) -> &Txid {
+        &self.prev_tx
+    }
+
+    pub fn prev_index(&self) -> u32 {
+        self.prev_index
+    }
+
+    pub fn script_sig(&self) -> &[u8] {
+        &self.script_sig
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn witness(&self) -> &[Vec<u8>] {
+        &self.witness
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    amount: u64,
+    script_pubkey: Vec<u8>,
+}
+
+impl TxOut {
+    pub fn new(amount: u64, script_pubkey: Vec<u8>) -> Self {
+        TxOut { amount, script_pubkey }
+    }
+
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let amount = u64::from_le_bytes(read_bytes(reader)?);
+        let script_pubkey = read_var_bytes(reader)?;
+        Ok(TxOut { amount, script_pubkey })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut result = self.amount.to_le_bytes().to_vec();
+        result.extend(encode_var_bytes(&self.script_pubkey));
+        result
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    pub fn script_pubkey(&self) -> &[u8] {
+        &self.script_pubkey
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tx {
+    version: u32,
+    inputs: Vec<TxIn>,
+    outputs: Vec<TxOut>,
+    locktime: u32,
+}
+
+impl Tx {
+    pub fn new(version: u32, inputs: Vec<TxIn>, outputs: Vec<TxOut>, locktime: u32) -> Self {
+        Tx { version, inputs, outputs, locktime }
+    }
+
+    // Handles both the legacy and the BIP 144 serialization, where an empty input list
+    // followed by a 0x01 flag marks a transaction that carries witness data.
+    pub fn parse<R: Read>(reader: &mut R) -> Result<Self, &'static str> {
+        let version = u32::from_le_bytes(read_bytes(reader)?);
+        let mut num_inputs = read_varint(reader)?;
+        let segwit = num_inputs == 0;
+        if segwit {
+            if read_bytes::<R, 1>(reader)?[0] != 0x01 {
+                return Err("invalid segwit flag");
+            }
+            num_inputs = read_varint(reader)?;
+        }
+
+        let mut inputs = Vec::new();
+        for _ in 0..num_inputs {
+            inputs.push(TxIn::parse(reader)?);
+        }
+        let num_outputs = read_varint(reader)?;
+        let mut outputs = Vec::new();
+        for _ in 0..num_outputs {
+            outputs.push(TxOut::parse(reader)?);
+        }
+
+        if segwit {
+            for input in inputs.iter_mut() {
+                let num_items = read_varint(reader)?;
+                for _ in 0..num_items {
+                    input.witness.push(read_var_bytes(reader)?);
+                }
+            }
+            if inputs.iter().all(|input| input.witness.is_empty()) {
+                return Err("segwit transaction without witness data");
+            }
+        }
+
+        let locktime = u32::from_le_bytes(read_bytes(reader)?);
+        Ok(Tx { version, inputs, outputs, locktime })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        if !self.has_witness() {
+            return self.serialize_legacy();
+        }
+
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend_from_slice(&[0x00, 0x01]);
+        result.extend(self.serialize_inputs_outputs());
+        for input in &self.inputs {
+            result.extend(encode_varint(input.witness.len() as u64));
+            for item in &input.witness {
+                result.extend(encode_var_bytes(item));
+            }
+        }
+        result.extend_from_slice(&self.locktime.to_le_bytes());
+        result
+    }
+
+    pub fn serialize_legacy(&self) -> Vec<u8> {
+        let mut result = self.version.to_le_bytes().to_vec();
+        result.extend(self.serialize_inputs_outputs());
+        result.extend_from_slice(&self.locktime.to_le_bytes());
+        result
+    }
+
+    fn serialize_inputs_outputs(&self) -> Vec<u8> {
+        let mut result = encode_varint(self.inputs.len() as u64);
+        for input in &self.inputs {
+            result.extend(input.serialize());
+        }
+        result.extend(encode_varint(self.outputs.len() as u64));
+        for output in &self.outputs {
+            result.extend(output.serialize());
+        }
+        result
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    pub fn id(&self) -> Txid {
+        let mut hash: Txid = hash256(&self.serialize_legacy()).try_into().unwrap();
+        hash.reverse();
+        hash
+    }
+
+    pub fn wtxid(&self) -> Txid {
+        let mut hash: Txid = hash256(&self.serialize()).try_into().unwrap();
+        hash.reverse();
+        hash
+    }
+
+    pub fn is_coinbase(&self) -> bool {
+        self.inputs.len() == 1 && self.inputs[0].prev_tx == [0u8; 32] && self.inputs[0].prev_index == 0xffffffff
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn inputs(&self) -> &[TxIn] {
+        &self.inputs
+    }
+
+    pub fn outputs(&self) -> &[TxOut] {
+        &self.outputs
+    }
+
+    pub fn locktime(&self) -> u32 {
+        self.locktime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+    const SEGWIT_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    fn parse_hex(tx: &str) -> Tx {
+        Tx::parse(&mut Cursor::new(hex::decode(tx).unwrap())).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let tx = parse_hex(GENESIS_COINBASE);
+        assert_eq!(tx.version(), 1);
+        assert_eq!(tx.inputs().len(), 1);
+        assert_eq!(tx.inputs()[0].prev_index(), 0xffffffff);
+        assert_eq!(tx.inputs()[0].sequence(), 0xffffffff);
+        assert_eq!(tx.inputs()[0].script_sig().len(), 0x4d);
+        assert_eq!(tx.outputs().len(), 1);
+        assert_eq!(tx.outputs()[0].amount(), 5_000_000_000);
+        assert_eq!(tx.outputs()[0].script_pubkey().len(), 0x43);
+        assert_eq!(tx.locktime(), 0);
+        assert!(tx.is_coinbase());
+        assert!(!tx.has_witness());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let tx = parse_hex(GENESIS_COINBASE);
+        assert_eq!(hex::encode(tx.serialize()), GENESIS_COINBASE);
+    }
+
+    #[test]
+    fn test_id() {
+        let tx = parse_hex(GENESIS_COINBASE);
+        assert_eq!(hex::encode(tx.id()), "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b");
+        assert_eq!(tx.id(), tx.wtxid());
+    }
+
+    #[test]
+    fn test_segwit() {
+        let tx = parse_hex(SEGWIT_TX);
+        assert!(tx.has_witness());
+        assert_eq!(tx.inputs().len(), 2);
+        assert!(tx.inputs()[0].witness().is_empty());
+        assert_eq!(tx.inputs()[1].witness().len(), 2);
+        assert_eq!(tx.outputs().len(), 2);
+        assert_eq!(tx.locktime(), 0x11);
+        assert_eq!(hex::encode(tx.serialize()), SEGWIT_TX);
+        assert_ne!(tx.id(), tx.wtxid());
+
+        let legacy = Tx::parse(&mut Cursor::new(tx.serialize_legacy())).unwrap();
+        assert!(!legacy.has_witness());
+        assert_eq!(legacy.id(), tx.id());
+    }
+
+    #[test]
+    fn test_parse_truncated() {
+        let bytes = hex::decode(&GENESIS_COINBASE[..GENESIS_COINBASE.len() - 8]).unwrap();
+        assert!(Tx::parse(&mut Cursor::new(bytes)).is_err());
+    }
+}